@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::{error::Error, fmt};
+
+use super::instruction::Instruction;
+use super::PROGRAM_OFFSET;
+
+// Assembler for the Cowgod mnemonics documented in cpu.rs, producing a ROM
+// that loads at PROGRAM_OFFSET:
+//
+//   ; comments run to the end of the line
+//   SPEED   EQU 2                 ; constant
+//   start:  LD   V0, SPEED        ; label, then an instruction
+//           LD   I, ball
+//           DRW  V0, V1, 3
+//           JP   start
+//   ball:   DB   "..XX....", %01111000
+//           DB   #30
+//   table:  DW   start, ball + 1
+//
+// Numbers are decimal, hex (`#2EA`, `0x2EA`, `$2EA`) or binary (`%0110`,
+// `0b0110`), and operands can add and subtract them, labels and constants.
+// Strings in DB are sprite rows: eight characters per byte, where `X`, `#`,
+// `*` and `1` are set pixels and anything else is clear. Register names (V0
+// to VF, I, DT, ST, K, F, HF, B, R) and mnemonics are case-insensitive;
+// labels and constants are not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    // 1-based source line
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+// a source line with its label(s) and comment stripped
+struct Statement<'a> {
+    line: usize,
+    addr: u16,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+enum Symbol<'a> {
+    Label(u16),
+    // defined with EQU; evaluated when used, so it may refer to labels
+    // further down
+    Constant(&'a str, usize),
+}
+
+enum Operand<'a> {
+    V(u8),
+    Range(u8, u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(&'a str),
+    Value(&'a str),
+}
+
+// Assembles `source` into a ROM image.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler {
+        symbols: HashMap::new(),
+    };
+    let statements = assembler.layout(source)?;
+    let mut rom = Vec::new();
+    for statement in &statements {
+        assembler.emit(statement, &mut rom)?;
+    }
+    Ok(rom)
+}
+
+struct Assembler<'a> {
+    symbols: HashMap<&'a str, Symbol<'a>>,
+}
+
+impl<'a> Assembler<'a> {
+    // First pass: splits the source into statements, gives each its address
+    // and records labels and constants.
+    fn layout(&mut self, source: &'a str) -> Result<Vec<Statement<'a>>, AsmError> {
+        let mut statements = Vec::new();
+        let mut addr = PROGRAM_OFFSET as usize;
+        for (idx, text) in source.lines().enumerate() {
+            let line = idx + 1;
+            let err = |message: String| AsmError { line, message };
+            let mut rest = strip_comment(text).trim();
+
+            // NAME EQU value
+            let mut words = rest.split_whitespace();
+            if let (Some(name), Some(equ)) = (words.next(), words.next()) {
+                if equ.eq_ignore_ascii_case("equ") {
+                    let value = rest[name.len()..].trim_start()[equ.len()..].trim();
+                    self.define(name, Symbol::Constant(value, line))
+                        .map_err(err)?;
+                    continue;
+                }
+            }
+
+            // leading labels
+            while let Some((label, after)) = rest.split_once(':') {
+                if !is_identifier(label.trim()) || after.starts_with(':') {
+                    break;
+                }
+                self.define(label.trim(), Symbol::Label(addr as u16))
+                    .map_err(err)?;
+                rest = after.trim_start();
+            }
+            if rest.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+                Some((mnemonic, operands)) => (mnemonic, split_operands(operands)),
+                None => (rest, Vec::new()),
+            };
+            let statement = Statement {
+                line,
+                addr: addr as u16,
+                mnemonic: mnemonic.to_ascii_uppercase(),
+                operands,
+            };
+            addr += statement_size(&statement).map_err(err)?;
+            if addr > 0x10000 {
+                return Err(err("program does not fit in memory".to_string()));
+            }
+            statements.push(statement);
+        }
+        Ok(statements)
+    }
+
+    fn define(&mut self, name: &'a str, symbol: Symbol<'a>) -> Result<(), String> {
+        if !is_identifier(name) || !matches!(operand(name), Operand::Value(_)) {
+            return Err(format!("`{}` can't be used as a name", name));
+        }
+        if self.symbols.insert(name, symbol).is_some() {
+            return Err(format!("`{}` is defined twice", name));
+        }
+        Ok(())
+    }
+
+    // Second pass: encodes one statement.
+    fn emit(&self, statement: &Statement, rom: &mut Vec<u8>) -> Result<(), AsmError> {
+        let err = |message: String| AsmError {
+            line: statement.line,
+            message,
+        };
+        debug_assert_eq!(rom.len() + PROGRAM_OFFSET as usize, statement.addr as usize);
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for operand in &statement.operands {
+                    match operand.strip_prefix('"') {
+                        Some(pixels) => rom.extend(sprite_row(pixels).map_err(err)?),
+                        None => rom.push(self.byte(operand).map_err(err)?),
+                    }
+                }
+            }
+            "DW" => {
+                for operand in &statement.operands {
+                    let value = self.value(operand, 0).map_err(err)?;
+                    let word = checked(value, -0x8000, 0xFFFF, "word").map_err(err)?;
+                    rom.extend((word as u16).to_be_bytes());
+                }
+            }
+            _ => rom.extend(self.instruction(statement).map_err(err)?.encode()),
+        }
+        Ok(())
+    }
+
+    fn instruction(&self, statement: &Statement) -> Result<Instruction, String> {
+        use Operand::*;
+
+        let operands: Vec<Operand> = statement.operands.iter().map(|o| operand(o)).collect();
+        let instr = match (statement.mnemonic.as_str(), operands.as_slice()) {
+            ("CLS", []) => Instruction::Cls,
+            ("RET", []) => Instruction::Ret,
+            ("SCD", [Value(n)]) => Instruction::ScrollDown(self.nibble(n)?),
+            ("SCU", [Value(n)]) => Instruction::ScrollUp(self.nibble(n)?),
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::Low,
+            ("HIGH", []) => Instruction::High,
+            ("SYS", [Value(nnn)]) => Instruction::Sys(self.addr(nnn)?),
+            ("JP", [Value(nnn)]) => Instruction::Jp(self.addr(nnn)?),
+            ("JP", [V(0), Value(nnn)]) => {
+                let nnn = self.addr(nnn)?;
+                Instruction::JpOffset {
+                    x: (nnn >> 8) as u8,
+                    nnn,
+                }
+            }
+            ("CALL", [Value(nnn)]) => Instruction::Call(self.addr(nnn)?),
+            ("SE", [V(x), V(y)]) => Instruction::SeReg { x: *x, y: *y },
+            ("SE", [V(x), Value(kk)]) => Instruction::SeByte {
+                x: *x,
+                kk: self.byte(kk)?,
+            },
+            ("SNE", [V(x), V(y)]) => Instruction::SneReg { x: *x, y: *y },
+            ("SNE", [V(x), Value(kk)]) => Instruction::SneByte {
+                x: *x,
+                kk: self.byte(kk)?,
+            },
+            ("LD", [V(x), V(y)]) => Instruction::LdReg { x: *x, y: *y },
+            ("LD", [V(x), Dt]) => Instruction::LdVxDt(*x),
+            ("LD", [V(x), K]) => Instruction::LdVxK(*x),
+            ("LD", [V(x), IndirectI]) => Instruction::LdVxI(*x),
+            ("LD", [V(x), R]) => Instruction::LdVxR(*x),
+            ("LD", [V(x), Value(kk)]) => Instruction::LdByte {
+                x: *x,
+                kk: self.byte(kk)?,
+            },
+            ("LD", [Range(x, y), IndirectI]) => Instruction::LoadRange { x: *x, y: *y },
+            ("LD", [I, Long(nnnn)]) => {
+                let nnnn = self.value(nnnn, 0)?;
+                Instruction::LdILong(checked(nnnn, 0, 0xFFFF, "address")? as u16)
+            }
+            ("LD", [I, Value(nnn)]) => Instruction::LdI(self.addr(nnn)?),
+            ("LD", [Dt, V(x)]) => Instruction::LdDtVx(*x),
+            ("LD", [St, V(x)]) => Instruction::LdStVx(*x),
+            ("LD", [F, V(x)]) => Instruction::LdF(*x),
+            ("LD", [Hf, V(x)]) => Instruction::LdHf(*x),
+            ("LD", [B, V(x)]) => Instruction::LdB(*x),
+            ("LD", [IndirectI, V(x)]) => Instruction::LdIVx(*x),
+            ("LD", [IndirectI, Range(x, y)]) => Instruction::SaveRange { x: *x, y: *y },
+            ("LD", [R, V(x)]) => Instruction::LdRVx(*x),
+            ("ADD", [V(x), V(y)]) => Instruction::AddReg { x: *x, y: *y },
+            ("ADD", [V(x), Value(kk)]) => Instruction::AddByte {
+                x: *x,
+                kk: self.byte(kk)?,
+            },
+            ("ADD", [I, V(x)]) => Instruction::AddI(*x),
+            ("OR", [V(x), V(y)]) => Instruction::Or { x: *x, y: *y },
+            ("AND", [V(x), V(y)]) => Instruction::And { x: *x, y: *y },
+            ("XOR", [V(x), V(y)]) => Instruction::Xor { x: *x, y: *y },
+            ("SUB", [V(x), V(y)]) => Instruction::Sub { x: *x, y: *y },
+            ("SUBN", [V(x), V(y)]) => Instruction::Subn { x: *x, y: *y },
+            // with Vy left out, shifting Vx behaves the same under every quirk
+            ("SHR", [V(x)]) => Instruction::Shr { x: *x, y: *x },
+            ("SHR", [V(x), V(y)]) => Instruction::Shr { x: *x, y: *y },
+            ("SHL", [V(x)]) => Instruction::Shl { x: *x, y: *x },
+            ("SHL", [V(x), V(y)]) => Instruction::Shl { x: *x, y: *y },
+            ("RND", [V(x), Value(kk)]) => Instruction::Rnd {
+                x: *x,
+                kk: self.byte(kk)?,
+            },
+            ("DRW", [V(x), V(y), Value(n)]) => Instruction::Drw {
+                x: *x,
+                y: *y,
+                n: self.nibble(n)?,
+            },
+            ("SKP", [V(x)]) => Instruction::Skp(*x),
+            ("SKNP", [V(x)]) => Instruction::Sknp(*x),
+            ("PLANE", [Value(n)]) => Instruction::Plane(self.nibble(n)?),
+            ("AUDIO", []) => Instruction::Audio,
+            ("PITCH", [V(x)]) => Instruction::Pitch(*x),
+            (mnemonic, _) if MNEMONICS.contains(&mnemonic) => {
+                return Err(format!("bad operands for {}", mnemonic))
+            }
+            (mnemonic, _) => return Err(format!("unknown instruction `{}`", mnemonic)),
+        };
+        Ok(instr)
+    }
+
+    fn addr(&self, text: &str) -> Result<u16, String> {
+        Ok(checked(self.value(text, 0)?, 0, 0xFFF, "address")? as u16)
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, String> {
+        Ok(checked(self.value(text, 0)?, -0x80, 0xFF, "byte")? as u8)
+    }
+
+    fn nibble(&self, text: &str) -> Result<u8, String> {
+        Ok(checked(self.value(text, 0)?, 0, 0xF, "nibble")? as u8)
+    }
+
+    // Evaluates `term (+|- term)*`, where terms are numbers and symbols.
+    fn value(&self, text: &str, depth: usize) -> Result<i64, String> {
+        if depth > 32 {
+            return Err("constants refer to each other in a loop".to_string());
+        }
+        let mut total = 0i64;
+        let mut sign = 1;
+        let mut rest = text.trim();
+        if rest.is_empty() {
+            return Err("missing value".to_string());
+        }
+        loop {
+            if let Some(after) = rest.strip_prefix('-') {
+                sign = -sign;
+                rest = after.trim_start();
+                continue;
+            }
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            total = total.wrapping_add(sign * self.term(term, depth)?);
+            rest = &rest[end..];
+            match rest.chars().next() {
+                None => return Ok(total),
+                Some(op) => {
+                    sign = if op == '-' { -1 } else { 1 };
+                    rest = rest[1..].trim_start();
+                }
+            }
+        }
+    }
+
+    fn term(&self, term: &str, depth: usize) -> Result<i64, String> {
+        if let Some(value) = parse_number(term) {
+            return Ok(value);
+        }
+        match self.symbols.get(term) {
+            Some(Symbol::Label(addr)) => Ok(*addr as i64),
+            Some(Symbol::Constant(text, line)) => self
+                .value(text, depth + 1)
+                .map_err(|e| format!("{} (in the constant `{}` on line {})", e, term, line)),
+            None if term.is_empty() => Err("missing value".to_string()),
+            None => Err(format!("`{}` is not defined", term)),
+        }
+    }
+}
+
+// every mnemonic the assembler knows, for telling bad operands from typos
+const MNEMONICS: [&str; 30] = [
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SYS", "JP", "CALL", "SE",
+    "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND", "DRW", "SKP",
+    "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+// bytes a statement will take, known before labels are resolved
+fn statement_size(statement: &Statement) -> Result<usize, String> {
+    match statement.mnemonic.as_str() {
+        "DB" => statement
+            .operands
+            .iter()
+            .map(|operand| match operand.strip_prefix('"') {
+                Some(pixels) => sprite_row(pixels).map(|bytes| bytes.len()),
+                None => Ok(1),
+            })
+            .sum(),
+        "DW" => Ok(2 * statement.operands.len()),
+        "LD" if matches!(
+            statement.operands.get(1).map(|o| operand(o)),
+            Some(Operand::Long(_))
+        ) =>
+        {
+            Ok(4)
+        }
+        _ => Ok(2),
+    }
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    let upper = text.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => return Operand::I,
+        "[I]" => return Operand::IndirectI,
+        "DT" => return Operand::Dt,
+        "ST" => return Operand::St,
+        "K" => return Operand::K,
+        "F" => return Operand::F,
+        "HF" => return Operand::Hf,
+        "B" => return Operand::B,
+        "R" => return Operand::R,
+        _ => (),
+    }
+    if let Some(reg) = parse_register(text) {
+        return Operand::V(reg);
+    }
+    if let Some((x, y)) = text.split_once('-') {
+        if let (Some(x), Some(y)) = (parse_register(x.trim()), parse_register(y.trim())) {
+            return Operand::Range(x, y);
+        }
+    }
+    if upper.starts_with("LONG ") {
+        return Operand::Long(text[5..].trim());
+    }
+    Operand::Value(text)
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (digits, radix) = if let Some(hex) = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix('$'))
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if let Some(bin) = text
+        .strip_prefix('%')
+        .or_else(|| text.strip_prefix("0b"))
+        .or_else(|| text.strip_prefix("0B"))
+    {
+        (bin, 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        (text, 10)
+    } else {
+        return None;
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+fn checked(value: i64, min: i64, max: i64, what: &str) -> Result<i64, String> {
+    if value < min || value > max {
+        return Err(format!("{} does not fit in a {}", value, what));
+    }
+    Ok(value & max)
+}
+
+// `..XX..XX"` (the opening quote already stripped) as bytes
+fn sprite_row(pixels: &str) -> Result<Vec<u8>, String> {
+    let pixels = pixels
+        .strip_suffix('"')
+        .ok_or_else(|| "unterminated sprite string".to_string())?;
+    if pixels.is_empty() || pixels.len() % 8 != 0 {
+        return Err(format!(
+            "sprite rows are multiples of 8 pixels, `{}` has {}",
+            pixels,
+            pixels.len()
+        ));
+    }
+    Ok(pixels
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            chunk.iter().fold(0u8, |byte, pixel| {
+                (byte << 1) | matches!(pixel, b'X' | b'x' | b'#' | b'*' | b'1') as u8
+            })
+        })
+        .collect())
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// Drops a `;` comment, unless the `;` is inside a sprite string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..idx],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                operands.push(text[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    operands.push(text[start..].trim());
+    operands
+}
@@ -0,0 +1,223 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use super::backend::AudioSink;
+use super::FRAME_RATE;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+pub const DEFAULT_PITCH: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+// XO-CHIP pitch register value that plays a pattern at 4000 bits per second
+pub const DEFAULT_PATTERN_PITCH: u8 = 64;
+
+// What the machine asks the buzzer to do for the current frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoundState {
+    // the buzzer sounds while the sound timer is non-zero
+    pub timer: u8,
+    // XO-CHIP 128-bit waveform loaded by F002; without one a plain square
+    // wave is played
+    pub pattern: Option<[u8; 16]>,
+    // XO-CHIP pitch register set by Fx3A, the playback rate of `pattern`
+    pub pitch: u8,
+}
+
+impl SoundState {
+    pub fn new(timer: u8) -> SoundState {
+        SoundState {
+            timer,
+            pattern: None,
+            pitch: DEFAULT_PATTERN_PITCH,
+        }
+    }
+
+    // bits per second at which the pattern is played back
+    pub fn pattern_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+// Tone generator for the CHIP-8 buzzer. It turns the sound timer (and, for
+// XO-CHIP, the audio pattern) into PCM samples, one frame at a time, for
+// whichever backend plays or records them.
+pub struct Beeper {
+    sample_rate: u32,
+    // tone frequency in Hz
+    pitch: f32,
+    // 0.0 (silent) to 1.0 (full scale)
+    volume: f32,
+    // position within the current wave period, 0.0 to 1.0
+    phase: f32,
+    // position within the XO-CHIP pattern, in bits
+    pattern_pos: f32,
+    // samples owed to the current frame, in 1/FRAME_RATE units, so sample
+    // rates that are not a multiple of the frame rate still average out
+    sample_debt: u32,
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Beeper {
+        Beeper {
+            sample_rate,
+            pitch: DEFAULT_PITCH,
+            volume: DEFAULT_VOLUME,
+            phase: 0.0,
+            pattern_pos: 0.0,
+            sample_debt: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch;
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    // Appends one frame (1/60 s) worth of samples to `out`: a square wave (or
+    // the XO-CHIP pattern) while the sound timer is running, silence
+    // otherwise.
+    pub fn render_frame(&mut self, sound: &SoundState, out: &mut Vec<i16>) {
+        self.sample_debt += self.sample_rate;
+        let count = self.sample_debt / FRAME_RATE;
+        self.sample_debt %= FRAME_RATE;
+
+        let amplitude = (self.volume * i16::MAX as f32) as i16;
+        if sound.timer == 0 {
+            out.extend(std::iter::repeat_n(0, count as usize));
+            return;
+        }
+
+        match sound.pattern {
+            Some(pattern) => {
+                let step = sound.pattern_rate() / self.sample_rate as f32;
+                for _ in 0..count {
+                    let bit = self.pattern_pos as usize;
+                    let high = (pattern[bit / 8] >> (7 - bit % 8)) & 1 == 1;
+                    out.push(if high { amplitude } else { -amplitude });
+                    self.pattern_pos = (self.pattern_pos + step) % 128.0;
+                }
+            }
+            None => {
+                let step = self.pitch / self.sample_rate as f32;
+                for _ in 0..count {
+                    out.push(if self.phase < 0.5 {
+                        amplitude
+                    } else {
+                        -amplitude
+                    });
+                    self.phase = (self.phase + step).fract();
+                }
+            }
+        }
+    }
+}
+
+// Records the buzzer to a 16-bit mono WAV file, so audio can be checked
+// without a sound card. The header is patched with the final sizes when the
+// writer is finished or dropped.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    beeper: Beeper,
+    samples: Vec<i16>,
+    // bytes of samples written in full, what the header says
+    data_len: u32,
+    // the write that failed; recording stops there
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, beeper: Beeper) -> io::Result<WavWriter<W>> {
+        write_wav_header(&mut out, beeper.sample_rate(), 0)?;
+        Ok(WavWriter {
+            out,
+            beeper,
+            samples: Vec::new(),
+            data_len: 0,
+            error: None,
+        })
+    }
+
+    // Patches the header with the sizes so far. Fails with the error that
+    // stopped the recording, if one did.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let end = HEADER_LEN + self.data_len as u64;
+        self.out.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.out, self.beeper.sample_rate(), self.data_len)?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavWriter<W> {
+    fn update(&mut self, sound: &SoundState) {
+        if self.error.is_some() {
+            return;
+        }
+        self.samples.clear();
+        self.beeper.render_frame(sound, &mut self.samples);
+
+        let mut bytes = Vec::with_capacity(self.samples.len() * 2);
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        // a full disk is not worth stopping emulation for, but a frame that
+        // was only partly written is left out of the header and the
+        // recording ends there
+        match self.out.write_all(&bytes) {
+            Ok(()) => self.data_len += bytes.len() as u32,
+            Err(e) => {
+                let _ = self
+                    .out
+                    .seek(SeekFrom::Start(HEADER_LEN + self.data_len as u64));
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+// RIFF, fmt and data chunk headers
+const HEADER_LEN: u64 = 44;
+
+fn write_wav_header<W: Write>(out: &mut W, sample_rate: u32, data_len: u32) -> io::Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let byte_rate = sample_rate * block_align as u32;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&channels.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&byte_rate.to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&bits_per_sample.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())
+}
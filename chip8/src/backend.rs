@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use super::audio::SoundState;
+use super::display::Display;
+
+// Host devices plugged into a `Chip8`. The core never talks to a window, a
+// terminal or a sound card directly, so any of them can be swapped (or left
+// out entirely) without touching the interpreter.
+
+// Receives the framebuffer whenever it has changed.
+pub trait VideoSink {
+    fn present(&mut self, display: &Display);
+}
+
+// Produces the state of the 16-key hex keypad, indexed by key value.
+pub trait InputSource {
+    fn poll(&mut self) -> [bool; 16];
+}
+
+// Driven once per frame by the sound timer: the buzzer sounds while
+// `sound.timer > 0`.
+pub trait AudioSink {
+    fn update(&mut self, sound: &SoundState);
+}
+
+// Backends that do nothing, used when no host device is attached.
+pub struct NullVideo;
+pub struct NullInput;
+pub struct NullAudio;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _display: &Display) {}
+}
+
+impl InputSource for NullInput {
+    fn poll(&mut self) -> [bool; 16] {
+        [false; 16]
+    }
+}
+
+impl AudioSink for NullAudio {
+    fn update(&mut self, _sound: &SoundState) {}
+}
+
+// A change to the keypad, for `ScriptedInput`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEvent {
+    Press(u8),
+    Release(u8),
+}
+
+// Replays key events at set frames, so headless runs and tests can play a
+// ROM without a keyboard. `poll` runs once at the start of every frame; the
+// events for frame N (counting from 0) are what that frame's instructions see.
+pub struct ScriptedInput {
+    // (frame, event), in frame order
+    script: Vec<(u64, KeyEvent)>,
+    next: usize,
+    frame: u64,
+    keys: [bool; 16],
+}
+
+impl ScriptedInput {
+    pub fn new(mut script: Vec<(u64, KeyEvent)>) -> ScriptedInput {
+        script.sort_by_key(|(frame, _)| *frame);
+        ScriptedInput {
+            script,
+            next: 0,
+            frame: 0,
+            keys: [false; 16],
+        }
+    }
+
+    // Reads a script written as `FRAME+KEY` (press) and `FRAME-KEY`
+    // (release), with the key in hex, separated by spaces, commas or new
+    // lines; `#` starts a comment. `30+5 40-5` holds key 5 from frame 30 up
+    // to frame 40.
+    pub fn parse_script(text: &str) -> Result<Vec<(u64, KeyEvent)>, String> {
+        let mut script = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            for token in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if token.is_empty() {
+                    continue;
+                }
+                let bad = || format!("bad key event `{}` (expected e.g. 30+5 or 40-5)", token);
+                let split = token.find(['+', '-']).ok_or_else(bad)?;
+                let frame = token[..split].parse().map_err(|_| bad())?;
+                let key = u8::from_str_radix(&token[split + 1..], 16)
+                    .ok()
+                    .filter(|key| *key < 16)
+                    .ok_or_else(bad)?;
+                let event = match &token[split..=split] {
+                    "+" => KeyEvent::Press(key),
+                    _ => KeyEvent::Release(key),
+                };
+                script.push((frame, event));
+            }
+        }
+        Ok(script)
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> [bool; 16] {
+        while let Some((frame, event)) = self.script.get(self.next) {
+            if *frame > self.frame {
+                break;
+            }
+            match *event {
+                KeyEvent::Press(key) => self.keys[(key & 0xF) as usize] = true,
+                KeyEvent::Release(key) => self.keys[(key & 0xF) as usize] = false,
+            }
+            self.next += 1;
+        }
+        self.frame += 1;
+        self.keys
+    }
+}
+
+// Draws the framebuffer as text, packing two pixel rows into each line with
+// half-block characters. Useful over ssh or when no window can be opened.
+pub struct TerminalVideo<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TerminalVideo<W> {
+    pub fn new(out: W) -> TerminalVideo<W> {
+        TerminalVideo { out }
+    }
+}
+
+impl<W: Write> VideoSink for TerminalVideo<W> {
+    fn present(&mut self, display: &Display) {
+        let rows: Vec<&[u8]> = display.rows().collect();
+        // move the cursor home so frames overwrite each other
+        let mut frame = String::from("\x1B[H");
+        for pair in rows.chunks(2) {
+            for x in 0..pair[0].len() {
+                let top = pair[0][x] != 0;
+                let bottom = pair.len() > 1 && pair[1][x] != 0;
+                frame.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            frame.push('\n');
+        }
+        // a broken terminal is not worth stopping emulation for
+        let _ = self.out.write_all(frame.as_bytes());
+        let _ = self.out.flush();
+    }
+}
@@ -0,0 +1,55 @@
+use chip8::{asm, octo};
+use std::{env, fs, path::Path, process};
+
+const USAGE: &str = "usage: chip8-asm [--octo] SOURCE [-o OUTPUT]";
+
+fn main() {
+    // 1. Read source and options from arguments
+    let mut source_path = None;
+    let mut output_path = None;
+    // Octo source instead of Cowgod mnemonics
+    let mut octo = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--octo" => octo = true,
+            "-o" => output_path = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`\n{}", arg, USAGE)),
+            _ => source_path = Some(arg),
+        }
+    }
+    let source_path = source_path.unwrap_or_else(|| fail(USAGE));
+    // game.asm assembles to game.ch8 unless told otherwise
+    let output_path = output_path.unwrap_or_else(|| {
+        Path::new(&source_path)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    let source = fs::read_to_string(&source_path)
+        .unwrap_or_else(|e| fail(&format!("could not read {}: {}", source_path, e)));
+
+    // 2. Assemble and write the ROM; `.8o` files are Octo
+    let octo = octo
+        || Path::new(&source_path)
+            .extension()
+            .is_some_and(|ext| ext == "8o");
+    let rom = if octo {
+        octo::compile(&source)
+    } else {
+        asm::assemble(&source)
+    }
+    .unwrap_or_else(|e| fail(&format!("{}: {}", source_path, e)));
+    fs::write(&output_path, &rom)
+        .unwrap_or_else(|e| fail(&format!("could not write {}: {}", output_path, e)));
+    eprintln!("wrote {} bytes to {}", rom.len(), output_path);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(2);
+}
@@ -0,0 +1,49 @@
+use chip8::{disasm, instruction::Syntax};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: chip8-disasm [--syntax cowgod|octo] [--source] ROM";
+
+fn main() {
+    // 1. Read ROM and options from arguments
+    let mut rom_path = None;
+    let mut syntax = Syntax::Cowgod;
+    // plain source for the assembler instead of an annotated listing
+    let mut source = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syntax" => {
+                syntax = match args.next().as_deref() {
+                    Some("cowgod") => Syntax::Cowgod,
+                    Some("octo") => Syntax::Octo,
+                    other => fail(&format!(
+                        "unknown syntax `{}` (expected cowgod or octo)",
+                        other.unwrap_or_default()
+                    )),
+                }
+            }
+            "--source" => source = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`\n{}", arg, USAGE)),
+            _ => rom_path = Some(arg),
+        }
+    }
+    let rom_path = rom_path.unwrap_or_else(|| fail(USAGE));
+    let rom = fs::read(&rom_path)
+        .unwrap_or_else(|e| fail(&format!("could not read {}: {}", rom_path, e)));
+
+    // 2. Print the listing
+    if source {
+        print!("{}", disasm::source(&rom, syntax));
+    } else {
+        print!("{}", disasm::listing(&rom, syntax));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(2);
+}
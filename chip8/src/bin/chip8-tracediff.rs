@@ -0,0 +1,79 @@
+use chip8::{
+    debugger, trace,
+    tracediff::{self, Align, Outcome},
+};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: chip8-tracediff [--by-cycle] [--context N] OURS REFERENCE";
+
+fn main() {
+    // 1. Read traces and options from arguments
+    let mut paths = Vec::new();
+    let mut align = Align::Sequence;
+    // records of each trace shown before the divergence
+    let mut context = 8;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--by-cycle" => align = Align::Cycle,
+            "--context" => {
+                context = args
+                    .next()
+                    .as_deref()
+                    .and_then(debugger::parse_number)
+                    .unwrap_or_else(|| fail("`--context` needs a number"))
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`\n{}", arg, USAGE)),
+            _ => paths.push(arg),
+        }
+    }
+    let [ours_path, theirs_path] = &paths[..] else {
+        fail(USAGE)
+    };
+    let (ours, ours_fields) = read(ours_path);
+    let (theirs, theirs_fields) = read(theirs_path);
+
+    // 2. Compare them; like diff, exit 1 when they differ
+    // only what both hold, e.g. no memory writes against another emulator's log
+    match tracediff::diff_only(&ours, &theirs, align, ours_fields.both(theirs_fields)) {
+        Outcome::Same => println!("traces match ({} records)", ours.len()),
+        Outcome::Length {
+            ours: ours_len,
+            theirs: theirs_len,
+        } => {
+            println!(
+                "traces match for {} records, then {} ends ({} records here, {} in the reference)",
+                ours_len.min(theirs_len),
+                if ours_len < theirs_len {
+                    "ours"
+                } else {
+                    "the reference"
+                },
+                ours_len,
+                theirs_len
+            );
+            process::exit(1);
+        }
+        Outcome::Diverged(divergence) => {
+            print!(
+                "{}",
+                tracediff::report(&ours, &theirs, &divergence, context)
+            );
+            process::exit(1);
+        }
+    }
+}
+
+fn read(path: &str) -> (Vec<trace::Record>, trace::Fields) {
+    let data = fs::read(path).unwrap_or_else(|e| fail(&format!("could not read {}: {}", path, e)));
+    trace::read(&data).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(2);
+}
@@ -0,0 +1,1033 @@
+use super::audio::{SoundState, DEFAULT_PATTERN_PITCH};
+use super::display::{Display, PLANES};
+use super::error::Chip8Error;
+use super::instruction::Instruction;
+use super::keyboard::Keyboard;
+use super::memory::{Memory, BIG_FONT_ADDR, FONT_ADDR, MEM_SIZE};
+use super::quirks::Quirks;
+use super::rng::Rng;
+use super::state::{StateError, StateReader, StateWriter};
+use super::unknown::{UnknownOpcode, UnknownOpcodePolicy, UnknownOpcodes};
+use super::PROGRAM_OFFSET;
+
+const OPCODE_SIZE: u16 = 2;
+const F: usize = 15;
+
+#[derive(Clone)]
+pub struct Cpu {
+    // 16 registers, often indexed as Vx
+    regs: [u8; 16],
+    // index register
+    i: u16,
+    // program counter
+    pc: u16,
+    // stack pointer
+    sp: u8,
+    // stack
+    stack: [u16; 16],
+    // delay timer
+    dt: u8,
+    // stack timer
+    st: u8,
+    // SUPER-CHIP RPL user flags, saved and restored by Fx75/Fx85
+    rpl: [u8; 16],
+    // XO-CHIP audio pattern buffer (F002) and pitch register (Fx3A)
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    // set once the ROM has executed 00FD - EXIT
+    halted: bool,
+    // interpreter behaviours the running ROM expects
+    quirks: Quirks,
+    // what to do about opcodes that mean nothing, and the ones seen so far
+    unknown_policy: UnknownOpcodePolicy,
+    unknown: UnknownOpcodes,
+}
+
+enum ProgramCounter {
+    Next,
+    Skip,
+    JumpTo(u16),
+    Wait,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
+}
+
+impl Cpu {
+    pub fn new(quirks: Quirks) -> Cpu {
+        Cpu {
+            regs: [0; 16],
+            i: 0,
+            pc: PROGRAM_OFFSET,
+            sp: 0,
+            stack: [0; 16],
+            dt: 0,
+            st: 0,
+            rpl: [0; 16],
+            audio_pattern: None,
+            pitch: DEFAULT_PATTERN_PITCH,
+            halted: false,
+            quirks,
+            unknown_policy: UnknownOpcodePolicy::default(),
+            unknown: UnknownOpcodes::default(),
+        }
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn set_unknown_opcode_policy(&mut self, policy: UnknownOpcodePolicy) {
+        self.unknown_policy = policy;
+    }
+
+    pub fn unknown_opcodes(&self) -> &UnknownOpcodes {
+        &self.unknown
+    }
+
+    // Unknown opcodes at addresses that had not run one before, to report
+    // under the `Warn` policy; always empty under the others.
+    pub fn take_unknown_warnings(&mut self) -> Vec<UnknownOpcode> {
+        let new = self.unknown.take_new();
+        if self.unknown_policy == UnknownOpcodePolicy::Warn {
+            new
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.regs);
+        w.u16(self.i);
+        w.u16(self.pc);
+        w.u8(self.sp);
+        for addr in self.stack {
+            w.u16(addr);
+        }
+        w.u8(self.dt);
+        w.u8(self.st);
+        w.bytes(&self.rpl);
+        w.bool(self.audio_pattern.is_some());
+        w.bytes(&self.audio_pattern.unwrap_or([0; 16]));
+        w.u8(self.pitch);
+        w.bool(self.halted);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.regs = r.array()?;
+        self.i = r.u16()?;
+        self.pc = r.u16()?;
+        self.sp = r.u8()?;
+        if self.sp as usize > self.stack.len() {
+            return Err(StateError::Corrupt("stack pointer"));
+        }
+        for addr in self.stack.iter_mut() {
+            *addr = r.u16()?;
+        }
+        self.dt = r.u8()?;
+        self.st = r.u8()?;
+        self.rpl = r.array()?;
+        let has_pattern = r.bool()?;
+        let pattern = r.array()?;
+        self.audio_pattern = if has_pattern { Some(pattern) } else { None };
+        self.pitch = r.u8()?;
+        self.halted = r.bool()?;
+        Ok(())
+    }
+
+    pub fn regs(&self) -> &[u8; 16] {
+        &self.regs
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    pub fn stack(&self) -> &[u16; 16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    pub fn rpl(&self) -> &[u8; 16] {
+        &self.rpl
+    }
+
+    pub fn sound(&self) -> SoundState {
+        SoundState {
+            timer: self.st,
+            pattern: self.audio_pattern,
+            pitch: self.pitch,
+        }
+    }
+
+    // Setters for tests and tools that put a machine into a given state
+    // by hand rather than by running a program.
+    pub fn set_reg(&mut self, x: usize, value: u8) {
+        self.regs[x] = value;
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.dt = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.st = value;
+    }
+
+    // Both timers count down at 60 Hz, independently of how many
+    // instructions are executed in between.
+    pub fn tick_timers(&mut self) {
+        if self.dt > 0 {
+            self.dt -= 1;
+        }
+
+        if self.st > 0 {
+            self.st -= 1;
+        }
+    }
+
+    pub fn execute_next_instruction(
+        &mut self,
+        mem: &mut Memory,
+        display: &mut Display,
+        keyboard: &mut Keyboard,
+        rng: &mut Rng,
+    ) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
+        }
+        self.execute(
+            Instruction::fetch(mem, self.pc),
+            mem,
+            display,
+            keyboard,
+            rng,
+        )
+    }
+
+    // Executes `instr` as if it had been fetched from PC, then moves PC on.
+    // Tests use this to run one opcode against a hand-built machine, e.g.
+    // `cpu.execute(Instruction::decode(0x8124, 0), ...)`. A skip still looks
+    // at memory to see how long the skipped instruction is.
+    pub fn execute(
+        &mut self,
+        instr: Instruction,
+        mem: &mut Memory,
+        display: &mut Display,
+        keyboard: &mut Keyboard,
+        rng: &mut Rng,
+    ) -> Result<(), Chip8Error> {
+        // an instruction that would leave PC past the end of memory fails
+        // before it changes anything
+        if !matches!(
+            instr,
+            Instruction::Jp(_)
+                | Instruction::JpOffset { .. }
+                | Instruction::Call(_)
+                | Instruction::Ret
+                | Instruction::Exit
+        ) {
+            offset(self.pc, instr.size() as usize)?;
+        }
+
+        let update = match instr {
+            Instruction::Cls => self.op_cls(display),
+            Instruction::Ret => self.op_ret()?,
+            Instruction::ScrollDown(n) => self.op_scd(n, display),
+            Instruction::ScrollUp(n) => self.op_scu(n, display),
+            Instruction::ScrollRight => self.op_scr(display),
+            Instruction::ScrollLeft => self.op_scl(display),
+            Instruction::Exit => self.op_exit(),
+            Instruction::Low => self.op_low(display),
+            Instruction::High => self.op_high(display),
+            Instruction::Jp(nnn) => self.op_jp_addr(nnn),
+            Instruction::Call(nnn) => self.op_call(nnn)?,
+            Instruction::SeByte { x, kk } => self.op_se_byte(x, kk),
+            Instruction::SneByte { x, kk } => self.op_sne_byte(x, kk),
+            Instruction::SeReg { x, y } => self.op_se_reg(x, y),
+            Instruction::SaveRange { x, y } => self.op_save_range(x, y, mem)?,
+            Instruction::LoadRange { x, y } => self.op_load_range(x, y, mem)?,
+            Instruction::LdByte { x, kk } => self.op_ld_byte(x, kk),
+            Instruction::AddByte { x, kk } => self.op_add_byte(x, kk),
+            Instruction::LdReg { x, y } => self.op_ld_regs(x, y),
+            Instruction::Or { x, y } => self.op_or(x, y),
+            Instruction::And { x, y } => self.op_and(x, y),
+            Instruction::Xor { x, y } => self.op_xor(x, y),
+            Instruction::AddReg { x, y } => self.op_add_regs(x, y),
+            Instruction::Sub { x, y } => self.op_sub(x, y),
+            Instruction::Shr { x, y } => self.op_shr(x, y),
+            Instruction::Subn { x, y } => self.op_subn(x, y),
+            Instruction::Shl { x, y } => self.op_shl(x, y),
+            Instruction::SneReg { x, y } => self.op_sne_regs(x, y),
+            Instruction::LdI(nnn) => self.op_ld_i(nnn),
+            Instruction::JpOffset { x, nnn } => self.op_jp_offset(x, nnn),
+            Instruction::Rnd { x, kk } => self.op_rand(x, kk, rng),
+            Instruction::Drw { x, y, n } => self.op_display_sprite(x, y, n, mem, display)?,
+            Instruction::Skp(x) => self.op_skp(x, keyboard),
+            Instruction::Sknp(x) => self.op_sknp(x, keyboard),
+            Instruction::LdILong(nnnn) => self.op_ld_i_long(nnnn)?,
+            Instruction::Plane(n) => self.op_plane(n, display),
+            Instruction::Audio => self.op_audio(mem)?,
+            Instruction::LdVxDt(x) => self.op_ld_dt(x),
+            Instruction::LdVxK(x) => self.op_ld_store_key(x, keyboard),
+            Instruction::LdDtVx(x) => self.op_ld_vx(x),
+            Instruction::LdStVx(x) => self.op_ld_st(x),
+            Instruction::AddI(x) => self.op_add_i(x),
+            Instruction::LdF(x) => self.op_ld_digit(x),
+            Instruction::LdHf(x) => self.op_ld_big_digit(x),
+            Instruction::LdB(x) => self.op_ld_bcd(x, mem)?,
+            Instruction::Pitch(x) => self.op_pitch(x),
+            Instruction::LdIVx(x) => self.op_str_regs(x, mem)?,
+            Instruction::LdVxI(x) => self.op_ld_all_regs(x, mem)?,
+            Instruction::LdRVx(x) => self.op_str_rpl(x),
+            Instruction::LdVxR(x) => self.op_ld_rpl(x),
+            Instruction::Sys(nnn) => self.op_unknown(nnn)?,
+            Instruction::Unknown(op) => self.op_unknown(op)?,
+        };
+
+        match update {
+            ProgramCounter::Next => self.pc = offset(self.pc, OPCODE_SIZE as usize)?,
+            ProgramCounter::Skip => {
+                // XO-CHIP: skipping `F000 nnnn` has to skip all four bytes
+                let next = offset(self.pc, OPCODE_SIZE as usize)?;
+                let skipped = Instruction::fetch(mem, next).size();
+                self.pc = offset(next, skipped as usize)?
+            }
+            ProgramCounter::JumpTo(addr) => self.pc = addr,
+            ProgramCounter::Wait => (),
+        }
+        Ok(())
+    }
+
+    // 0nnn - SYS addr, and anything else undefined
+    // Jump to a machine code routine at nnn.
+
+    // Only the original interpreters could run the host's machine code, so
+    // this is skipped like every opcode no variant defines, unless the
+    // unknown opcode policy says to halt. Skipped ones are counted; a halt
+    // is reported through the error instead.
+    fn op_unknown(&mut self, op: u16) -> Result<ProgramCounter, Chip8Error> {
+        match self.unknown_policy {
+            UnknownOpcodePolicy::Halt => Err(Chip8Error::InvalidOpcode {
+                pc: self.pc,
+                instr: op,
+            }),
+            UnknownOpcodePolicy::Ignore | UnknownOpcodePolicy::Warn => {
+                self.unknown.record(self.pc, op);
+                Ok(ProgramCounter::Next)
+            }
+        }
+    }
+
+    // 00E0 - CLS
+    // Clear the display.
+    fn op_cls(&self, display: &mut Display) -> ProgramCounter {
+        display.clear();
+        ProgramCounter::Next
+    }
+
+    // ---- OPCODE INTERPRETATION ----
+
+    // 00EE - RET
+    // Return from a subroutine.
+
+    // The interpreter sets the program counter to the address at the top of the
+    // stack, then subtracts 1 from the stack pointer.
+    fn op_ret(&mut self) -> Result<ProgramCounter, Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
+        self.sp -= 1;
+        Ok(ProgramCounter::JumpTo(self.stack[self.sp as usize]))
+    }
+
+    // 00Cn - SCD nibble
+    // Scroll display n lines down. (SUPER-CHIP)
+
+    // The picture moves down by n rows; the rows scrolled in are blank.
+    fn op_scd(&self, n: u8, display: &mut Display) -> ProgramCounter {
+        display.scroll_down(n as usize);
+        ProgramCounter::Next
+    }
+
+    // 00Dn - SCU nibble
+    // Scroll display n lines up. (XO-CHIP)
+
+    // The picture on the selected planes moves up by n rows; the rows scrolled
+    // in are blank.
+    fn op_scu(&self, n: u8, display: &mut Display) -> ProgramCounter {
+        display.scroll_up(n as usize);
+        ProgramCounter::Next
+    }
+
+    // 00FB - SCR
+    // Scroll display 4 pixels right. (SUPER-CHIP)
+    fn op_scr(&self, display: &mut Display) -> ProgramCounter {
+        display.scroll_right(4);
+        ProgramCounter::Next
+    }
+
+    // 00FC - SCL
+    // Scroll display 4 pixels left. (SUPER-CHIP)
+    fn op_scl(&self, display: &mut Display) -> ProgramCounter {
+        display.scroll_left(4);
+        ProgramCounter::Next
+    }
+
+    // 00FD - EXIT
+    // Exit the interpreter. (SUPER-CHIP)
+
+    // The program stops; no further instructions are executed.
+    fn op_exit(&mut self) -> ProgramCounter {
+        self.halted = true;
+        ProgramCounter::Wait
+    }
+
+    // 00FE - LOW
+    // Disable extended screen mode. (SUPER-CHIP)
+
+    // Switches to the 64x32 display, clearing it.
+    fn op_low(&self, display: &mut Display) -> ProgramCounter {
+        display.set_hires(false);
+        ProgramCounter::Next
+    }
+
+    // 00FF - HIGH
+    // Enable extended screen mode for full-screen graphics. (SUPER-CHIP)
+
+    // Switches to the 128x64 display, clearing it.
+    fn op_high(&self, display: &mut Display) -> ProgramCounter {
+        display.set_hires(true);
+        ProgramCounter::Next
+    }
+
+    // 1nnn - JP addr
+    // Jump to location nnn.
+
+    // The interpreter sets the program counter to nnn.
+    fn op_jp_addr(&self, nnn: u16) -> ProgramCounter {
+        ProgramCounter::JumpTo(nnn)
+    }
+
+    // 2nnn - CALL addr
+    // Call subroutine at nnn.
+
+    // The interpreter increments the stack pointer, then puts the current PC on
+    // the top of the stack. The PC is then set to nnn.
+    fn op_call(&mut self, nnn: u16) -> Result<ProgramCounter, Chip8Error> {
+        if self.sp as usize == self.stack.len() {
+            return Err(Chip8Error::StackOverflow);
+        }
+        self.stack[self.sp as usize] = offset(self.pc, OPCODE_SIZE as usize)?;
+        self.sp += 1;
+        Ok(ProgramCounter::JumpTo(nnn))
+    }
+
+    // 3xkk - SE Vx, byte
+    // Skip next instruction if Vx = kk.
+
+    // The interpreter compares register Vx to kk, and if they are equal,
+    // increments the program counter by 2.
+    fn op_se_byte(&self, x: u8, kk: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        if vx == kk {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // 4xkk - SNE Vx, byte
+    // Skip next instruction if Vx != kk.
+
+    // The interpreter compares register Vx to kk, and if they are not equal,
+    // increments the program counter by 2.
+    fn op_sne_byte(&self, x: u8, kk: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        if vx != kk {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // 5xy0 - SE Vx, Vy
+    // Skip next instruction if Vx = Vy.
+
+    // The interpreter compares register Vx to register Vy, and if they are
+    // equal, increments the program counter by 2.
+    fn op_se_reg(&self, x: u8, y: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        let vy = self.regs[y as usize];
+        if vx == vy {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // 5xy2 - LD [I], Vx-Vy
+    // Store registers Vx through Vy in memory starting at location I.
+    // (XO-CHIP)
+
+    // The registers are copied in order from Vx to Vy, which may also count
+    // downwards if x > y. I is not modified.
+    fn op_save_range(&self, x: u8, y: u8, mem: &mut Memory) -> Result<ProgramCounter, Chip8Error> {
+        mem.check_write(self.i, x.abs_diff(y) as usize + 1)?;
+        for (idx, reg) in Cpu::reg_range(x, y).enumerate() {
+            mem.write_byte(offset(self.i, idx)?, self.regs[reg])?;
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    // 5xy3 - LD Vx-Vy, [I]
+    // Read registers Vx through Vy from memory starting at location I.
+    // (XO-CHIP)
+
+    // The registers are loaded in order from Vx to Vy, which may also count
+    // downwards if x > y. I is not modified.
+    fn op_load_range(&mut self, x: u8, y: u8, mem: &Memory) -> Result<ProgramCounter, Chip8Error> {
+        span(self.i, x.abs_diff(y) as usize + 1)?;
+        for (idx, reg) in Cpu::reg_range(x, y).enumerate() {
+            self.regs[reg] = mem.read_byte(offset(self.i, idx)?);
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    fn reg_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    // 6xkk - LD Vx, byte
+    // Set Vx = kk.
+
+    // The interpreter puts the value kk into register Vx.
+    fn op_ld_byte(&mut self, x: u8, kk: u8) -> ProgramCounter {
+        self.regs[x as usize] = kk;
+        ProgramCounter::Next
+    }
+
+    // 7xkk - ADD Vx, byte
+    // Set Vx = Vx + kk.
+
+    // Adds the value kk to the value of register Vx, then stores the result in
+    // Vx.
+    fn op_add_byte(&mut self, x: u8, kk: u8) -> ProgramCounter {
+        let result = (self.regs[x as usize] as u16) + (kk as u16);
+        self.regs[x as usize] = result as u8;
+        ProgramCounter::Next
+    }
+
+    // 8xy0 - LD Vx, Vy
+    // Set Vx = Vy.
+
+    // Stores the value of register Vy in register Vx.
+    fn op_ld_regs(&mut self, x: u8, y: u8) -> ProgramCounter {
+        self.regs[x as usize] = self.regs[y as usize];
+        ProgramCounter::Next
+    }
+
+    // 8xy1 - OR Vx, Vy
+    // Set Vx = Vx OR Vy.
+
+    // Performs a bitwise OR on the values of Vx and Vy, then stores the result
+    // in Vx. A bitwise OR compares the corrseponding bits from two values, and
+    // if either bit is 1, then the same bit in the result is also 1. Otherwise,
+    // it is 0.
+    fn op_or(&mut self, x: u8, y: u8) -> ProgramCounter {
+        self.regs[x as usize] |= self.regs[y as usize];
+        if self.quirks.logic_resets_vf {
+            self.regs[F] = 0;
+        }
+        ProgramCounter::Next
+    }
+
+    // 8xy2 - AND Vx, Vy
+    // Set Vx = Vx AND Vy.
+
+    // Performs a bitwise AND on the values of Vx and Vy, then stores the result
+    // in Vx. A bitwise AND compares the corrseponding bits from two values, and
+    // if both bits are 1, then the same bit in the result is also 1. Otherwise,
+    // it is 0.
+    fn op_and(&mut self, x: u8, y: u8) -> ProgramCounter {
+        self.regs[x as usize] &= self.regs[y as usize];
+        if self.quirks.logic_resets_vf {
+            self.regs[F] = 0;
+        }
+        ProgramCounter::Next
+    }
+
+    // 8xy3 - XOR Vx, Vy
+    // Set Vx = Vx XOR Vy.
+
+    // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores
+    // the result in Vx. An exclusive OR compares the corrseponding bits from
+    // two values, and if the bits are not both the same, then the corresponding
+    // bit in the result is set to 1. Otherwise, it is 0.
+    fn op_xor(&mut self, x: u8, y: u8) -> ProgramCounter {
+        self.regs[x as usize] ^= self.regs[y as usize];
+        if self.quirks.logic_resets_vf {
+            self.regs[F] = 0;
+        }
+        ProgramCounter::Next
+    }
+
+    // 8xy4 - ADD Vx, Vy
+    // Set Vx = Vx + Vy, set VF = carry.
+
+    // The values of Vx and Vy are added together. If the result is greater than
+    // 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits
+    // of the result are kept, and stored in Vx.
+    fn op_add_regs(&mut self, x: u8, y: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize] as u16;
+        let vy = self.regs[y as usize] as u16;
+
+        let sum = vx + vy;
+        self.regs[x as usize] = sum as u8;
+        self.regs[F] = if sum > 255 { 1 } else { 0 };
+        ProgramCounter::Next
+    }
+
+    // 8xy5 - SUB Vx, Vy
+    // Set Vx = Vx - Vy, set VF = NOT borrow.
+
+    // Vy is subtracted from Vx, and the results stored in Vx. If Vx >= Vy, so
+    // nothing was borrowed, VF is then set to 1, otherwise 0. The flag is
+    // written last, so it survives when Vx is VF.
+    fn op_sub(&mut self, x: u8, y: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        let vy = self.regs[y as usize];
+
+        self.regs[x as usize] = vx.wrapping_sub(vy);
+        self.regs[F] = if vx >= vy { 1 } else { 0 };
+        ProgramCounter::Next
+    }
+
+    //8xy6 - SHR Vx {, Vy}
+    // Set Vx = Vx SHR 1.
+
+    // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise
+    // 0. Then Vx is divided by 2. With the `shift_uses_vy` quirk Vy is shifted
+    // instead and the result stored in Vx.
+    fn op_shr(&mut self, x: u8, y: u8) -> ProgramCounter {
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let v = self.regs[src as usize];
+        self.regs[x as usize] = v >> 1;
+        self.regs[F] = v & 1;
+        ProgramCounter::Next
+    }
+
+    // 8xy7 - SUBN Vx, Vy
+    // Set Vx = Vy - Vx, set VF = NOT borrow.
+
+    // Vx is subtracted from Vy, and the results stored in Vx. If Vy >= Vx, so
+    // nothing was borrowed, VF is then set to 1, otherwise 0. The flag is
+    // written last, so it survives when Vx is VF.
+    fn op_subn(&mut self, x: u8, y: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        let vy = self.regs[y as usize];
+
+        self.regs[x as usize] = vy.wrapping_sub(vx);
+        self.regs[F] = if vy >= vx { 1 } else { 0 };
+        ProgramCounter::Next
+    }
+
+    // 8xyE - SHL Vx {, Vy}
+    // Set Vx = Vx SHL 1.
+
+    // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to
+    // 0. Then Vx is multiplied by 2. With the `shift_uses_vy` quirk Vy is
+    // shifted instead and the result stored in Vx.
+    fn op_shl(&mut self, x: u8, y: u8) -> ProgramCounter {
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let v = self.regs[src as usize];
+        self.regs[x as usize] = v << 1;
+        self.regs[F] = v >> 7;
+        ProgramCounter::Next
+    }
+
+    // 9xy0 - SNE Vx, Vy
+    // Skip next instruction if Vx != Vy.
+
+    // The values of Vx and Vy are compared, and if they are not equal, the
+    // program counter is increased by 2.
+    fn op_sne_regs(&self, x: u8, y: u8) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        let vy = self.regs[y as usize];
+        if vx != vy {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // Annn - LD I, addr
+    // Set I = nnn.
+
+    // The value of register I is set to nnn.
+    fn op_ld_i(&mut self, nnn: u16) -> ProgramCounter {
+        self.i = nnn;
+        ProgramCounter::Next
+    }
+
+    // Bnnn - JP V0, addr
+    // Jump to location nnn + V0.
+
+    // The program counter is set to nnn plus the value of V0. With the
+    // `jump_uses_vx` quirk (CHIP-48/SUPER-CHIP) this is Bxnn - JP Vx, addr and
+    // Vx is added instead.
+    fn op_jp_offset(&self, x: u8, nnn: u16) -> ProgramCounter {
+        let offset_reg = if self.quirks.jump_uses_vx { x } else { 0 };
+        ProgramCounter::JumpTo(nnn + self.regs[offset_reg as usize] as u16)
+    }
+
+    // Cxkk - RND Vx, byte
+    // Set Vx = random byte AND kk.
+
+    // The interpreter generates a random number from 0 to 255, which is then
+    // ANDed with the value kk. The results are stored in Vx. See instruction
+    // 8xy2 for more information on AND.
+    fn op_rand(&mut self, x: u8, kk: u8, rng: &mut Rng) -> ProgramCounter {
+        let n = rng.next_u8();
+        self.regs[x as usize] = n & kk;
+        ProgramCounter::Next
+    }
+
+    // Dxyn - DRW Vx, Vy, nibble
+    // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+
+    // The interpreter reads n bytes from memory, starting at the address stored
+    // in I. These bytes are then displayed as sprites on screen at coordinates
+    // (Vx, Vy). Sprites are XORed onto the existing screen. If this causes any
+    // pixels to be erased, VF is set to 1, otherwise it is set to 0. If the
+    // sprite is positioned so part of it is outside the coordinates of the
+    // display, it wraps around to the opposite side of the screen (or, with the
+    // `clip_sprites` quirk, the part outside is not drawn). See instruction
+    // 8xy3 for more information on XOR, and section 2.4, Display, for more
+    // information on the Chip-8 screen and sprites.
+
+    // Dxy0 - DRW Vx, Vy, 0 (SUPER-CHIP)
+    // With n = 0 a 16x16 sprite is drawn instead, read as 32 bytes (two bytes
+    // per row) starting at I.
+    fn op_display_sprite(
+        &mut self,
+        x: u8,
+        y: u8,
+        n: u8,
+        mem: &Memory,
+        display: &mut Display,
+    ) -> Result<ProgramCounter, Chip8Error> {
+        let (rows, cols) = if n == 0 { (16, 16) } else { (n as usize, 8) };
+        let bytes_per_row = cols / 8;
+        let (width, height) = (display.width(), display.height());
+
+        // the starting position always wraps; only the sprite's overhang clips
+        let x0 = self.regs[x as usize] as usize % width;
+        let y0 = self.regs[y as usize] as usize % height;
+        let clip = self.quirks.clip_sprites;
+
+        // XO-CHIP: the sprite is drawn once per selected plane, each plane
+        // reading the next sprite's worth of bytes
+        let planes = display.selected_planes().count_ones() as usize;
+        span(self.i, planes * rows * bytes_per_row)?;
+        let mut addr = self.i as usize;
+        self.regs[F] = 0;
+        for plane in (0..PLANES).map(|p| 1 << p) {
+            if display.selected_planes() & plane == 0 {
+                continue;
+            }
+            for row in 0..rows {
+                let y = y0 + row;
+                if clip && y >= height {
+                    break;
+                }
+                let y = y % height;
+                let mut bits = 0u16;
+                for idx in 0..bytes_per_row {
+                    let byte = addr + row * bytes_per_row + idx;
+                    bits = (bits << 8) | mem.read_byte(byte as u16) as u16;
+                }
+                for col in 0..cols {
+                    let x = x0 + col;
+                    if clip && x >= width {
+                        break;
+                    }
+                    let x = x % width;
+                    if (bits >> (cols - 1 - col)) & 1 == 0 {
+                        continue;
+                    }
+
+                    let curr = display.read_pixel(x, y);
+                    if curr & plane != 0 {
+                        self.regs[F] = 1;
+                    }
+                    display.write(x, y, curr ^ plane);
+                }
+            }
+            addr += rows * bytes_per_row;
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    // Ex9E - SKP Vx
+    // Skip next instruction if key with the value of Vx is pressed.
+
+    // Checks the keyboard, and if the key corresponding to the value of Vx is
+    // currently in the down position, PC is increased by 2.
+    fn op_skp(&self, x: u8, keyboard: &Keyboard) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        if keyboard.query_key(vx as usize) {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // ExA1 - SKNP Vx
+    // Skip next instruction if key with the value of Vx is not pressed.
+
+    // Checks the keyboard, and if the key corresponding to the value of Vx is
+    // currently in the up position, PC is increased by 2.
+    fn op_sknp(&self, x: u8, keyboard: &Keyboard) -> ProgramCounter {
+        let vx = self.regs[x as usize];
+        if !keyboard.query_key(vx as usize) {
+            ProgramCounter::Skip
+        } else {
+            ProgramCounter::Next
+        }
+    }
+
+    // F000 nnnn - LD I, long addr
+    // Set I = nnnn. (XO-CHIP)
+
+    // The 16-bit address in the two bytes following the instruction is loaded
+    // into I, and execution continues after them.
+    fn op_ld_i_long(&mut self, nnnn: u16) -> Result<ProgramCounter, Chip8Error> {
+        self.i = nnnn;
+        Ok(ProgramCounter::JumpTo(offset(
+            self.pc,
+            2 * OPCODE_SIZE as usize,
+        )?))
+    }
+
+    // Fn01 - PLANE n
+    // Select bit-planes n for drawing, clearing and scrolling. (XO-CHIP)
+    fn op_plane(&self, n: u8, display: &mut Display) -> ProgramCounter {
+        display.select_planes(n);
+        ProgramCounter::Next
+    }
+
+    // F002 - AUDIO
+    // Load the 16-byte audio pattern buffer from memory at I. (XO-CHIP)
+    fn op_audio(&mut self, mem: &Memory) -> Result<ProgramCounter, Chip8Error> {
+        let mut pattern = [0; 16];
+        for (idx, byte) in pattern.iter_mut().enumerate() {
+            *byte = mem.read_byte(offset(self.i, idx)?);
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(ProgramCounter::Next)
+    }
+
+    // Fx07 - LD Vx, DT
+    // Set Vx = delay timer value.
+
+    // The value of DT is placed into Vx.
+    fn op_ld_dt(&mut self, x: u8) -> ProgramCounter {
+        self.regs[x as usize] = self.dt;
+        ProgramCounter::Next
+    }
+
+    // Fx0A - LD Vx, K
+    // Wait for a key press, store the value of the key in Vx.
+
+    // All execution stops until a key is pressed and released again, as on
+    // the COSMAC VIP, then the value of that key is stored in Vx. Until then
+    // the instruction runs again every cycle; timers keep counting down.
+    fn op_ld_store_key(&mut self, x: u8, keyboard: &mut Keyboard) -> ProgramCounter {
+        match keyboard.wait_for_key() {
+            Some(key) => {
+                self.regs[x as usize] = key;
+                ProgramCounter::Next
+            }
+            None => ProgramCounter::Wait,
+        }
+    }
+
+    // Fx15 - LD DT, Vx
+    // Set delay timer = Vx.
+
+    // DT is set equal to the value of Vx.
+    fn op_ld_vx(&mut self, x: u8) -> ProgramCounter {
+        self.dt = self.regs[x as usize];
+        ProgramCounter::Next
+    }
+
+    // Fx18 - LD ST, Vx
+    // Set sound timer = Vx.
+
+    // ST is set equal to the value of Vx.
+    fn op_ld_st(&mut self, x: u8) -> ProgramCounter {
+        self.st = self.regs[x as usize];
+        ProgramCounter::Next
+    }
+
+    // Fx1E - ADD I, Vx
+    // Set I = I + Vx.
+
+    // The values of I and Vx are added, and the results are stored in I.
+    fn op_add_i(&mut self, x: u8) -> ProgramCounter {
+        self.i = self.i.wrapping_add(self.regs[x as usize] as u16);
+        ProgramCounter::Next
+    }
+
+    // Fx29 - LD F, Vx
+    // Set I = location of sprite for digit Vx.
+
+    // The value of I is set to the location for the hexadecimal sprite
+    // corresponding to the value of Vx. See section 2.4, Display, for more
+    // information on the Chip-8 hexadecimal font.
+    fn op_ld_digit(&mut self, x: u8) -> ProgramCounter {
+        self.i = FONT_ADDR + (self.regs[x as usize] as u16) * 5;
+        ProgramCounter::Next
+    }
+
+    // Fx30 - LD HF, Vx
+    // Set I = location of 10-byte sprite for digit Vx. (SUPER-CHIP)
+
+    // The value of I is set to the location of the big hexadecimal sprite
+    // corresponding to the value of Vx.
+    fn op_ld_big_digit(&mut self, x: u8) -> ProgramCounter {
+        self.i = BIG_FONT_ADDR + (self.regs[x as usize] as u16 & 0xF) * 10;
+        ProgramCounter::Next
+    }
+
+    // Fx3A - PITCH Vx
+    // Set the audio pattern playback rate to 4000*2^((Vx-64)/48) Hz. (XO-CHIP)
+    fn op_pitch(&mut self, x: u8) -> ProgramCounter {
+        self.pitch = self.regs[x as usize];
+        ProgramCounter::Next
+    }
+
+    // Fx33 - LD B, Vx
+    // Store BCD representation of Vx in memory locations I, I+1, and I+2.
+
+    // The interpreter takes the decimal value of Vx, and places the hundreds
+    // digit in memory at location in I, the tens digit at location I+1, and the
+    // ones digit at location I+2.
+    fn op_ld_bcd(&self, x: u8, mem: &mut Memory) -> Result<ProgramCounter, Chip8Error> {
+        let vx = self.regs[x as usize];
+        mem.check_write(self.i, 3)?;
+        mem.write_byte(self.i, vx / 100)?;
+        mem.write_byte(offset(self.i, 1)?, (vx % 100) / 10)?;
+        mem.write_byte(offset(self.i, 2)?, vx % 10)?;
+
+        Ok(ProgramCounter::Next)
+    }
+
+    // Fx55 - LD [I], Vx
+    // Store registers V0 through Vx in memory starting at location I.
+
+    // The interpreter copies the values of registers V0 through Vx into memory,
+    // starting at the address in I. With the `load_store_increments_i` quirk I
+    // is left pointing past the last byte written.
+    fn op_str_regs(&mut self, x: u8, mem: &mut Memory) -> Result<ProgramCounter, Chip8Error> {
+        mem.check_write(self.i, x as usize + 1)?;
+        for j in 0..((x as usize) + 1) {
+            mem.write_byte(offset(self.i, j)?, self.regs[j])?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    // Fx65 - LD Vx, [I]
+    // Read registers V0 through Vx from memory starting at location I.
+
+    // The interpreter reads values from memory starting at location I into
+    // registers V0 through Vx. With the `load_store_increments_i` quirk I is
+    // left pointing past the last byte read.
+    fn op_ld_all_regs(&mut self, x: u8, mem: &Memory) -> Result<ProgramCounter, Chip8Error> {
+        span(self.i, x as usize + 1)?;
+        for j in 0..((x as usize) + 1) {
+            self.regs[j] = mem.read_byte(offset(self.i, j)?);
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    // Fx75 - LD R, Vx
+    // Store V0..Vx in RPL user flags. (SUPER-CHIP)
+
+    // The interpreter copies registers V0 through Vx into the RPL user flags,
+    // which survive across programs on the HP-48.
+    fn op_str_rpl(&mut self, x: u8) -> ProgramCounter {
+        let count = x as usize + 1;
+        self.rpl[..count].copy_from_slice(&self.regs[..count]);
+        ProgramCounter::Next
+    }
+
+    // Fx85 - LD Vx, R
+    // Read V0..Vx from RPL user flags. (SUPER-CHIP)
+
+    // The interpreter copies the RPL user flags back into registers V0
+    // through Vx.
+    fn op_ld_rpl(&mut self, x: u8) -> ProgramCounter {
+        let count = x as usize + 1;
+        self.regs[..count].copy_from_slice(&self.rpl[..count]);
+        ProgramCounter::Next
+    }
+}
+
+// `base + offset` as an address, if it is still inside memory
+fn offset(base: u16, offset: usize) -> Result<u16, Chip8Error> {
+    let address = base as usize + offset;
+    u16::try_from(address).map_err(|_| Chip8Error::MemoryOutOfBounds { address })
+}
+
+// Checks that all `len` bytes from `base` on are inside memory, so an
+// instruction can fail before it has changed anything. The error names the
+// first address past the end, as a byte-by-byte `offset` would.
+fn span(base: u16, len: usize) -> Result<(), Chip8Error> {
+    if base as usize + len > MEM_SIZE {
+        return Err(Chip8Error::MemoryOutOfBounds { address: MEM_SIZE });
+    }
+    Ok(())
+}
@@ -0,0 +1,603 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::expr::Expr;
+use super::instruction::Instruction;
+use super::memory::AccessKind;
+use super::rewind::{Rewind, DEFAULT_MAX_BYTES};
+use super::Chip8;
+
+// how many instructions `back` can undo
+pub const HISTORY_DEPTH: usize = 10_000;
+
+const HELP: &str = "\
+commands:
+  c, continue             resume execution
+  p, pause                stop execution
+  s, step [n]             execute n instructions (default 1)
+  n, next                 step, running over CALLs as one instruction
+  f, finish               run until the current subroutine returns
+  bs, back [n]            undo the last n instructions (default 1)
+  b, break <addr> [if <expr>]
+                          break at addr (only when expr holds)
+  d, delete <addr>        remove the breakpoint at addr
+  w, watch <addr>[..<end>] [r|w|rw]
+                          break when memory in addr..=end is written
+                          (r: read, rw: either; default w)
+  w, watch <expr>         break when the value of expr changes, e.g. `VF`
+  cond <expr>             break when expr becomes true, e.g. `V3 == 0x10 && I > 0x300`
+  uw, unwatch <n>         remove watch number n
+  bl, breakpoints         list breakpoints and watches
+  r, regs                 show registers, stack and timers
+  x <addr> [len]          dump len bytes of memory (default 16)
+  q, quit                 exit the emulator
+  h, help                 show this list
+an empty line repeats the previous command
+expressions use V0-VF, I, PC, SP, DT, ST, [addr] for memory, numbers and
+  || && | ^ & == != < <= > >= + - !";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    // running until the CALL at `sp` returns to `pc`
+    StepOver { pc: u16, sp: u8 },
+    // running until the stack drops below `sp`
+    Finish { sp: u8 },
+}
+
+enum Watch {
+    // data accesses to start..=end
+    Memory {
+        start: u16,
+        end: u16,
+        reads: bool,
+        writes: bool,
+    },
+    // the value of an expression changes
+    Value {
+        expr: Expr,
+        last: i64,
+    },
+    // an expression goes from false to true
+    Condition {
+        expr: Expr,
+        last: bool,
+    },
+}
+
+impl Watch {
+    fn describe(&self) -> String {
+        match self {
+            Watch::Memory {
+                start,
+                end,
+                reads,
+                writes,
+            } => {
+                let kind = match (reads, writes) {
+                    (true, true) => "access",
+                    (true, false) => "read",
+                    _ => "write",
+                };
+                if start == end {
+                    format!("{} of {:#06X}", kind, start)
+                } else {
+                    format!("{} of {:#06X}..{:#06X}", kind, start, end)
+                }
+            }
+            Watch::Value { expr, .. } => format!("change of {}", expr),
+            Watch::Condition { expr, .. } => format!("{} becomes true", expr),
+        }
+    }
+}
+
+// Interactive debugger driving a `Chip8`. The host feeds it command lines
+// (e.g. from a REPL on stdin) and calls `run_frame` in place of
+// `Chip8::run_frame` once per frame.
+pub struct Debugger {
+    mode: Mode,
+    // PC breakpoints, each with an optional condition
+    breakpoints: BTreeMap<u16, Option<Expr>>,
+    watches: Vec<Watch>,
+    // the next instruction is where execution resumed from, so it must not
+    // stop there again
+    resumed: bool,
+    // per-instruction snapshots for stepping backwards
+    history: Rewind,
+    last_command: String,
+    quit: bool,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    // The debugger starts out paused, before the first instruction.
+    pub fn new() -> Debugger {
+        Debugger {
+            mode: Mode::Paused,
+            breakpoints: BTreeMap::new(),
+            watches: Vec::new(),
+            resumed: false,
+            history: Rewind::new(HISTORY_DEPTH, DEFAULT_MAX_BYTES),
+            last_command: String::new(),
+            quit: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr, None);
+    }
+
+    // Runs one frame unless paused. Returns a report when execution stopped
+    // during the frame (breakpoint or watch hit, CALL stepped over,
+    // subroutine finished, or an instruction failed).
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Option<String> {
+        if self.mode == Mode::Paused {
+            return None;
+        }
+
+        let mut reason = None;
+        let stopped = chip8.run_frame_until(|c| {
+            reason = self.before_instruction(c);
+            if reason.is_none() {
+                self.history.push(c);
+            }
+            reason.is_some()
+        });
+
+        let stopped = match stopped {
+            Ok(stopped) => stopped,
+            Err(e) => {
+                self.mode = Mode::Paused;
+                return Some(format!("error: {}\n{}", e, registers(chip8)));
+            }
+        };
+        if chip8.is_halted() {
+            self.mode = Mode::Paused;
+            return Some(format!("program exited\n{}", registers(chip8)));
+        }
+        if !stopped {
+            return None;
+        }
+        self.mode = Mode::Paused;
+        Some(format!(
+            "{}\n{}",
+            reason.unwrap_or_default(),
+            registers(chip8)
+        ))
+    }
+
+    // Decides whether to stop before the instruction at PC. Watches look at
+    // what the previous instruction did.
+    fn before_instruction(&mut self, chip8: &Chip8) -> Option<String> {
+        if self.resumed {
+            self.resumed = false;
+            self.refresh_watches(chip8);
+            return None;
+        }
+        if let Some(hit) = self.check_watches(chip8) {
+            return Some(hit);
+        }
+
+        let (pc, sp) = (chip8.cpu().pc(), chip8.cpu().sp());
+        match self.mode {
+            Mode::StepOver {
+                pc: ret,
+                sp: call_sp,
+            } if pc == ret && sp == call_sp => {
+                return Some(format!("stepped over call, now at {:#06X}", pc));
+            }
+            Mode::Finish { sp: call_sp } if sp < call_sp => {
+                return Some(format!("returned to {:#06X}", pc));
+            }
+            _ => (),
+        }
+        match self.breakpoints.get(&pc) {
+            Some(None) => Some(format!("breakpoint at {:#06X}", pc)),
+            Some(Some(cond)) if cond.is_true(chip8) => {
+                Some(format!("breakpoint at {:#06X} if {}", pc, cond))
+            }
+            _ => None,
+        }
+    }
+
+    // Reports the first watch the last instruction(s) triggered, and brings
+    // every watch up to date.
+    fn check_watches(&mut self, chip8: &Chip8) -> Option<String> {
+        let accesses = chip8.memory().take_accesses();
+        let mut hit = None;
+        for (idx, watch) in self.watches.iter_mut().enumerate() {
+            let report = match watch {
+                Watch::Memory {
+                    start,
+                    end,
+                    reads,
+                    writes,
+                } => accesses
+                    .iter()
+                    .find(|access| {
+                        (*start..=*end).contains(&access.address)
+                            && match access.kind {
+                                AccessKind::Read => *reads,
+                                AccessKind::Write => *writes,
+                            }
+                    })
+                    .map(|access| match access.kind {
+                        AccessKind::Read => {
+                            format!("read {:#04X} from {:#06X}", access.value, access.address)
+                        }
+                        AccessKind::Write => {
+                            format!("wrote {:#04X} to {:#06X}", access.value, access.address)
+                        }
+                    }),
+                Watch::Value { expr, last } => {
+                    let value = expr.eval(chip8);
+                    let changed = value != *last;
+                    let report = format!("{} changed from {:#X} to {:#X}", expr, last, value);
+                    *last = value;
+                    changed.then_some(report)
+                }
+                Watch::Condition { expr, last } => {
+                    let value = expr.is_true(chip8);
+                    let became_true = value && !*last;
+                    *last = value;
+                    became_true.then(|| format!("{} is true", expr))
+                }
+            };
+            if hit.is_none() {
+                hit = report.map(|report| format!("watch {}: {}", idx + 1, report));
+            }
+        }
+        hit
+    }
+
+    // Rebases the watches on the current state, e.g. after the user changed
+    // it by stepping back.
+    fn refresh_watches(&mut self, chip8: &Chip8) {
+        chip8.memory().take_accesses();
+        for watch in &mut self.watches {
+            match watch {
+                Watch::Memory { .. } => (),
+                Watch::Value { expr, last } => *last = expr.eval(chip8),
+                Watch::Condition { expr, last } => *last = expr.is_true(chip8),
+            }
+        }
+    }
+
+    fn resume(&mut self, mode: Mode, chip8: &Chip8) {
+        self.mode = mode;
+        self.resumed = true;
+        self.refresh_watches(chip8);
+    }
+
+    // Memory is only recorded while there is a memory watch to look at it.
+    fn update_recording(&self, chip8: &Chip8) {
+        let on = self
+            .watches
+            .iter()
+            .any(|watch| matches!(watch, Watch::Memory { .. }));
+        chip8.memory().record_accesses(on);
+    }
+
+    // Executes one command line and returns what to print.
+    pub fn execute(&mut self, line: &str, chip8: &mut Chip8) -> String {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            self.last_command = line.trim().to_string();
+            self.last_command.clone()
+        };
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return String::new();
+        };
+        let args: Vec<&str> = words.collect();
+
+        match self.command(command, &args, chip8) {
+            Ok(reply) => reply,
+            Err(e) => format!("error: {}", e),
+        }
+    }
+
+    fn command(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        chip8: &mut Chip8,
+    ) -> Result<String, String> {
+        match command {
+            "c" | "continue" => {
+                self.resume(Mode::Running, chip8);
+                Ok("running".to_string())
+            }
+            "p" | "pause" => {
+                self.mode = Mode::Paused;
+                self.refresh_watches(chip8);
+                Ok(registers(chip8))
+            }
+            "s" | "step" => {
+                let count = optional_number(args.first(), 1)?;
+                self.mode = Mode::Paused;
+                for _ in 0..count {
+                    if chip8.is_halted() {
+                        break;
+                    }
+                    self.history.push(chip8);
+                    if let Err(e) = chip8.step() {
+                        return Ok(format!("error: {}\n{}", e, registers(chip8)));
+                    }
+                    if let Some(hit) = self.check_watches(chip8) {
+                        return Ok(format!("{}\n{}", hit, registers(chip8)));
+                    }
+                }
+                Ok(registers(chip8))
+            }
+            "n" | "next" => {
+                let pc = chip8.cpu().pc();
+                if let Instruction::Call(_) = Instruction::fetch(chip8.memory(), pc) {
+                    let sp = chip8.cpu().sp();
+                    self.resume(
+                        Mode::StepOver {
+                            pc: pc.wrapping_add(2),
+                            sp,
+                        },
+                        chip8,
+                    );
+                    Ok("stepping over call".to_string())
+                } else {
+                    self.command("step", &[], chip8)
+                }
+            }
+            "f" | "finish" => {
+                let sp = chip8.cpu().sp();
+                if sp == 0 {
+                    return Err("not inside a subroutine".to_string());
+                }
+                self.resume(Mode::Finish { sp }, chip8);
+                Ok("running until return".to_string())
+            }
+            "bs" | "back" => {
+                let count = optional_number(args.first(), 1)?;
+                let mut undone = 0;
+                while undone < count && self.history.rewind(chip8) {
+                    undone += 1;
+                }
+                self.mode = Mode::Paused;
+                self.refresh_watches(chip8);
+                if undone < count {
+                    Ok(format!(
+                        "history exhausted after {} instruction(s)\n{}",
+                        undone,
+                        registers(chip8)
+                    ))
+                } else {
+                    Ok(registers(chip8))
+                }
+            }
+            "b" | "break" => {
+                let addr = required_address(args.first())?;
+                let cond = match args.get(1) {
+                    None => None,
+                    Some(&"if") if args.len() > 2 => Some(Expr::parse(&args[2..].join(" "))?),
+                    Some(_) => return Err("expected `if <expr>` after the address".to_string()),
+                };
+                let reply = match &cond {
+                    Some(cond) => format!("breakpoint at {:#06X} if {}", addr, cond),
+                    None => format!("breakpoint at {:#06X}", addr),
+                };
+                self.breakpoints.insert(addr, cond);
+                Ok(reply)
+            }
+            "d" | "delete" => {
+                let addr = required_address(args.first())?;
+                if self.breakpoints.remove(&addr).is_some() {
+                    Ok(format!("deleted breakpoint at {:#06X}", addr))
+                } else {
+                    Err(format!("no breakpoint at {:#06X}", addr))
+                }
+            }
+            "w" | "watch" => {
+                if args.is_empty() {
+                    return Err("missing address or expression".to_string());
+                }
+                let watch = match parse_range(args[0])? {
+                    Some((start, end)) => {
+                        let (reads, writes) = match args.get(1).copied() {
+                            None | Some("w") => (false, true),
+                            Some("r") => (true, false),
+                            Some("rw") => (true, true),
+                            Some(other) => return Err(format!("unknown watch kind `{}`", other)),
+                        };
+                        Watch::Memory {
+                            start,
+                            end,
+                            reads,
+                            writes,
+                        }
+                    }
+                    None => {
+                        let expr = Expr::parse(&args.join(" "))?;
+                        let last = expr.eval(chip8);
+                        Watch::Value { expr, last }
+                    }
+                };
+                Ok(self.add_watch(watch, chip8))
+            }
+            "cond" => {
+                let expr = Expr::parse(&args.join(" "))?;
+                let last = expr.is_true(chip8);
+                Ok(self.add_watch(Watch::Condition { expr, last }, chip8))
+            }
+            "uw" | "unwatch" => {
+                let number = required_number(args.first())?;
+                if number == 0 || number > self.watches.len() {
+                    return Err(format!("no watch {}", number));
+                }
+                let watch = self.watches.remove(number - 1);
+                self.update_recording(chip8);
+                Ok(format!("deleted watch {}: {}", number, watch.describe()))
+            }
+            "bl" | "breakpoints" => {
+                if self.breakpoints.is_empty() && self.watches.is_empty() {
+                    return Ok("no breakpoints or watches".to_string());
+                }
+                let breakpoints = self.breakpoints.iter().map(|(addr, cond)| match cond {
+                    Some(cond) => format!("breakpoint at {:#06X} if {}", addr, cond),
+                    None => format!("breakpoint at {:#06X}", addr),
+                });
+                let watches = self
+                    .watches
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, watch)| format!("watch {}: {}", idx + 1, watch.describe()));
+                Ok(breakpoints.chain(watches).collect::<Vec<_>>().join("\n"))
+            }
+            "r" | "regs" => Ok(registers(chip8)),
+            "x" => {
+                let addr = required_address(args.first())?;
+                let len = optional_number(args.get(1), 16)?;
+                Ok(hex_dump(chip8, addr, len))
+            }
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command `{}` (try `help`)", command)),
+        }
+    }
+
+    fn add_watch(&mut self, watch: Watch, chip8: &Chip8) -> String {
+        let reply = format!("watch {}: {}", self.watches.len() + 1, watch.describe());
+        let was_recording = self
+            .watches
+            .iter()
+            .any(|watch| matches!(watch, Watch::Memory { .. }));
+        self.watches.push(watch);
+        if !was_recording {
+            self.update_recording(chip8);
+        }
+        reply
+    }
+}
+
+fn next_instruction(chip8: &Chip8) -> u16 {
+    let pc = chip8.cpu().pc();
+    let mem = chip8.memory();
+    ((mem.peek(pc) as u16) << 8) | mem.peek(pc.wrapping_add(1)) as u16
+}
+
+// PC, I, SP, timers, V0-VF, the stack and the next instruction.
+pub fn registers(chip8: &Chip8) -> String {
+    let cpu = chip8.cpu();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "PC={:#06X}  I={:#06X}  SP={:X}  DT={:02X}  ST={:02X}",
+        cpu.pc(),
+        cpu.i(),
+        cpu.sp(),
+        cpu.delay_timer(),
+        cpu.sound_timer()
+    );
+    for (half, regs) in cpu.regs().chunks(8).enumerate() {
+        let line: Vec<String> = regs
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| format!("V{:X}={:02X}", half * 8 + idx, v))
+            .collect();
+        let _ = writeln!(out, "{}", line.join(" "));
+    }
+    let stack: Vec<String> = cpu.stack()[..cpu.sp() as usize]
+        .iter()
+        .map(|addr| format!("{:#06X}", addr))
+        .collect();
+    let _ = writeln!(out, "stack: [{}]", stack.join(", "));
+    let instr = Instruction::fetch(chip8.memory(), cpu.pc());
+    let _ = write!(
+        out,
+        "next:  {:#06X}  {:04X}  {}",
+        cpu.pc(),
+        next_instruction(chip8),
+        instr
+    );
+    out
+}
+
+fn hex_dump(chip8: &Chip8, addr: u16, len: usize) -> String {
+    let mem = chip8.memory();
+    let mut lines = Vec::new();
+    for row in (0..len).step_by(16) {
+        let start = addr.wrapping_add(row as u16);
+        let bytes: Vec<String> = (0..16.min(len - row))
+            .map(|idx| format!("{:02X}", mem.peek(start.wrapping_add(idx as u16))))
+            .collect();
+        lines.push(format!("{:#06X}: {}", start, bytes.join(" ")));
+    }
+    lines.join("\n")
+}
+
+// Parses `0x200`, `$200`, `#200` (hex) or `512` (decimal).
+pub fn parse_number(text: &str) -> Option<usize> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('$'))
+        .or_else(|| text.strip_prefix('#'));
+    match hex {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// `addr` or the inclusive range `start..end`. `Ok(None)` means `text` is
+// neither, so it can be tried as an expression instead.
+fn parse_range(text: &str) -> Result<Option<(u16, u16)>, String> {
+    let (start, end) = match text.split_once("..") {
+        Some((start, end)) => match (parse_number(start), parse_number(end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Ok(None),
+        },
+        None => match parse_number(text) {
+            Some(addr) => (addr, addr),
+            None => return Ok(None),
+        },
+    };
+    if end > u16::MAX as usize {
+        return Err(format!("{:#X} is past the end of memory", end));
+    }
+    if start > end {
+        return Err(format!("`{}` ends before it starts", text));
+    }
+    Ok(Some((start as u16, end as u16)))
+}
+
+fn required_number(arg: Option<&&str>) -> Result<usize, String> {
+    let text = arg.ok_or("missing argument")?;
+    parse_number(text).ok_or_else(|| format!("`{}` is not a number", text))
+}
+
+fn required_address(arg: Option<&&str>) -> Result<u16, String> {
+    let addr = required_number(arg)?;
+    u16::try_from(addr).map_err(|_| format!("{:#X} is past the end of memory", addr))
+}
+
+fn optional_number(arg: Option<&&str>, default: usize) -> Result<usize, String> {
+    match arg {
+        Some(text) => parse_number(text).ok_or_else(|| format!("`{}` is not a number", text)),
+        None => Ok(default),
+    }
+}
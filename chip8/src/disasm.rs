@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::instruction::{Instruction, Syntax};
+use super::PROGRAM_OFFSET;
+
+// at most this many data bytes go on one line, as many as the longest
+// instruction has
+const DATA_PER_LINE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Code(Instruction),
+    Data,
+}
+
+// One line of a listing: an instruction, or a run of bytes no path from the
+// entry point executes (sprites, tables, padding).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub kind: Kind,
+}
+
+impl Line {
+    // address, raw bytes and mnemonic
+    pub fn render(&self, syntax: Syntax) -> String {
+        let mut raw = String::new();
+        for byte in &self.bytes {
+            let _ = write!(raw, "{:02X} ", byte);
+        }
+        format!(
+            "{:#06X}  {:<14}{}",
+            self.addr,
+            raw.trim_end(),
+            self.text(syntax)
+        )
+    }
+
+    // the mnemonic, or a data directive
+    pub fn text(&self, syntax: Syntax) -> String {
+        match &self.kind {
+            Kind::Code(instr) => instr.mnemonic(syntax),
+            Kind::Data => {
+                let bytes: Vec<String> = self
+                    .bytes
+                    .iter()
+                    .map(|byte| match syntax {
+                        Syntax::Cowgod => format!("#{:02X}", byte),
+                        Syntax::Octo => format!("0x{:02X}", byte),
+                    })
+                    .collect();
+                match syntax {
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                    Syntax::Octo => bytes.join(" "),
+                }
+            }
+        }
+    }
+}
+
+// A ROM image as loaded at PROGRAM_OFFSET.
+struct Image<'a> {
+    rom: &'a [u8],
+}
+
+impl Image<'_> {
+    fn contains(&self, addr: u16) -> bool {
+        addr >= PROGRAM_OFFSET && ((addr - PROGRAM_OFFSET) as usize) < self.rom.len()
+    }
+
+    // reads past the end of the ROM as zero, like the emulator's memory
+    fn byte(&self, addr: u16) -> u8 {
+        if self.contains(addr) {
+            self.rom[(addr - PROGRAM_OFFSET) as usize]
+        } else {
+            0
+        }
+    }
+
+    fn word(&self, addr: u16) -> u16 {
+        ((self.byte(addr) as u16) << 8) | self.byte(addr.wrapping_add(1)) as u16
+    }
+
+    fn instruction(&self, addr: u16) -> Instruction {
+        Instruction::decode(self.word(addr), self.word(addr.wrapping_add(2)))
+    }
+}
+
+// Addresses of every instruction reachable from the entry point, following
+// jumps, calls and both outcomes of skips. Computed jumps (Bnnn) can't be
+// followed statically, so code only reached through them shows up as data.
+pub fn reachable(rom: &[u8]) -> BTreeSet<u16> {
+    let image = Image { rom };
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![PROGRAM_OFFSET];
+    while let Some(addr) = pending.pop() {
+        if !image.contains(addr) || !seen.insert(addr) {
+            continue;
+        }
+        let instr = image.instruction(addr);
+        let next = addr.wrapping_add(instr.size());
+        match instr {
+            Instruction::Jp(nnn) => pending.push(nnn),
+            Instruction::Call(nnn) => pending.extend([nnn, next]),
+            Instruction::Ret
+            | Instruction::Exit
+            | Instruction::JpOffset { .. }
+            | Instruction::Unknown(_) => (),
+            _ if instr.is_skip() => {
+                let skipped = next.wrapping_add(image.instruction(next).size());
+                pending.extend([next, skipped]);
+            }
+            _ => pending.push(next),
+        }
+    }
+    seen
+}
+
+// Splits a ROM into code and data lines.
+pub fn disassemble(rom: &[u8]) -> Vec<Line> {
+    let image = Image { rom };
+    let code = reachable(rom);
+    let end = PROGRAM_OFFSET as usize + rom.len();
+
+    let mut lines = Vec::new();
+    let mut addr = PROGRAM_OFFSET as usize;
+    while addr < end {
+        let instr = image.instruction(addr as u16);
+        let size = instr.size() as usize;
+        // an instruction cut off by the end of the ROM is listed as data
+        if code.contains(&(addr as u16)) && addr + size <= end {
+            lines.push(Line {
+                addr: addr as u16,
+                bytes: (0..size)
+                    .map(|idx| image.byte((addr + idx) as u16))
+                    .collect(),
+                kind: Kind::Code(instr),
+            });
+            addr += size;
+        } else {
+            let start = addr;
+            addr += 1;
+            while addr < end && addr - start < DATA_PER_LINE && !code.contains(&(addr as u16)) {
+                addr += 1;
+            }
+            lines.push(Line {
+                addr: start as u16,
+                bytes: (start..addr).map(|addr| image.byte(addr as u16)).collect(),
+                kind: Kind::Data,
+            });
+        }
+    }
+    lines
+}
+
+// The whole listing as text, one line per instruction or data run.
+pub fn listing(rom: &[u8], syntax: Syntax) -> String {
+    let mut out = String::new();
+    for line in disassemble(rom) {
+        let _ = writeln!(out, "{}", line.render(syntax));
+    }
+    out
+}
+
+// The ROM as source text, one instruction or data directive per line without
+// addresses, which `asm::assemble` turns back into the same bytes.
+pub fn source(rom: &[u8], syntax: Syntax) -> String {
+    let mut out = String::new();
+    for line in disassemble(rom) {
+        let _ = writeln!(out, "{}", line.text(syntax));
+    }
+    out
+}
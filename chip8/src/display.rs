@@ -0,0 +1,189 @@
+use super::state::{StateError, StateReader, StateWriter};
+
+// CHIP-8 resolution, also used by SUPER-CHIP in low-resolution mode
+pub const HEIGHT: usize = 32;
+pub const WIDTH: usize = 64;
+
+// SUPER-CHIP high-resolution mode
+pub const HIRES_HEIGHT: usize = 64;
+pub const HIRES_WIDTH: usize = 128;
+
+// XO-CHIP draws on two bit-planes, so each pixel is a value from 0 to 3:
+// bit 0 is set by the first plane and bit 1 by the second.
+pub const PLANES: u8 = 2;
+
+pub const BG_COLOR: u32 = 0;
+pub const FG_COLOR: u32 = 0x004cd137;
+// colours of pixels set only on the second plane, and on both planes
+pub const FG2_COLOR: u32 = 0x00e84118;
+pub const BLEND_COLOR: u32 = 0x00fbc531;
+
+// colour of each pixel value
+pub const PALETTE: [u32; 4] = [BG_COLOR, FG_COLOR, FG2_COLOR, BLEND_COLOR];
+
+#[derive(Clone)]
+pub struct Display {
+    // vram, large enough for high-resolution mode; in low-resolution mode
+    // only the top-left WIDTH x HEIGHT corner is used
+    data: [[u8; HIRES_WIDTH]; HIRES_HEIGHT],
+    hires: bool,
+    // bit-planes that drawing, clearing and scrolling apply to (XO-CHIP)
+    selected_planes: u8,
+    // indicate when to redraw
+    needs_update: bool,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            data: [[0; HIRES_WIDTH]; HIRES_HEIGHT],
+            hires: false,
+            selected_planes: 1,
+            needs_update: true,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            HEIGHT
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    // Switching resolution clears the screen, on every plane.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.data = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+        self.needs_update = true;
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    // Selects the bit-planes later operations apply to, as a bitmask.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.selected_planes = planes & 0b11;
+    }
+
+    pub fn read_pixel(&self, x: usize, y: usize) -> u8 {
+        self.data[y][x]
+    }
+
+    pub fn write(&mut self, x: usize, y: usize, value: u8) {
+        self.data[y][x] = value;
+        self.needs_update = true;
+    }
+
+    // Clears the selected planes.
+    pub fn clear(&mut self) {
+        let keep = !self.selected_planes;
+        for row in self.data.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= keep;
+            }
+        }
+        self.needs_update = true;
+    }
+
+    // Rows of the visible framebuffer at the current resolution.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let width = self.width();
+        self.data[..self.height()]
+            .iter()
+            .map(move |row| &row[..width])
+    }
+
+    // Moves the picture on the selected planes down by `n` rows; rows
+    // scrolled in are blank.
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+
+    // Moves the picture on the selected planes up by `n` rows; rows scrolled
+    // in are blank.
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
+    }
+
+    // Moves the picture on the selected planes right by `n` columns; columns
+    // scrolled in are blank.
+    pub fn scroll_right(&mut self, n: usize) {
+        self.scroll(n as isize, 0);
+    }
+
+    // Moves the picture on the selected planes left by `n` columns; columns
+    // scrolled in are blank.
+    pub fn scroll_left(&mut self, n: usize) {
+        self.scroll(-(n as isize), 0);
+    }
+
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let mask = self.selected_planes;
+        let old = self.data;
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&src_x) && (0..height).contains(&src_y) {
+                    old[src_y as usize][src_x as usize] & mask
+                } else {
+                    0
+                };
+                let pixel = &mut self.data[y as usize][x as usize];
+                *pixel = (*pixel & !mask) | moved;
+            }
+        }
+        self.needs_update = true;
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.hires);
+        w.u8(self.selected_planes);
+        for row in self.data.iter() {
+            w.bytes(row);
+        }
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.hires = r.bool()?;
+        self.selected_planes = r.u8()?;
+        for row in self.data.iter_mut() {
+            row.copy_from_slice(r.bytes(HIRES_WIDTH)?);
+        }
+        if self.selected_planes > 0b11 || self.data.iter().flatten().any(|pixel| *pixel > 0b11) {
+            return Err(StateError::Corrupt("display"));
+        }
+        // whatever was on screen before is stale now
+        self.needs_update = true;
+        Ok(())
+    }
+
+    pub fn needs_update(&self) -> bool {
+        self.needs_update
+    }
+
+    // called by the host once it has presented the current frame
+    pub fn mark_updated(&mut self) {
+        self.needs_update = false;
+    }
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, fmt};
+
+// Why the machine could not go on. The failing instruction has not been
+// completed and PC still points at it, so a host can show where it stopped,
+// hand over to the debugger, or move PC on and carry on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    // CALL with all 16 stack entries in use
+    StackOverflow,
+    // RET with nothing on the stack
+    StackUnderflow,
+    // an opcode no supported CHIP-8 variant defines
+    InvalidOpcode { pc: u16, instr: u16 },
+    // an address computed past the end of the 64 KiB address space
+    MemoryOutOfBounds { address: usize },
+    // a write into the interpreter's area below PROGRAM_OFFSET
+    ProtectedWrite { address: u16 },
+    // a ROM that doesn't fit between PROGRAM_OFFSET and the end of memory
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow => write!(f, "stack overflow: CALL with a full stack"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow: RET with an empty stack"),
+            Chip8Error::InvalidOpcode { pc, instr } => {
+                write!(f, "invalid opcode {:04X} at {:#06X}", instr, pc)
+            }
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "address {:#X} is outside memory", address)
+            }
+            Chip8Error::ProtectedWrite { address } => {
+                write!(f, "write to the interpreter's memory at {:#06X}", address)
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} fit in memory", size, max)
+            }
+        }
+    }
+}
+
+impl Error for Chip8Error {}
@@ -0,0 +1,348 @@
+use std::fmt;
+
+use super::Chip8;
+
+// Small expression language over machine state, used by the debugger for
+// conditional breakpoints and watches:
+//
+//   V3 == 0x10 && I > 0x300
+//   [I + 2] != 0 || DT == 0
+//
+// Operands are numbers (`16`, `0x10`, `$10`, `#10`), the registers V0-VF,
+// I, PC, SP, DT and ST, and `[addr]` for the memory byte at addr. Addresses
+// wrap around the 64 KiB address space, so `[I - 1]` with I = 0 reads 0xFFFF.
+// Operators, loosest binding first:
+//
+//   ||   &&   |   ^   &   == !=   < <= > >=   + -   ! (unary)
+//
+// Expressions may nest at most MAX_DEPTH deep, counting brackets, unary
+// operators and chained binary ones, so evaluating one can't overflow the
+// stack.
+//
+// Comparisons and logic evaluate to 1 or 0, and any non-zero value is true.
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Reg(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Mem(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+impl BinOp {
+    // operators from loosest to tightest binding
+    const LEVELS: [&'static [BinOp]; 7] = [
+        &[BinOp::Or],
+        &[BinOp::And],
+        &[BinOp::BitOr],
+        &[BinOp::BitXor],
+        &[BinOp::BitAnd],
+        &[BinOp::Eq, BinOp::Ne],
+        &[BinOp::Lt, BinOp::Le, BinOp::Gt, BinOp::Ge],
+    ];
+    const ADDITIVE: &'static [BinOp] = &[BinOp::Add, BinOp::Sub];
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Or => "||",
+            BinOp::And => "&&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::BitAnd => "&",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+        }
+    }
+
+    fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            BinOp::Or => ((lhs != 0) || (rhs != 0)) as i64,
+            BinOp::And => ((lhs != 0) && (rhs != 0)) as i64,
+            BinOp::BitOr => lhs | rhs,
+            BinOp::BitXor => lhs ^ rhs,
+            BinOp::BitAnd => lhs & rhs,
+            BinOp::Eq => (lhs == rhs) as i64,
+            BinOp::Ne => (lhs != rhs) as i64,
+            BinOp::Lt => (lhs < rhs) as i64,
+            BinOp::Le => (lhs <= rhs) as i64,
+            BinOp::Gt => (lhs > rhs) as i64,
+            BinOp::Ge => (lhs >= rhs) as i64,
+            BinOp::Add => lhs.wrapping_add(rhs),
+            BinOp::Sub => lhs.wrapping_sub(rhs),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.binary(0)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected `{}`", token)),
+        }
+    }
+
+    pub fn eval(&self, chip8: &Chip8) -> i64 {
+        let cpu = chip8.cpu();
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Reg(reg) => cpu.regs()[*reg as usize] as i64,
+            Expr::I => cpu.i() as i64,
+            Expr::Pc => cpu.pc() as i64,
+            Expr::Sp => cpu.sp() as i64,
+            Expr::Dt => cpu.delay_timer() as i64,
+            Expr::St => cpu.sound_timer() as i64,
+            // wraps, as documented above
+            Expr::Mem(addr) => chip8.memory().peek(addr.eval(chip8) as u16) as i64,
+            Expr::Not(expr) => (expr.eval(chip8) == 0) as i64,
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.eval(chip8), rhs.eval(chip8)),
+        }
+    }
+
+    pub fn is_true(&self, chip8: &Chip8) -> bool {
+        self.eval(chip8) != 0
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(value) => write!(f, "{:#X}", value),
+            Expr::Reg(reg) => write!(f, "V{:X}", reg),
+            Expr::I => write!(f, "I"),
+            Expr::Pc => write!(f, "PC"),
+            Expr::Sp => write!(f, "SP"),
+            Expr::Dt => write!(f, "DT"),
+            Expr::St => write!(f, "ST"),
+            Expr::Mem(addr) => write!(f, "[{}]", addr),
+            Expr::Not(expr) => write!(f, "!{}", expr),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol(), rhs),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+// longest first, so `<=` is not read as `<` `=`
+const SYMBOLS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "|", "^", "&", "<", ">", "+", "-", "!", "(", ")", "[", "]",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Op(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '#'))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                // not always ASCII, so not always one byte long
+                let c = rest.chars().next().unwrap_or_default();
+                return Err(format!("unexpected `{}`", c));
+            }
+            let word = &rest[..len];
+            tokens.push(match parse_number(word) {
+                Some(value) => Token::Number(value),
+                None => Token::Name(word.to_string()),
+            });
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// `0x10`, `$10` and `#10` are hex, anything else starting with a digit is
+// decimal.
+fn parse_number(word: &str) -> Option<i64> {
+    let hex = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .or_else(|| word.strip_prefix('$'))
+        .or_else(|| word.strip_prefix('#'));
+    match hex {
+        Some(digits) => i64::from_str_radix(digits, 16).ok(),
+        None if word.starts_with(|c: char| c.is_ascii_digit()) => word.parse().ok(),
+        None => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // how deep the expression being parsed nests so far
+    depth: usize,
+}
+
+impl Parser {
+    // Goes one level deeper, unless that is past MAX_DEPTH.
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("expression nests more than {} deep", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // consumes `op` if it is the next token
+    fn eat(&mut self, op: &str) -> bool {
+        if self.tokens.get(self.pos) == Some(&Token::Op(symbol(op))) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", op))
+        }
+    }
+
+    // Parses operators at binding level `level` and tighter.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let ops = match BinOp::LEVELS.get(level) {
+            Some(ops) => *ops,
+            None if level == BinOp::LEVELS.len() => BinOp::ADDITIVE,
+            None => return self.unary(),
+        };
+        let depth = self.depth;
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for op in ops {
+                if self.eat(op.symbol()) {
+                    // each operator in a chain nests the ones before it
+                    self.enter()?;
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            self.depth = depth;
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let expr = if self.eat("!") {
+            self.enter()?;
+            Expr::Not(Box::new(self.unary()?))
+        } else if self.eat("-") {
+            self.enter()?;
+            Expr::Binary(
+                BinOp::Sub,
+                Box::new(Expr::Number(0)),
+                Box::new(self.unary()?),
+            )
+        } else if self.eat("(") {
+            self.enter()?;
+            let expr = self.binary(0)?;
+            self.expect(")")?;
+            expr
+        } else if self.eat("[") {
+            self.enter()?;
+            let addr = self.binary(0)?;
+            self.expect("]")?;
+            Expr::Mem(Box::new(addr))
+        } else {
+            return self.operand();
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) => register(&name),
+            Some(token) => Err(format!("unexpected `{}`", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn symbol(op: &str) -> &'static str {
+    SYMBOLS.iter().find(|s| **s == op).copied().unwrap_or("")
+}
+
+fn register(name: &str) -> Result<Expr, String> {
+    let upper = name.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => Ok(Expr::I),
+        "PC" => Ok(Expr::Pc),
+        "SP" => Ok(Expr::Sp),
+        "DT" => Ok(Expr::Dt),
+        "ST" => Ok(Expr::St),
+        _ => upper
+            .strip_prefix('V')
+            .filter(|digit| digit.len() == 1)
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+            .map(Expr::Reg)
+            .ok_or_else(|| format!("unknown register `{}`", name)),
+    }
+}
@@ -0,0 +1,409 @@
+use std::fmt;
+
+use super::memory::Memory;
+
+// One decoded instruction of CHIP-8, SUPER-CHIP 1.1 or XO-CHIP. `x` and `y`
+// are register numbers, `kk` an immediate byte and `nnn` an address, as in
+// Cowgod's reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 0nnn - SYS addr: machine code routine, ignored by interpreters
+    Sys(u16),
+    // 00E0 - CLS
+    Cls,
+    // 00EE - RET
+    Ret,
+    // 00Cn - SCD nibble
+    ScrollDown(u8),
+    // 00Dn - SCU nibble
+    ScrollUp(u8),
+    // 00FB - SCR
+    ScrollRight,
+    // 00FC - SCL
+    ScrollLeft,
+    // 00FD - EXIT
+    Exit,
+    // 00FE - LOW
+    Low,
+    // 00FF - HIGH
+    High,
+    // 1nnn - JP addr
+    Jp(u16),
+    // 2nnn - CALL addr
+    Call(u16),
+    // 3xkk - SE Vx, byte
+    SeByte { x: u8, kk: u8 },
+    // 4xkk - SNE Vx, byte
+    SneByte { x: u8, kk: u8 },
+    // 5xy0 - SE Vx, Vy
+    SeReg { x: u8, y: u8 },
+    // 5xy2 - LD [I], Vx-Vy
+    SaveRange { x: u8, y: u8 },
+    // 5xy3 - LD Vx-Vy, [I]
+    LoadRange { x: u8, y: u8 },
+    // 6xkk - LD Vx, byte
+    LdByte { x: u8, kk: u8 },
+    // 7xkk - ADD Vx, byte
+    AddByte { x: u8, kk: u8 },
+    // 8xy0 - LD Vx, Vy
+    LdReg { x: u8, y: u8 },
+    // 8xy1 - OR Vx, Vy
+    Or { x: u8, y: u8 },
+    // 8xy2 - AND Vx, Vy
+    And { x: u8, y: u8 },
+    // 8xy3 - XOR Vx, Vy
+    Xor { x: u8, y: u8 },
+    // 8xy4 - ADD Vx, Vy
+    AddReg { x: u8, y: u8 },
+    // 8xy5 - SUB Vx, Vy
+    Sub { x: u8, y: u8 },
+    // 8xy6 - SHR Vx {, Vy}
+    Shr { x: u8, y: u8 },
+    // 8xy7 - SUBN Vx, Vy
+    Subn { x: u8, y: u8 },
+    // 8xyE - SHL Vx {, Vy}
+    Shl { x: u8, y: u8 },
+    // 9xy0 - SNE Vx, Vy
+    SneReg { x: u8, y: u8 },
+    // Annn - LD I, addr
+    LdI(u16),
+    // Bnnn - JP V0, addr (x only matters with the `jump_uses_vx` quirk)
+    JpOffset { x: u8, nnn: u16 },
+    // Cxkk - RND Vx, byte
+    Rnd { x: u8, kk: u8 },
+    // Dxyn - DRW Vx, Vy, nibble
+    Drw { x: u8, y: u8, n: u8 },
+    // Ex9E - SKP Vx
+    Skp(u8),
+    // ExA1 - SKNP Vx
+    Sknp(u8),
+    // F000 nnnn - LD I, long addr
+    LdILong(u16),
+    // Fn01 - PLANE n
+    Plane(u8),
+    // F002 - AUDIO
+    Audio,
+    // Fx07 - LD Vx, DT
+    LdVxDt(u8),
+    // Fx0A - LD Vx, K
+    LdVxK(u8),
+    // Fx15 - LD DT, Vx
+    LdDtVx(u8),
+    // Fx18 - LD ST, Vx
+    LdStVx(u8),
+    // Fx1E - ADD I, Vx
+    AddI(u8),
+    // Fx29 - LD F, Vx
+    LdF(u8),
+    // Fx30 - LD HF, Vx
+    LdHf(u8),
+    // Fx33 - LD B, Vx
+    LdB(u8),
+    // Fx3A - PITCH Vx
+    Pitch(u8),
+    // Fx55 - LD [I], Vx
+    LdIVx(u8),
+    // Fx65 - LD Vx, [I]
+    LdVxI(u8),
+    // Fx75 - LD R, Vx
+    LdRVx(u8),
+    // Fx85 - LD Vx, R
+    LdVxR(u8),
+    // anything else
+    Unknown(u16),
+}
+
+// how mnemonics are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    // `LD V3, #10` as in Cowgod's Chip-8 Technical Reference
+    Cowgod,
+    // `v3 := 0x10` as in the Octo assembler
+    Octo,
+}
+
+impl Instruction {
+    // Decodes the instruction word `op`. `operand` is the word that follows
+    // it, which only `F000 nnnn` uses.
+    pub fn decode(op: u16, operand: u16) -> Instruction {
+        let x = ((op & 0x0F00) >> 8) as u8;
+        let y = ((op & 0x00F0) >> 4) as u8;
+        let n = (op & 0x000F) as u8;
+        let kk = (op & 0x00FF) as u8;
+        let nnn = op & 0x0FFF;
+
+        match (op >> 12, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => Instruction::Cls,
+            (0x0, 0x0, 0xE, 0xE) => Instruction::Ret,
+            (0x0, 0x0, 0xC, _) => Instruction::ScrollDown(n),
+            (0x0, 0x0, 0xD, _) => Instruction::ScrollUp(n),
+            (0x0, 0x0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Instruction::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Instruction::Low,
+            (0x0, 0x0, 0xF, 0xF) => Instruction::High,
+            (0x0, _, _, _) => Instruction::Sys(nnn),
+            (0x1, _, _, _) => Instruction::Jp(nnn),
+            (0x2, _, _, _) => Instruction::Call(nnn),
+            (0x3, _, _, _) => Instruction::SeByte { x, kk },
+            (0x4, _, _, _) => Instruction::SneByte { x, kk },
+            (0x5, _, _, 0x0) => Instruction::SeReg { x, y },
+            (0x5, _, _, 0x2) => Instruction::SaveRange { x, y },
+            (0x5, _, _, 0x3) => Instruction::LoadRange { x, y },
+            (0x6, _, _, _) => Instruction::LdByte { x, kk },
+            (0x7, _, _, _) => Instruction::AddByte { x, kk },
+            (0x8, _, _, 0x0) => Instruction::LdReg { x, y },
+            (0x8, _, _, 0x1) => Instruction::Or { x, y },
+            (0x8, _, _, 0x2) => Instruction::And { x, y },
+            (0x8, _, _, 0x3) => Instruction::Xor { x, y },
+            (0x8, _, _, 0x4) => Instruction::AddReg { x, y },
+            (0x8, _, _, 0x5) => Instruction::Sub { x, y },
+            (0x8, _, _, 0x6) => Instruction::Shr { x, y },
+            (0x8, _, _, 0x7) => Instruction::Subn { x, y },
+            (0x8, _, _, 0xE) => Instruction::Shl { x, y },
+            (0x9, _, _, 0x0) => Instruction::SneReg { x, y },
+            (0xA, _, _, _) => Instruction::LdI(nnn),
+            (0xB, _, _, _) => Instruction::JpOffset { x, nnn },
+            (0xC, _, _, _) => Instruction::Rnd { x, kk },
+            (0xD, _, _, _) => Instruction::Drw { x, y, n },
+            (0xE, _, 0x9, 0xE) => Instruction::Skp(x),
+            (0xE, _, 0xA, 0x1) => Instruction::Sknp(x),
+            (0xF, 0x0, 0x0, 0x0) => Instruction::LdILong(operand),
+            (0xF, _, 0x0, 0x1) => Instruction::Plane(x),
+            (0xF, 0x0, 0x0, 0x2) => Instruction::Audio,
+            (0xF, _, 0x0, 0x7) => Instruction::LdVxDt(x),
+            (0xF, _, 0x0, 0xA) => Instruction::LdVxK(x),
+            (0xF, _, 0x1, 0x5) => Instruction::LdDtVx(x),
+            (0xF, _, 0x1, 0x8) => Instruction::LdStVx(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddI(x),
+            (0xF, _, 0x2, 0x9) => Instruction::LdF(x),
+            (0xF, _, 0x3, 0x0) => Instruction::LdHf(x),
+            (0xF, _, 0x3, 0x3) => Instruction::LdB(x),
+            (0xF, _, 0x3, 0xA) => Instruction::Pitch(x),
+            (0xF, _, 0x5, 0x5) => Instruction::LdIVx(x),
+            (0xF, _, 0x6, 0x5) => Instruction::LdVxI(x),
+            (0xF, _, 0x7, 0x5) => Instruction::LdRVx(x),
+            (0xF, _, 0x8, 0x5) => Instruction::LdVxR(x),
+            _ => Instruction::Unknown(op),
+        }
+    }
+
+    // Decodes the instruction stored at `addr`.
+    pub fn fetch(mem: &Memory, addr: u16) -> Instruction {
+        let word =
+            |addr: u16| ((mem.peek(addr) as u16) << 8) | mem.peek(addr.wrapping_add(1)) as u16;
+        Instruction::decode(word(addr), word(addr.wrapping_add(2)))
+    }
+
+    // The inverse of `decode`: the instruction's bytes as stored in memory.
+    pub fn encode(&self) -> Vec<u8> {
+        let xy = |x: u8, y: u8| ((x as u16) << 8) | ((y as u16) << 4);
+        let xkk = |x: u8, kk: u8| ((x as u16) << 8) | kk as u16;
+        let op = match *self {
+            Instruction::Sys(nnn) => nnn,
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::ScrollDown(n) => 0x00C0 | n as u16,
+            Instruction::ScrollUp(n) => 0x00D0 | n as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Jp(nnn) => 0x1000 | nnn,
+            Instruction::Call(nnn) => 0x2000 | nnn,
+            Instruction::SeByte { x, kk } => 0x3000 | xkk(x, kk),
+            Instruction::SneByte { x, kk } => 0x4000 | xkk(x, kk),
+            Instruction::SeReg { x, y } => 0x5000 | xy(x, y),
+            Instruction::SaveRange { x, y } => 0x5002 | xy(x, y),
+            Instruction::LoadRange { x, y } => 0x5003 | xy(x, y),
+            Instruction::LdByte { x, kk } => 0x6000 | xkk(x, kk),
+            Instruction::AddByte { x, kk } => 0x7000 | xkk(x, kk),
+            Instruction::LdReg { x, y } => 0x8000 | xy(x, y),
+            Instruction::Or { x, y } => 0x8001 | xy(x, y),
+            Instruction::And { x, y } => 0x8002 | xy(x, y),
+            Instruction::Xor { x, y } => 0x8003 | xy(x, y),
+            Instruction::AddReg { x, y } => 0x8004 | xy(x, y),
+            Instruction::Sub { x, y } => 0x8005 | xy(x, y),
+            Instruction::Shr { x, y } => 0x8006 | xy(x, y),
+            Instruction::Subn { x, y } => 0x8007 | xy(x, y),
+            Instruction::Shl { x, y } => 0x800E | xy(x, y),
+            Instruction::SneReg { x, y } => 0x9000 | xy(x, y),
+            Instruction::LdI(nnn) => 0xA000 | nnn,
+            Instruction::JpOffset { nnn, .. } => 0xB000 | nnn,
+            Instruction::Rnd { x, kk } => 0xC000 | xkk(x, kk),
+            Instruction::Drw { x, y, n } => 0xD000 | xy(x, y) | n as u16,
+            Instruction::Skp(x) => 0xE09E | xy(x, 0),
+            Instruction::Sknp(x) => 0xE0A1 | xy(x, 0),
+            Instruction::LdILong(nnnn) => return vec![0xF0, 0x00, (nnnn >> 8) as u8, nnnn as u8],
+            Instruction::Plane(n) => 0xF001 | xy(n, 0),
+            Instruction::Audio => 0xF002,
+            Instruction::LdVxDt(x) => 0xF007 | xy(x, 0),
+            Instruction::LdVxK(x) => 0xF00A | xy(x, 0),
+            Instruction::LdDtVx(x) => 0xF015 | xy(x, 0),
+            Instruction::LdStVx(x) => 0xF018 | xy(x, 0),
+            Instruction::AddI(x) => 0xF01E | xy(x, 0),
+            Instruction::LdF(x) => 0xF029 | xy(x, 0),
+            Instruction::LdHf(x) => 0xF030 | xy(x, 0),
+            Instruction::LdB(x) => 0xF033 | xy(x, 0),
+            Instruction::Pitch(x) => 0xF03A | xy(x, 0),
+            Instruction::LdIVx(x) => 0xF055 | xy(x, 0),
+            Instruction::LdVxI(x) => 0xF065 | xy(x, 0),
+            Instruction::LdRVx(x) => 0xF075 | xy(x, 0),
+            Instruction::LdVxR(x) => 0xF085 | xy(x, 0),
+            Instruction::Unknown(op) => op,
+        };
+        op.to_be_bytes().to_vec()
+    }
+
+    // size in bytes
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    // Skips over the next instruction when a condition holds.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::SeByte { .. }
+                | Instruction::SneByte { .. }
+                | Instruction::SeReg { .. }
+                | Instruction::SneReg { .. }
+                | Instruction::Skp(_)
+                | Instruction::Sknp(_)
+        )
+    }
+
+    pub fn mnemonic(&self, syntax: Syntax) -> String {
+        match syntax {
+            Syntax::Cowgod => self.cowgod(),
+            Syntax::Octo => self.octo(),
+        }
+    }
+
+    fn cowgod(&self) -> String {
+        match *self {
+            Instruction::Sys(nnn) => format!("SYS #{:03X}", nnn),
+            Instruction::Cls => "CLS".to_string(),
+            Instruction::Ret => "RET".to_string(),
+            Instruction::ScrollDown(n) => format!("SCD {}", n),
+            Instruction::ScrollUp(n) => format!("SCU {}", n),
+            Instruction::ScrollRight => "SCR".to_string(),
+            Instruction::ScrollLeft => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::Low => "LOW".to_string(),
+            Instruction::High => "HIGH".to_string(),
+            Instruction::Jp(nnn) => format!("JP #{:03X}", nnn),
+            Instruction::Call(nnn) => format!("CALL #{:03X}", nnn),
+            Instruction::SeByte { x, kk } => format!("SE V{:X}, #{:02X}", x, kk),
+            Instruction::SneByte { x, kk } => format!("SNE V{:X}, #{:02X}", x, kk),
+            Instruction::SeReg { x, y } => format!("SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => format!("LD [I], V{:X}-V{:X}", x, y),
+            Instruction::LoadRange { x, y } => format!("LD V{:X}-V{:X}, [I]", x, y),
+            Instruction::LdByte { x, kk } => format!("LD V{:X}, #{:02X}", x, kk),
+            Instruction::AddByte { x, kk } => format!("ADD V{:X}, #{:02X}", x, kk),
+            Instruction::LdReg { x, y } => format!("LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => format!("LD I, #{:03X}", nnn),
+            Instruction::JpOffset { nnn, .. } => format!("JP V0, #{:03X}", nnn),
+            Instruction::Rnd { x, kk } => format!("RND V{:X}, #{:02X}", x, kk),
+            Instruction::Drw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp(x) => format!("SKP V{:X}", x),
+            Instruction::Sknp(x) => format!("SKNP V{:X}", x),
+            Instruction::LdILong(nnnn) => format!("LD I, LONG #{:04X}", nnnn),
+            Instruction::Plane(n) => format!("PLANE {}", n),
+            Instruction::Audio => "AUDIO".to_string(),
+            Instruction::LdVxDt(x) => format!("LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => format!("LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => format!("LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => format!("LD ST, V{:X}", x),
+            Instruction::AddI(x) => format!("ADD I, V{:X}", x),
+            Instruction::LdF(x) => format!("LD F, V{:X}", x),
+            Instruction::LdHf(x) => format!("LD HF, V{:X}", x),
+            Instruction::LdB(x) => format!("LD B, V{:X}", x),
+            Instruction::Pitch(x) => format!("PITCH V{:X}", x),
+            Instruction::LdIVx(x) => format!("LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => format!("LD V{:X}, [I]", x),
+            Instruction::LdRVx(x) => format!("LD R, V{:X}", x),
+            Instruction::LdVxR(x) => format!("LD V{:X}, R", x),
+            Instruction::Unknown(op) => format!("DW #{:04X}", op),
+        }
+    }
+
+    // Octo spells skips as the condition under which the next instruction
+    // *runs*, so SE becomes `if vx != kk then`.
+    fn octo(&self) -> String {
+        match *self {
+            Instruction::Sys(nnn) => format!("0x{:02X} 0x{:02X}", nnn >> 8, nnn & 0xFF),
+            Instruction::Cls => "clear".to_string(),
+            Instruction::Ret => "return".to_string(),
+            Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+            Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::Low => "lores".to_string(),
+            Instruction::High => "hires".to_string(),
+            Instruction::Jp(nnn) => format!("jump 0x{:03X}", nnn),
+            Instruction::Call(nnn) => format!(":call 0x{:03X}", nnn),
+            Instruction::SeByte { x, kk } => format!("if v{:x} != 0x{:02X} then", x, kk),
+            Instruction::SneByte { x, kk } => format!("if v{:x} == 0x{:02X} then", x, kk),
+            Instruction::SeReg { x, y } => format!("if v{:x} != v{:x} then", x, y),
+            Instruction::SaveRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+            Instruction::LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+            Instruction::LdByte { x, kk } => format!("v{:x} := 0x{:02X}", x, kk),
+            Instruction::AddByte { x, kk } => format!("v{:x} += 0x{:02X}", x, kk),
+            Instruction::LdReg { x, y } => format!("v{:x} := v{:x}", x, y),
+            Instruction::Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+            Instruction::And { x, y } => format!("v{:x} &= v{:x}", x, y),
+            Instruction::Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+            Instruction::AddReg { x, y } => format!("v{:x} += v{:x}", x, y),
+            Instruction::Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+            Instruction::Shr { x, y } => format!("v{:x} >>= v{:x}", x, y),
+            Instruction::Subn { x, y } => format!("v{:x} =- v{:x}", x, y),
+            Instruction::Shl { x, y } => format!("v{:x} <<= v{:x}", x, y),
+            Instruction::SneReg { x, y } => format!("if v{:x} == v{:x} then", x, y),
+            Instruction::LdI(nnn) => format!("i := 0x{:03X}", nnn),
+            Instruction::JpOffset { nnn, .. } => format!("jump0 0x{:03X}", nnn),
+            Instruction::Rnd { x, kk } => format!("v{:x} := random 0x{:02X}", x, kk),
+            Instruction::Drw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+            Instruction::Skp(x) => format!("if v{:x} -key then", x),
+            Instruction::Sknp(x) => format!("if v{:x} key then", x),
+            Instruction::LdILong(nnnn) => format!("i := long 0x{:04X}", nnnn),
+            Instruction::Plane(n) => format!("plane {}", n),
+            Instruction::Audio => "audio".to_string(),
+            Instruction::LdVxDt(x) => format!("v{:x} := delay", x),
+            Instruction::LdVxK(x) => format!("v{:x} := key", x),
+            Instruction::LdDtVx(x) => format!("delay := v{:x}", x),
+            Instruction::LdStVx(x) => format!("buzzer := v{:x}", x),
+            Instruction::AddI(x) => format!("i += v{:x}", x),
+            Instruction::LdF(x) => format!("i := hex v{:x}", x),
+            Instruction::LdHf(x) => format!("i := bighex v{:x}", x),
+            Instruction::LdB(x) => format!("bcd v{:x}", x),
+            Instruction::Pitch(x) => format!("pitch := v{:x}", x),
+            Instruction::LdIVx(x) => format!("save v{:x}", x),
+            Instruction::LdVxI(x) => format!("load v{:x}", x),
+            Instruction::LdRVx(x) => format!("saveflags v{:x}", x),
+            Instruction::LdVxR(x) => format!("loadflags v{:x}", x),
+            Instruction::Unknown(op) => format!("0x{:02X} 0x{:02X}", op >> 8, op & 0xFF),
+        }
+    }
+}
+
+// Cowgod syntax
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.cowgod())
+    }
+}
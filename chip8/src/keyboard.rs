@@ -0,0 +1,92 @@
+use super::state::{StateError, StateReader, StateWriter};
+
+#[derive(Clone)]
+pub struct Keyboard {
+    // which keys are held down right now
+    keys: [bool; 16],
+    // how far a running Fx0A - LD Vx, K has got
+    wait: KeyWait,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyWait {
+    Idle,
+    // waiting for any key to go down
+    Press,
+    // got this key, waiting for it to come back up
+    Release(u8),
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {
+            keys: [false; 16],
+            wait: KeyWait::Idle,
+        }
+    }
+
+    // Replaces the held state of every key, as reported by the host.
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keys = keys;
+    }
+
+    pub fn poll(&self) -> usize {
+        // first key that is down, or 0xFF if there is none
+        self.keys.iter().position(|&down| down).unwrap_or(0xFF)
+    }
+
+    // Drives Fx0A, which calls this every cycle until it returns a key: the
+    // first key held down, once it has been released. A key already down when
+    // the wait starts counts too, so it ends as soon as that key comes up.
+    pub fn wait_for_key(&mut self) -> Option<u8> {
+        match self.wait {
+            KeyWait::Idle | KeyWait::Press => {
+                self.wait = match self.poll() {
+                    0xFF => KeyWait::Press,
+                    key => KeyWait::Release(key as u8),
+                };
+                None
+            }
+            KeyWait::Release(key) if !self.keys[key as usize] => {
+                self.wait = KeyWait::Idle;
+                Some(key)
+            }
+            KeyWait::Release(_) => None,
+        }
+    }
+
+    // true while an Fx0A is waiting
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.wait != KeyWait::Idle
+    }
+
+    // Only the Fx0A progress is saved; which keys are down is up to the host.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        match self.wait {
+            KeyWait::Idle => w.u8(0),
+            KeyWait::Press => w.u8(1),
+            KeyWait::Release(key) => w.u8(0x10 | key),
+        }
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.wait = match r.u8()? {
+            0 => KeyWait::Idle,
+            1 => KeyWait::Press,
+            tag @ 0x10..=0x1F => KeyWait::Release(tag & 0xF),
+            _ => return Err(StateError::Corrupt("key wait")),
+        };
+        Ok(())
+    }
+
+    pub fn query_key(&self, key_value: usize) -> bool {
+        // only the low nibble names a key
+        self.keys[key_value & 0xF]
+    }
+}
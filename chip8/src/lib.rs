@@ -0,0 +1,301 @@
+pub mod asm;
+pub mod audio;
+pub mod backend;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod expr;
+pub mod instruction;
+pub mod keyboard;
+pub mod memory;
+pub mod octo;
+pub mod png;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod snapshot;
+pub mod state;
+pub mod trace;
+pub mod tracediff;
+pub mod unknown;
+
+use backend::{AudioSink, InputSource, NullAudio, NullInput, NullVideo, VideoSink};
+use error::Chip8Error;
+use state::{StateError, StateReader, StateWriter};
+
+pub const PROGRAM_OFFSET: u16 = 0x200;
+
+// frames per second of emulated time; the delay and sound timers tick once
+// per frame
+pub const FRAME_RATE: u32 = 60;
+// instructions per second when the host does not ask for anything else
+pub const DEFAULT_IPS: u32 = 700;
+
+pub struct Chip8 {
+    memory: memory::Memory,
+    cpu: cpu::Cpu,
+
+    // device periphery
+    display: display::Display,
+    keyboard: keyboard::Keyboard,
+    rng: rng::Rng,
+
+    // identifies the loaded ROM in save states
+    rom_hash: u64,
+
+    // host backends
+    video: Box<dyn VideoSink>,
+    input: Box<dyn InputSource>,
+    audio: Box<dyn AudioSink>,
+
+    // clock
+    ips: u32,
+    // instructions owed to the current frame, in 1/FRAME_RATE units, so IPS
+    // values that are not a multiple of the frame rate still average out
+    cycle_debt: u32,
+    // instructions left to run before the current frame ends
+    cycles_left: u32,
+    // a frame has begun but not ended yet, e.g. because a debugger stopped it
+    in_frame: bool,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Chip8 {
+        Chip8 {
+            memory: memory::Memory::new(),
+            cpu: cpu::Cpu::default(),
+            display: display::Display::new(),
+            keyboard: keyboard::Keyboard::new(),
+            rng: rng::Rng::default(),
+            rom_hash: state::rom_hash(&[]),
+            video: Box::new(NullVideo),
+            input: Box::new(NullInput),
+            audio: Box::new(NullAudio),
+            ips: DEFAULT_IPS,
+            cycle_debt: 0,
+            cycles_left: 0,
+            in_frame: false,
+        }
+    }
+
+    pub fn set_ips(&mut self, ips: u32) {
+        self.ips = ips;
+    }
+
+    pub fn ips(&self) -> u32 {
+        self.ips
+    }
+
+    // Restarts the random number generator from `seed`, so RND draws the same
+    // numbers on every run.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = rng::Rng::new(seed);
+    }
+
+    pub fn set_quirks(&mut self, quirks: quirks::Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    pub fn set_unknown_opcode_policy(&mut self, policy: unknown::UnknownOpcodePolicy) {
+        self.cpu.set_unknown_opcode_policy(policy);
+    }
+
+    pub fn set_video(&mut self, video: Box<dyn VideoSink>) {
+        self.video = video;
+    }
+
+    pub fn set_input(&mut self, input: Box<dyn InputSource>) {
+        self.input = input;
+    }
+
+    pub fn set_audio(&mut self, audio: Box<dyn AudioSink>) {
+        self.audio = audio;
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let max = memory::MEM_SIZE - PROGRAM_OFFSET as usize;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        for (idx, byte) in rom.iter().enumerate() {
+            self.memory
+                .write_byte(PROGRAM_OFFSET + (idx as u16), *byte)?;
+        }
+        self.rom_hash = state::rom_hash(rom);
+        Ok(())
+    }
+
+    // Snapshots the whole machine (but not the host backends or settings such
+    // as quirks and speed) into the versioned save state format.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.write_state(true).0
+    }
+
+    // `save_state` with the memory section left out, and the offset it goes
+    // back in at. `Rewind` keeps memory page by page instead.
+    pub(crate) fn save_state_without_memory(&self) -> (Vec<u8>, usize) {
+        self.write_state(false)
+    }
+
+    fn write_state(&self, with_memory: bool) -> (Vec<u8>, usize) {
+        let mut w = StateWriter::new(self.rom_hash);
+        self.cpu.save_state(&mut w);
+        let memory_at = w.position();
+        if with_memory {
+            self.memory.save_state(&mut w);
+        }
+        self.display.save_state(&mut w);
+        self.keyboard.save_state(&mut w);
+        w.u64(self.rng.state());
+        w.u32(self.cycle_debt);
+        w.u32(self.cycles_left);
+        w.bool(self.in_frame);
+        (w.finish(), memory_at)
+    }
+
+    // Restores a snapshot made by `save_state`. States made for another ROM,
+    // or damaged ones, are rejected and leave the machine untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(data, self.rom_hash)?;
+        let mut cpu = self.cpu.clone();
+        let mut memory = self.memory.clone();
+        let mut display = self.display.clone();
+        let mut keyboard = self.keyboard.clone();
+        cpu.load_state(&mut r)?;
+        memory.load_state(&mut r)?;
+        display.load_state(&mut r)?;
+        keyboard.load_state(&mut r)?;
+        let rng_state = r.u64()?;
+        let cycle_debt = r.u32()?;
+        let cycles_left = r.u32()?;
+        let in_frame = r.bool()?;
+        r.finish()?;
+
+        self.cpu = cpu;
+        self.memory = memory;
+        self.display = display;
+        self.keyboard = keyboard;
+        self.rng.set_state(rng_state);
+        self.cycle_debt = cycle_debt % FRAME_RATE;
+        self.cycles_left = cycles_left;
+        self.in_frame = in_frame;
+        Ok(())
+    }
+
+    // Runs one 1/60 s frame of emulated time: poll input, execute this
+    // frame's share of instructions, tick the timers once and present the
+    // display once.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        self.run_frame_until(|_| false).map(|_| ())
+    }
+
+    // Like `run_frame`, but asks `stop` before every instruction whether to
+    // stop there. Returns true if it did; the frame is then left unfinished
+    // and the next call picks it up where it stopped. An instruction that
+    // fails leaves the frame unfinished the same way.
+    pub fn run_frame_until(
+        &mut self,
+        mut stop: impl FnMut(&Chip8) -> bool,
+    ) -> Result<bool, Chip8Error> {
+        if !self.in_frame {
+            self.begin_frame();
+        }
+        while self.cycles_left > 0 && !self.cpu.is_halted() {
+            if stop(self) {
+                return Ok(true);
+            }
+            self.execute_next_instruction()?;
+            self.cycles_left -= 1;
+        }
+        self.end_frame();
+        Ok(false)
+    }
+
+    // Executes exactly one instruction, starting or finishing a frame around
+    // it when it is the frame's first or last, so timers keep their pace
+    // while single-stepping.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if !self.in_frame {
+            self.begin_frame();
+        }
+        if self.cycles_left > 0 {
+            self.execute_next_instruction()?;
+            self.cycles_left -= 1;
+        }
+        if self.cycles_left == 0 || self.cpu.is_halted() {
+            self.end_frame();
+        }
+        Ok(())
+    }
+
+    fn begin_frame(&mut self) {
+        self.keyboard.set_keys(self.input.poll());
+        // in u64, as the debt plus an IPS near u32::MAX would overflow
+        let owed = self.cycle_debt as u64 + self.ips as u64;
+        self.cycles_left = (owed / FRAME_RATE as u64) as u32;
+        self.cycle_debt = (owed % FRAME_RATE as u64) as u32;
+        self.in_frame = true;
+    }
+
+    fn end_frame(&mut self) {
+        self.cycles_left = 0;
+        self.in_frame = false;
+        self.cpu.tick_timers();
+        self.audio.update(&self.cpu.sound());
+        self.present();
+    }
+
+    // Hands the display to the video backend if it changed since last time.
+    pub fn present(&mut self) {
+        if self.display.needs_update() {
+            self.video.present(&self.display);
+            self.display.mark_updated();
+        }
+    }
+
+    pub fn execute_next_instruction(&mut self) -> Result<(), Chip8Error> {
+        self.cpu.execute_next_instruction(
+            &mut self.memory,
+            &mut self.display,
+            &mut self.keyboard,
+            &mut self.rng,
+        )
+    }
+
+    // true once the ROM has executed the SUPER-CHIP 00FD - EXIT instruction
+    pub fn is_halted(&self) -> bool {
+        self.cpu.is_halted()
+    }
+
+    // see `Cpu::take_unknown_warnings`
+    pub fn take_unknown_warnings(&mut self) -> Vec<unknown::UnknownOpcode> {
+        self.cpu.take_unknown_warnings()
+    }
+
+    pub fn cpu(&self) -> &cpu::Cpu {
+        &self.cpu
+    }
+
+    pub fn memory(&self) -> &memory::Memory {
+        &self.memory
+    }
+
+    pub fn keyboard(&self) -> &keyboard::Keyboard {
+        &self.keyboard
+    }
+
+    pub fn display(&self) -> &display::Display {
+        &self.display
+    }
+}
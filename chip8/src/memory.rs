@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::error::Chip8Error;
+use super::state::{StateError, StateReader, StateWriter};
+
+// 64 KiB, the XO-CHIP address space; CHIP-8 programs only use the first 4 KiB
+pub const MEM_SIZE: usize = 0x10000;
+const RESERVED: u16 = 0x200;
+
+// Memory is tracked in pages so `Rewind` only has to diff the ones written
+// since it last looked.
+pub const PAGE_SIZE: usize = 0x100;
+pub const PAGES: usize = MEM_SIZE / PAGE_SIZE;
+
+// Each write stamps its page with a number no other write, to any Memory,
+// has used, so a page whose stamp hasn't changed holds the same bytes.
+static NEXT_STAMP: AtomicU64 = AtomicU64::new(0);
+
+fn new_stamp() -> u64 {
+    NEXT_STAMP.fetch_add(1, Ordering::Relaxed)
+}
+
+// where the interpreter keeps its fonts
+pub const FONT_ADDR: u16 = 0x0;
+pub const BIG_FONT_ADDR: u16 = 0x50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+// one byte read or written by an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub address: u16,
+    pub kind: AccessKind,
+    pub value: u8,
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    // ram
+    data: [u8; MEM_SIZE],
+    // stamp of the last write to each page
+    stamps: [u64; PAGES],
+    // data accesses since the last `take_accesses`, while recording is on
+    accesses: RefCell<Option<Vec<Access>>>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        let mut memory = Memory {
+            data: [0; MEM_SIZE],
+            stamps: [new_stamp(); PAGES],
+            accesses: RefCell::new(None),
+        };
+
+        let digit_sprites: [[u8; 5]; 16] = [
+            [0xF0, 0x90, 0x90, 0x90, 0xF0],
+            [0x20, 0x60, 0x20, 0x20, 0x70],
+            [0xF0, 0x10, 0xF0, 0x80, 0xF0],
+            [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+            [0x90, 0x90, 0xF0, 0x10, 0x10],
+            [0xF0, 0x80, 0xF0, 0x10, 0xF0],
+            [0xF0, 0x80, 0xF0, 0x90, 0xF0],
+            [0xF0, 0x10, 0x20, 0x40, 0x40],
+            [0xF0, 0x90, 0xF0, 0x90, 0xF0],
+            [0xF0, 0x90, 0xF0, 0x10, 0xF0],
+            [0xF0, 0x90, 0xF0, 0x90, 0x90],
+            [0xE0, 0x90, 0xE0, 0x90, 0xE0],
+            [0xF0, 0x80, 0x80, 0x80, 0xF0],
+            [0xE0, 0x90, 0x90, 0x90, 0xE0],
+            [0xF0, 0x80, 0xF0, 0x80, 0xF0],
+            [0xF0, 0x80, 0xF0, 0x80, 0x80],
+        ];
+
+        // SUPER-CHIP 8x10 digits
+        let big_digit_sprites: [[u8; 10]; 16] = [
+            [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF],
+            [0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+            [0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03],
+            [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18],
+            [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+            [0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3],
+            [0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC],
+            [0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C],
+            [0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC],
+            [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0],
+        ];
+
+        let mut curr_idx = FONT_ADDR as usize;
+        for num in digit_sprites {
+            for byte in num {
+                memory.data[curr_idx] = byte;
+                curr_idx += 1;
+            }
+        }
+
+        let mut curr_idx = BIG_FONT_ADDR as usize;
+        for num in big_digit_sprites {
+            for byte in num {
+                memory.data[curr_idx] = byte;
+                curr_idx += 1;
+            }
+        }
+
+        memory
+    }
+
+    // Programs may not overwrite the fonts and the rest of the interpreter's
+    // area below RESERVED.
+    pub fn write_byte(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        if address < RESERVED {
+            return Err(Chip8Error::ProtectedWrite { address });
+        }
+        self.data[address as usize] = value;
+        self.stamps[address as usize / PAGE_SIZE] = new_stamp();
+        self.record(address, AccessKind::Write, value);
+        Ok(())
+    }
+
+    // Checks that `len` bytes from `address` on could all be written, so an
+    // instruction can fail before it has written any of them.
+    pub fn check_write(&self, address: u16, len: usize) -> Result<(), Chip8Error> {
+        if address < RESERVED {
+            return Err(Chip8Error::ProtectedWrite { address });
+        }
+        if address as usize + len > MEM_SIZE {
+            return Err(Chip8Error::MemoryOutOfBounds { address: MEM_SIZE });
+        }
+        Ok(())
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+        let value = self.data[address as usize];
+        self.record(address, AccessKind::Read, value);
+        value
+    }
+
+    // Reads a byte without it counting as a data access: instruction fetches
+    // and debugger views go through here.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    // The bytes of page `page`, and its stamp: equal stamps mean the page
+    // hasn't changed.
+    pub fn page(&self, page: usize) -> &[u8] {
+        &self.data[page * PAGE_SIZE..(page + 1) * PAGE_SIZE]
+    }
+
+    pub fn page_stamp(&self, page: usize) -> u64 {
+        self.stamps[page]
+    }
+
+    // Turns recording of `read_byte`/`write_byte` calls on or off, e.g. for
+    // watchpoints. Turning it off drops whatever was recorded.
+    pub fn record_accesses(&self, on: bool) {
+        *self.accesses.borrow_mut() = if on { Some(Vec::new()) } else { None };
+    }
+
+    // Hands out the accesses recorded so far and starts a fresh list.
+    pub fn take_accesses(&self) -> Vec<Access> {
+        self.accesses
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn record(&self, address: u16, kind: AccessKind, value: u8) {
+        if let Some(accesses) = self.accesses.borrow_mut().as_mut() {
+            accesses.push(Access {
+                address,
+                kind,
+                value,
+            });
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.data.copy_from_slice(r.bytes(MEM_SIZE)?);
+        self.stamps = [new_stamp(); PAGES];
+        Ok(())
+    }
+}
@@ -0,0 +1,1047 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::asm::AsmError;
+use super::instruction::Instruction;
+use super::PROGRAM_OFFSET;
+
+// Compiler for Octo, the assembly language most community CHIP-8, SUPER-CHIP
+// and XO-CHIP programs are written in, producing a ROM that loads at
+// PROGRAM_OFFSET:
+//
+//   # comments run to the end of the line
+//   :alias x v1
+//   :const SPEED 2
+//   : main
+//     x := 0
+//     i := ball
+//     loop
+//       sprite x v2 3
+//       x += SPEED
+//       if x == 60 then x := 0
+//     again
+//   : ball 0x30 0x78 0x30
+//
+// Tokens are separated by whitespace. Everything Octo 1.2 compiles is
+// understood except string mode: labels (`: name`, `:next name`),
+// `:alias`, `:const`, `:calc name { expr }`, `:macro`, `:byte`, `:org`,
+// `:unpack`, `:call`, structured `if ... then`, `if ... begin ... else ...
+// end` and `loop ... while ... again`, the `<`, `>`, `<=` and `>=`
+// comparisons (which clobber VF) and the XO-CHIP statements. `:breakpoint`,
+// `:monitor` and `:proto` are accepted and ignored. A bare number emits a byte
+// and a bare name calls the subroutine with that label, which may be defined
+// further down.
+//
+// Errors use the Cowgod assembler's AsmError so both report the same way.
+pub fn compile(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut compiler = Compiler::new(source);
+    while let Some(token) = compiler.next() {
+        compiler.statement(token)?;
+    }
+    compiler.finish()
+}
+
+// compiled memory; programs may use all 64K of XO-CHIP
+const MEMORY_SIZE: usize = 0x10000;
+
+// macros expanding into themselves would otherwise never stop
+const MAX_EXPANSIONS: usize = 100_000;
+
+const KEYWORDS: [&str; 44] = [
+    "clear",
+    "return",
+    ";",
+    "exit",
+    "hires",
+    "lores",
+    "scroll-down",
+    "scroll-up",
+    "scroll-left",
+    "scroll-right",
+    "audio",
+    "plane",
+    "bcd",
+    "save",
+    "load",
+    "saveflags",
+    "loadflags",
+    "sprite",
+    "jump",
+    "jump0",
+    "native",
+    "loop",
+    "again",
+    "while",
+    "if",
+    "then",
+    "begin",
+    "else",
+    "end",
+    "key",
+    "-key",
+    "random",
+    "delay",
+    "buzzer",
+    "pitch",
+    "hex",
+    "bighex",
+    "long",
+    "i",
+    "{",
+    "}",
+    ":",
+    ":=",
+    "HERE",
+];
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+struct Macro<'a> {
+    args: Vec<&'a str>,
+    body: Vec<Token<'a>>,
+}
+
+// a value that may name a label defined further down
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Known(i64),
+    Label(&'a str),
+}
+
+// how a label's address is patched into the bytes at a fixup
+#[derive(Clone, Copy)]
+enum Patch {
+    // low 12 bits of an instruction word
+    Addr,
+    // a whole 16-bit word, after `i := long`
+    Word,
+    // `nibble << 4 | addr >> 8`, the first byte of `:unpack`
+    Unpack(u8),
+    High,
+    Low,
+}
+
+struct Fixup<'a> {
+    addr: usize,
+    label: &'a str,
+    patch: Patch,
+    line: usize,
+}
+
+// open control structures, innermost last
+enum Block {
+    // jump over the body, patched at `else` or `end`
+    If {
+        jump: usize,
+        line: usize,
+    },
+    // jump over the else branch, patched at `end`
+    Else {
+        jump: usize,
+        line: usize,
+    },
+    // `while` exits, patched at `again`
+    Loop {
+        start: usize,
+        exits: Vec<usize>,
+        line: usize,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Key,
+    NotKey,
+}
+
+impl Compare {
+    fn negate(self) -> Compare {
+        match self {
+            Compare::Eq => Compare::Ne,
+            Compare::Ne => Compare::Eq,
+            Compare::Lt => Compare::Ge,
+            Compare::Ge => Compare::Lt,
+            Compare::Gt => Compare::Le,
+            Compare::Le => Compare::Gt,
+            Compare::Key => Compare::NotKey,
+            Compare::NotKey => Compare::Key,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Reg(u8),
+    Byte(u8),
+}
+
+struct Condition {
+    x: u8,
+    compare: Compare,
+    rhs: Operand,
+}
+
+struct Compiler<'a> {
+    tokens: VecDeque<Token<'a>>,
+    // line of the token being compiled, for errors
+    line: usize,
+    memory: Vec<u8>,
+    here: usize,
+    end: usize,
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, f64>,
+    aliases: HashMap<&'a str, u8>,
+    macros: HashMap<&'a str, Macro<'a>>,
+    fixups: Vec<Fixup<'a>>,
+    blocks: Vec<Block>,
+    expansions: usize,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(source: &'a str) -> Compiler<'a> {
+        let mut tokens = VecDeque::new();
+        for (idx, line) in source.lines().enumerate() {
+            let code = line.split_once('#').map_or(line, |(code, _)| code);
+            for text in code.split_whitespace() {
+                tokens.push_back(Token {
+                    text,
+                    line: idx + 1,
+                });
+            }
+        }
+        Compiler {
+            tokens,
+            line: 0,
+            memory: vec![0; MEMORY_SIZE],
+            here: PROGRAM_OFFSET as usize,
+            end: PROGRAM_OFFSET as usize,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.pop_front()?;
+        self.line = token.line;
+        Some(token.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.front().map(|token| token.text)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<&'a str, AsmError> {
+        self.next()
+            .ok_or_else(|| self.error(format!("expected {} at the end of the file", what)))
+    }
+
+    fn expect_token(&mut self, wanted: &str) -> Result<(), AsmError> {
+        let token = self.expect(&format!("`{}`", wanted))?;
+        if token != wanted {
+            return Err(self.error(format!("expected `{}`, found `{}`", wanted, token)));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, token: &'a str) -> Result<(), AsmError> {
+        if let Some(x) = self.register(token) {
+            return self.register_statement(x);
+        }
+        match token {
+            ":" => {
+                let name = self.name()?;
+                self.define_label(name, self.here)?;
+            }
+            ":next" => {
+                let name = self.name()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.expect("a register")?;
+                let x = self
+                    .register(reg)
+                    .ok_or_else(|| self.error(format!("expected a register, found `{}`", reg)))?;
+                self.aliases.insert(name, x);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.known()?;
+                self.constants.insert(name, value as f64);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                self.expect_token("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":byte" => match self.value()? {
+                Value::Known(value) => {
+                    let byte = self.checked(value, -128, 255, "byte")?;
+                    self.byte(byte as u8)?;
+                }
+                label => {
+                    self.byte(0)?;
+                    self.patch_value(self.here - 1, label, Patch::Low)?;
+                }
+            },
+            ":org" => {
+                let addr = self.known()?;
+                if !(PROGRAM_OFFSET as i64..MEMORY_SIZE as i64).contains(&addr) {
+                    return Err(self.error(format!("cannot place code at {:#X}", addr)));
+                }
+                self.here = addr as usize;
+            }
+            ":call" => {
+                let target = self.value()?;
+                self.emit_address(Instruction::Call, target)?;
+            }
+            ":unpack" => {
+                let nibble = match self.expect("a nibble or `long`")? {
+                    "long" => None,
+                    token => {
+                        self.tokens.push_front(Token {
+                            text: token,
+                            line: self.line,
+                        });
+                        Some(self.number(0, 15, "nibble")? as u8)
+                    }
+                };
+                let target = self.value()?;
+                let high = match nibble {
+                    Some(nibble) => Patch::Unpack(nibble),
+                    None => Patch::High,
+                };
+                self.emit(Instruction::LdByte { x: 0, kk: 0 })?;
+                self.patch_value(self.here - 1, target, high)?;
+                self.emit(Instruction::LdByte { x: 1, kk: 0 })?;
+                self.patch_value(self.here - 1, target, Patch::Low)?;
+            }
+            ":breakpoint" | ":proto" => {
+                self.expect("a name")?;
+            }
+            ":monitor" => {
+                self.expect("an address")?;
+                self.expect("a length")?;
+            }
+            ";" | "return" => self.emit(Instruction::Ret)?,
+            "clear" => self.emit(Instruction::Cls)?,
+            "exit" => self.emit(Instruction::Exit)?,
+            "hires" => self.emit(Instruction::High)?,
+            "lores" => self.emit(Instruction::Low)?,
+            "scroll-left" => self.emit(Instruction::ScrollLeft)?,
+            "scroll-right" => self.emit(Instruction::ScrollRight)?,
+            "scroll-down" => {
+                let n = self.number(0, 15, "nibble")? as u8;
+                self.emit(Instruction::ScrollDown(n))?;
+            }
+            "scroll-up" => {
+                let n = self.number(0, 15, "nibble")? as u8;
+                self.emit(Instruction::ScrollUp(n))?;
+            }
+            "audio" => self.emit(Instruction::Audio)?,
+            "plane" => {
+                let n = self.number(0, 15, "nibble")? as u8;
+                self.emit(Instruction::Plane(n))?;
+            }
+            "bcd" => {
+                let x = self.expect_register()?;
+                self.emit(Instruction::LdB(x))?;
+            }
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                let instr = if self.peek() == Some("-") {
+                    self.next();
+                    let y = self.expect_register()?;
+                    if token == "save" {
+                        Instruction::SaveRange { x, y }
+                    } else {
+                        Instruction::LoadRange { x, y }
+                    }
+                } else if token == "save" {
+                    Instruction::LdIVx(x)
+                } else {
+                    Instruction::LdVxI(x)
+                };
+                self.emit(instr)?;
+            }
+            "saveflags" => {
+                let x = self.expect_register()?;
+                self.emit(Instruction::LdRVx(x))?;
+            }
+            "loadflags" => {
+                let x = self.expect_register()?;
+                self.emit(Instruction::LdVxR(x))?;
+            }
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n = self.number(0, 15, "nibble")? as u8;
+                self.emit(Instruction::Drw { x, y, n })?;
+            }
+            "jump" => {
+                let target = self.value()?;
+                self.emit_address(Instruction::Jp, target)?;
+            }
+            "jump0" => {
+                let target = self.value()?;
+                self.emit_address(
+                    |nnn| Instruction::JpOffset {
+                        x: (nnn >> 8) as u8,
+                        nnn,
+                    },
+                    target,
+                )?;
+            }
+            "native" => {
+                let target = self.value()?;
+                self.emit_address(Instruction::Sys, target)?;
+            }
+            "i" => self.i_statement()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect_token(":=")?;
+                let x = self.expect_register()?;
+                self.emit(match token {
+                    "delay" => Instruction::LdDtVx(x),
+                    "buzzer" => Instruction::LdStVx(x),
+                    _ => Instruction::Pitch(x),
+                })?;
+            }
+            "if" => {
+                let condition = self.condition()?;
+                match self.expect("`then` or `begin`")? {
+                    "then" => self.emit_guard(&condition)?,
+                    "begin" => {
+                        let jump = self.emit_exit(condition)?;
+                        self.blocks.push(Block::If {
+                            jump,
+                            line: self.line,
+                        });
+                    }
+                    other => {
+                        return Err(
+                            self.error(format!("expected `then` or `begin`, found `{}`", other))
+                        )
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    let skip = self.here;
+                    self.emit(Instruction::Jp(0))?;
+                    self.patch_jump(jump, self.here)?;
+                    self.blocks.push(Block::Else {
+                        jump: skip,
+                        line: self.line,
+                    });
+                }
+                _ => return Err(self.error("`else` without `if ... begin`".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) | Some(Block::Else { jump, .. }) => {
+                    self.patch_jump(jump, self.here)?;
+                }
+                _ => return Err(self.error("`end` without `begin`".to_string())),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                exits: Vec::new(),
+                line: self.line,
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                let exit = self.emit_exit(condition)?;
+                match self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    Some(Block::Loop { exits, .. }) => exits.push(exit),
+                    _ => return Err(self.error("`while` outside a loop".to_string())),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, exits, .. }) => {
+                    self.emit_address(Instruction::Jp, Value::Known(start as i64))?;
+                    for exit in exits {
+                        self.patch_jump(exit, self.here)?;
+                    }
+                }
+                _ => return Err(self.error("`again` without `loop`".to_string())),
+            },
+            _ if self.macros.contains_key(token) => self.expand(token)?,
+            _ => match self.lookup(token) {
+                // a bare number is a byte of data
+                Some(Value::Known(value)) if !self.labels.contains_key(token) => {
+                    let byte = self.checked(value, -128, 255, "byte")?;
+                    self.byte(byte as u8)?;
+                }
+                // and a bare label a subroutine call
+                Some(target) => self.emit_address(Instruction::Call, target)?,
+                None => return Err(self.error(format!("unexpected `{}`", token))),
+            },
+        }
+        Ok(())
+    }
+
+    // `vx` followed by an assignment or arithmetic operator
+    fn register_statement(&mut self, x: u8) -> Result<(), AsmError> {
+        let op = self.expect("an operator")?;
+        let source = self.expect("an operand")?;
+        let y = self.register(source);
+        let instr = match (op, y) {
+            (":=", Some(y)) => Instruction::LdReg { x, y },
+            (":=", None) => match source {
+                "random" => Instruction::Rnd {
+                    x,
+                    kk: self.number(-128, 255, "byte")? as u8,
+                },
+                "delay" => Instruction::LdVxDt(x),
+                "key" => Instruction::LdVxK(x),
+                _ => Instruction::LdByte {
+                    x,
+                    kk: self.byte_operand(source)?,
+                },
+            },
+            ("+=", Some(y)) => Instruction::AddReg { x, y },
+            ("+=", None) => Instruction::AddByte {
+                x,
+                kk: self.byte_operand(source)?,
+            },
+            ("-=", Some(y)) => Instruction::Sub { x, y },
+            // there is no subtract-immediate, so add the negation
+            ("-=", None) => Instruction::AddByte {
+                x,
+                kk: self.byte_operand(source)?.wrapping_neg(),
+            },
+            ("=-", Some(y)) => Instruction::Subn { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::Shr { x, y },
+            ("<<=", Some(y)) => Instruction::Shl { x, y },
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(self.error(format!("expected a register, found `{}`", source)))
+            }
+            _ => return Err(self.error(format!("unknown operator `{}`", op))),
+        };
+        self.emit(instr)
+    }
+
+    // `i := ...` and `i += vx`
+    fn i_statement(&mut self) -> Result<(), AsmError> {
+        match self.expect("an operator")? {
+            ":=" => match self.expect("a value")? {
+                "hex" => {
+                    let x = self.expect_register()?;
+                    self.emit(Instruction::LdF(x))
+                }
+                "bighex" => {
+                    let x = self.expect_register()?;
+                    self.emit(Instruction::LdHf(x))
+                }
+                "long" => {
+                    let target = self.value()?;
+                    self.emit(Instruction::LdILong(0))?;
+                    self.patch_value(self.here - 2, target, Patch::Word)
+                }
+                token => {
+                    let target = self.operand_value(token)?;
+                    self.emit_address(Instruction::LdI, target)
+                }
+            },
+            "+=" => {
+                let x = self.expect_register()?;
+                self.emit(Instruction::AddI(x))
+            }
+            op => Err(self.error(format!("unknown operator `{}` for i", op))),
+        }
+    }
+
+    // `vx == vy`, `vx < 5`, `vx key` and so on, after `if` or `while`
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.expect_register()?;
+        let compare = match self.expect("a comparison")? {
+            "==" => Compare::Eq,
+            "!=" => Compare::Ne,
+            "<" => Compare::Lt,
+            ">" => Compare::Gt,
+            "<=" => Compare::Le,
+            ">=" => Compare::Ge,
+            "key" => Compare::Key,
+            "-key" => Compare::NotKey,
+            other => return Err(self.error(format!("expected a comparison, found `{}`", other))),
+        };
+        let rhs = match compare {
+            Compare::Key | Compare::NotKey => Operand::Reg(x),
+            _ => {
+                let token = self.expect("an operand")?;
+                match self.register(token) {
+                    Some(y) => Operand::Reg(y),
+                    None => Operand::Byte(self.byte_operand(token)?),
+                }
+            }
+        };
+        if x == 0xF
+            && matches!(
+                compare,
+                Compare::Lt | Compare::Gt | Compare::Le | Compare::Ge
+            )
+        {
+            return Err(self.error("vf cannot be compared with < or >".to_string()));
+        }
+        Ok(Condition { x, compare, rhs })
+    }
+
+    // Code after which the next instruction only runs if the condition
+    // holds. The ordering comparisons subtract into VF and test the borrow.
+    fn emit_guard(&mut self, condition: &Condition) -> Result<(), AsmError> {
+        let x = condition.x;
+        match (condition.compare, condition.rhs) {
+            (Compare::Eq, Operand::Byte(kk)) => self.emit(Instruction::SneByte { x, kk }),
+            (Compare::Ne, Operand::Byte(kk)) => self.emit(Instruction::SeByte { x, kk }),
+            (Compare::Eq, Operand::Reg(y)) => self.emit(Instruction::SneReg { x, y }),
+            (Compare::Ne, Operand::Reg(y)) => self.emit(Instruction::SeReg { x, y }),
+            (Compare::Key, _) => self.emit(Instruction::Sknp(x)),
+            (Compare::NotKey, _) => self.emit(Instruction::Skp(x)),
+            (compare, rhs) => {
+                self.emit(match rhs {
+                    Operand::Reg(y) => Instruction::LdReg { x: 0xF, y },
+                    Operand::Byte(kk) => Instruction::LdByte { x: 0xF, kk },
+                })?;
+                // VF is 1 when no borrow: vf -= vx leaves rhs >= vx, vf =- vx
+                // leaves vx >= rhs
+                let (instr, kk) = match compare {
+                    Compare::Gt => (Instruction::Sub { x: 0xF, y: x }, 0),
+                    Compare::Le => (Instruction::Sub { x: 0xF, y: x }, 1),
+                    Compare::Lt => (Instruction::Subn { x: 0xF, y: x }, 0),
+                    _ => (Instruction::Subn { x: 0xF, y: x }, 1),
+                };
+                self.emit(instr)?;
+                self.emit(Instruction::SneByte { x: 0xF, kk })
+            }
+        }
+    }
+
+    // A jump taken when the condition fails, returning its address to patch.
+    fn emit_exit(&mut self, condition: Condition) -> Result<usize, AsmError> {
+        let negated = Condition {
+            compare: condition.compare.negate(),
+            ..condition
+        };
+        self.emit_guard(&negated)?;
+        let jump = self.here;
+        self.emit(Instruction::Jp(0))?;
+        Ok(jump)
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name = self.name()?;
+        let mut args = Vec::new();
+        loop {
+            match self.expect("`{`")? {
+                "{" => break,
+                arg => args.push(arg),
+            }
+        }
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or_else(|| self.error(format!("macro `{}` is missing its `}}`", name)))?;
+            match token.text {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => (),
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { args, body });
+        Ok(())
+    }
+
+    // Replaces a macro invocation by its body, with the arguments substituted.
+    fn expand(&mut self, name: &'a str) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error(format!("macro `{}` expands without end", name)));
+        }
+        let line = self.line;
+        let count = self.macros[name].args.len();
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(self.expect("a macro argument")?);
+        }
+        let definition = &self.macros[name];
+        let expanded: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|token| {
+                let text = match definition.args.iter().position(|arg| *arg == token.text) {
+                    Some(idx) => values[idx],
+                    None => token.text,
+                };
+                // errors inside a macro point at its use
+                Token { text, line }
+            })
+            .collect();
+        for token in expanded.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    // The contents of `{ ... }` up to the closing brace. Like Octo there is no
+    // precedence: operators apply right to left, `2 * 3 + 1` is 8.
+    fn calc(&mut self) -> Result<f64, AsmError> {
+        let mut tokens = Vec::new();
+        loop {
+            match self.expect("`}`")? {
+                "}" => break,
+                token => tokens.push(token),
+            }
+        }
+        let mut pos = 0;
+        let value = self
+            .expression(&tokens, &mut pos)
+            .map_err(|message| self.error(message))?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(self.error(format!("unexpected `{}` in expression", token)));
+        }
+        Ok(value)
+    }
+
+    fn expression(&self, tokens: &[&str], pos: &mut usize) -> Result<f64, String> {
+        let left = self.term(tokens, pos)?;
+        let op = match tokens.get(*pos) {
+            Some(&op) if op != ")" => op,
+            _ => return Ok(left),
+        };
+        *pos += 1;
+        let right = self.expression(tokens, pos)?;
+        let (a, b) = (left as i64, right as i64);
+        Ok(match op {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => (a << b) as f64,
+            ">>" => (a >> b) as f64,
+            "<" => (left < right) as i64 as f64,
+            ">" => (left > right) as i64 as f64,
+            "<=" => (left <= right) as i64 as f64,
+            ">=" => (left >= right) as i64 as f64,
+            "==" => (left == right) as i64 as f64,
+            "!=" => (left != right) as i64 as f64,
+            _ => return Err(format!("unknown operator `{}` in expression", op)),
+        })
+    }
+
+    fn term(&self, tokens: &[&str], pos: &mut usize) -> Result<f64, String> {
+        let token = *tokens
+            .get(*pos)
+            .ok_or_else(|| "expression ends early".to_string())?;
+        *pos += 1;
+        let unary: Option<fn(f64) -> f64> = match token {
+            "-" => Some(|v| -v),
+            "~" => Some(|v| !(v as i64) as f64),
+            "!" => Some(|v| (v == 0.0) as i64 as f64),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.term(tokens, pos)?));
+        }
+        match token {
+            "(" => {
+                let value = self.expression(tokens, pos)?;
+                if tokens.get(*pos) != Some(&")") {
+                    return Err("missing `)` in expression".to_string());
+                }
+                *pos += 1;
+                Ok(value)
+            }
+            // a byte already compiled
+            "@" => {
+                let addr = self.term(tokens, pos)? as i64;
+                Ok(self.memory[addr.rem_euclid(MEMORY_SIZE as i64) as usize] as f64)
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => {
+                if let Some(value) = self.constants.get(token) {
+                    Ok(*value)
+                } else if let Some(addr) = self.labels.get(token) {
+                    Ok(*addr as f64)
+                } else if let Some(value) = parse_number(token) {
+                    Ok(value as f64)
+                } else {
+                    Err(format!("`{}` is not defined yet", token))
+                }
+            }
+        }
+    }
+
+    // a number, constant, label or `{ expression }`
+    fn value(&mut self) -> Result<Value<'a>, AsmError> {
+        let token = self.expect("a value")?;
+        self.operand_value(token)
+    }
+
+    fn operand_value(&mut self, token: &'a str) -> Result<Value<'a>, AsmError> {
+        if token == "{" {
+            return Ok(Value::Known(self.calc()?.floor() as i64));
+        }
+        self.lookup(token)
+            .ok_or_else(|| self.error(format!("expected a value, found `{}`", token)))
+    }
+
+    fn lookup(&self, token: &'a str) -> Option<Value<'a>> {
+        if let Some(value) = parse_number(token) {
+            Some(Value::Known(value))
+        } else if let Some(value) = self.constants.get(token) {
+            Some(Value::Known(value.floor() as i64))
+        } else if let Some(addr) = self.labels.get(token) {
+            Some(Value::Known(*addr as i64))
+        } else if is_name(token) && !KEYWORDS.contains(&token) {
+            Some(Value::Label(token))
+        } else {
+            None
+        }
+    }
+
+    // a value that must be known now, like Octo's constants
+    fn known(&mut self) -> Result<i64, AsmError> {
+        match self.value()? {
+            Value::Known(value) => Ok(value),
+            Value::Label(name) => Err(self.error(format!("`{}` is not defined yet", name))),
+        }
+    }
+
+    fn number(&mut self, min: i64, max: i64, what: &str) -> Result<i64, AsmError> {
+        let value = self.known()?;
+        self.checked(value, min, max, what)
+    }
+
+    fn byte_operand(&mut self, token: &'a str) -> Result<u8, AsmError> {
+        self.tokens.push_front(Token {
+            text: token,
+            line: self.line,
+        });
+        Ok(self.number(-128, 255, "byte")? as u8)
+    }
+
+    fn checked(&self, value: i64, min: i64, max: i64, what: &str) -> Result<i64, AsmError> {
+        if value < min || value > max {
+            return Err(self.error(format!("{} does not fit in a {}", value, what)));
+        }
+        Ok(value & max)
+    }
+
+    fn register(&self, token: &str) -> Option<u8> {
+        if let Some(x) = self.aliases.get(token) {
+            return Some(*x);
+        }
+        let digit = token.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u8, AsmError> {
+        let token = self.expect("a register")?;
+        self.register(token)
+            .ok_or_else(|| self.error(format!("expected a register, found `{}`", token)))
+    }
+
+    // a new label, constant, alias or macro name
+    fn name(&mut self) -> Result<&'a str, AsmError> {
+        let name = self.expect("a name")?;
+        if !is_name(name) || KEYWORDS.contains(&name) || self.register(name).is_some() {
+            return Err(self.error(format!("`{}` can't be used as a name", name)));
+        }
+        Ok(name)
+    }
+
+    fn define_label(&mut self, name: &'a str, addr: usize) -> Result<(), AsmError> {
+        if self.labels.insert(name, addr).is_some() || self.constants.contains_key(name) {
+            return Err(self.error(format!("`{}` is defined twice", name)));
+        }
+        Ok(())
+    }
+
+    fn byte(&mut self, byte: u8) -> Result<(), AsmError> {
+        if self.here >= MEMORY_SIZE {
+            return Err(self.error("program does not fit in memory".to_string()));
+        }
+        self.memory[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    fn emit(&mut self, instr: Instruction) -> Result<(), AsmError> {
+        for byte in instr.encode() {
+            self.byte(byte)?;
+        }
+        Ok(())
+    }
+
+    // an instruction taking a 12-bit address, patched later for a label
+    fn emit_address(
+        &mut self,
+        make: fn(u16) -> Instruction,
+        target: Value<'a>,
+    ) -> Result<(), AsmError> {
+        let addr = self.here;
+        self.emit(make(0))?;
+        self.patch_value(addr, target, Patch::Addr)
+    }
+
+    fn patch_value(&mut self, addr: usize, value: Value<'a>, patch: Patch) -> Result<(), AsmError> {
+        match value {
+            Value::Known(value) => self.apply(addr, value, patch),
+            Value::Label(label) => {
+                self.fixups.push(Fixup {
+                    addr,
+                    label,
+                    patch,
+                    line: self.line,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn patch_jump(&mut self, addr: usize, target: usize) -> Result<(), AsmError> {
+        self.apply(addr, target as i64, Patch::Addr)
+    }
+
+    fn apply(&mut self, addr: usize, value: i64, patch: Patch) -> Result<(), AsmError> {
+        let limit = match patch {
+            Patch::Addr | Patch::Unpack(_) => 0xFFF,
+            Patch::Word | Patch::High | Patch::Low => 0xFFFF,
+        };
+        if !(0..=limit).contains(&value) {
+            return Err(self.error(format!(
+                "address {:#X} is out of reach{}",
+                value,
+                if limit == 0xFFF {
+                    ", use `i := long`"
+                } else {
+                    ""
+                }
+            )));
+        }
+        let value = value as u16;
+        let memory = &mut self.memory;
+        match patch {
+            Patch::Addr => {
+                memory[addr] = (memory[addr] & 0xF0) | (value >> 8) as u8;
+                memory[addr + 1] = value as u8;
+            }
+            Patch::Word => {
+                memory[addr] = (value >> 8) as u8;
+                memory[addr + 1] = value as u8;
+            }
+            Patch::Unpack(nibble) => memory[addr] = (nibble << 4) | (value >> 8) as u8,
+            Patch::High => memory[addr] = (value >> 8) as u8,
+            Patch::Low => memory[addr] = value as u8,
+        }
+        Ok(())
+    }
+
+    // checks every block is closed and resolves labels used before their
+    // definition
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match block {
+                Block::If { line, .. } | Block::Else { line, .. } => {
+                    (line, "`begin` without `end`")
+                }
+                Block::Loop { line, .. } => (line, "`loop` without `again`"),
+            };
+            return Err(AsmError {
+                line: *line,
+                message: message.to_string(),
+            });
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let addr = *self
+                .labels
+                .get(fixup.label)
+                .ok_or_else(|| self.error(format!("`{}` is not defined", fixup.label)))?;
+            self.apply(fixup.addr, addr as i64, fixup.patch)?;
+        }
+        Ok(self.memory[PROGRAM_OFFSET as usize..self.end].to_vec())
+    }
+}
+
+// Octo numbers: decimal, `0x` hex or `0b` binary, optionally negated
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
@@ -0,0 +1,199 @@
+// A small PNG encoder for screenshots, so the core can write images without
+// pulling in an image or compression crate.
+//
+// Images are stored as 8-bit indexed colour with a PLTE chunk. The pixel
+// data is compressed with a single fixed-Huffman deflate block, and matches
+// are only looked for at two distances: the previous byte and the same
+// place one row up. That is nearly all a screenshot needs, because it is
+// made of flat runs and rows repeated by scaling.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Encodes a `width` x `height` image whose pixels, row by row, index into
+// `palette` (at most 256 RGB entries).
+pub fn encode_indexed(width: usize, height: usize, palette: &[[u8; 3]], pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "pixel count");
+    assert!(!palette.is_empty() && palette.len() <= 256, "palette size");
+
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 8, colour type 3 (indexed), deflate, adaptive filtering,
+    // no interlace
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    chunk(&mut png, b"PLTE", &palette.concat());
+
+    // every row starts with its filter type, 0 (none)
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    chunk(&mut png, b"IDAT", &zlib(&raw, width + 1));
+
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Deflate bits go out least significant first.
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are defined most significant bit first.
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+// (base length, extra bits) for length codes 257 to 285
+#[rustfmt::skip]
+const LENGTHS: [(u16, u32); 29] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+    (11, 1), (13, 1), (15, 1), (17, 1), (19, 2), (23, 2), (27, 2), (31, 2),
+    (35, 3), (43, 3), (51, 3), (59, 3), (67, 4), (83, 4), (99, 4), (115, 4),
+    (131, 5), (163, 5), (195, 5), (227, 5), (258, 0),
+];
+
+// (base distance, extra bits) for distance codes 0 to 29
+#[rustfmt::skip]
+const DISTANCES: [(u16, u32); 30] = [
+    (1, 0), (2, 0), (3, 0), (4, 0), (5, 1), (7, 1), (9, 2), (13, 2),
+    (17, 3), (25, 3), (33, 4), (49, 4), (65, 5), (97, 5), (129, 6), (193, 6),
+    (257, 7), (385, 7), (513, 8), (769, 8), (1025, 9), (1537, 9),
+    (2049, 10), (3073, 10), (4097, 11), (6145, 11), (8193, 12), (12289, 12),
+    (16385, 13), (24577, 13),
+];
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_DISTANCE: usize = 32768;
+
+// `data` as a zlib stream. `stride` is the distance back to the same byte
+// one row up.
+fn zlib(data: &[u8], stride: usize) -> Vec<u8> {
+    let mut bits = BitWriter {
+        out: vec![0x78, 0x01],
+        bits: 0,
+        count: 0,
+    };
+    // one final block, fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let best = [1, stride]
+            .into_iter()
+            .filter(|&distance| distance <= pos && distance <= MAX_DISTANCE)
+            .map(|distance| (match_length(data, pos, distance), distance))
+            .max();
+        match best {
+            Some((length, distance)) if length >= MIN_MATCH => {
+                write_match(&mut bits, length, distance);
+                pos += length;
+            }
+            _ => {
+                write_symbol(&mut bits, data[pos] as u16);
+                pos += 1;
+            }
+        }
+    }
+    write_symbol(&mut bits, 256);
+
+    let mut out = bits.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn match_length(data: &[u8], pos: usize, distance: usize) -> usize {
+    (0..MAX_MATCH.min(data.len() - pos))
+        .take_while(|&k| data[pos + k] == data[pos + k - distance])
+        .count()
+}
+
+// a literal byte, end of block (256) or length code (257 to 285)
+fn write_symbol(bits: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTHS
+        .iter()
+        .rposition(|&(base, _)| base as usize <= length)
+        .unwrap();
+    let (base, extra) = LENGTHS[code];
+    write_symbol(bits, 257 + code as u16);
+    bits.write((length - base as usize) as u32, extra);
+
+    let code = DISTANCES
+        .iter()
+        .rposition(|&(base, _)| base as usize <= distance)
+        .unwrap();
+    let (base, extra) = DISTANCES[code];
+    bits.write_code(code as u32, 5);
+    bits.write((distance - base as usize) as u32, extra);
+}
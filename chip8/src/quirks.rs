@@ -0,0 +1,111 @@
+// Behaviours that differ between CHIP-8 interpreters. ROMs are written
+// against one of them, so the interpreter lets the host pick. Every flag is
+// off by default, which is how this emulator has always behaved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    // 8xy6/8xyE shift Vy and store the result in Vx, instead of shifting Vx
+    // in place
+    pub shift_uses_vy: bool,
+    // Fx55/Fx65 leave I pointing just past the last register transferred
+    pub load_store_increments_i: bool,
+    // Bnnn jumps to nnn + Vx, with x taken from the top nibble of nnn,
+    // instead of nnn + V0
+    pub jump_uses_vx: bool,
+    // Dxyn clips sprites at the screen edges instead of wrapping them around
+    pub clip_sprites: bool,
+    // 8xy1/8xy2/8xy3 reset VF to 0
+    pub logic_resets_vf: bool,
+}
+
+pub const PROFILES: [&str; 4] = ["vip", "chip48", "schip", "modern"];
+
+const FLAGS: [&str; 5] = [
+    "shift_uses_vy",
+    "load_store_increments_i",
+    "jump_uses_vx",
+    "clip_sprites",
+    "logic_resets_vf",
+];
+
+impl Quirks {
+    // The original COSMAC VIP interpreter.
+    pub fn vip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators.
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1.
+    pub fn schip() -> Quirks {
+        Quirks::chip48()
+    }
+
+    // What Octo and most present-day interpreters (and XO-CHIP) do.
+    pub fn modern() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+        }
+    }
+
+    pub fn from_profile(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac" | "chip8" => Some(Quirks::vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" | "superchip" => Some(Quirks::schip()),
+            "modern" | "octo" | "xochip" => Some(Quirks::modern()),
+            _ => None,
+        }
+    }
+
+    // Overrides a single flag by name, e.g. `set("clip_sprites", true)`.
+    pub fn set(&mut self, flag: &str, value: bool) -> Result<(), String> {
+        let field = match flag {
+            "shift_uses_vy" => &mut self.shift_uses_vy,
+            "load_store_increments_i" => &mut self.load_store_increments_i,
+            "jump_uses_vx" => &mut self.jump_uses_vx,
+            "clip_sprites" => &mut self.clip_sprites,
+            "logic_resets_vf" => &mut self.logic_resets_vf,
+            _ => {
+                return Err(format!(
+                    "unknown quirk `{}` (expected one of: {})",
+                    flag,
+                    FLAGS.join(", ")
+                ))
+            }
+        };
+        *field = value;
+        Ok(())
+    }
+
+    // Parses an override of the form `flag=on` / `flag=off`.
+    pub fn apply_override(&mut self, spec: &str) -> Result<(), String> {
+        let (flag, value) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("quirk override `{}` must look like flag=on|off", spec))?;
+        let value = match value {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("quirk value `{}` must be on or off", value)),
+        };
+        self.set(flag, value)
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use super::memory::{PAGES, PAGE_SIZE};
+use super::Chip8;
+
+// about ten seconds of frames
+pub const DEFAULT_DEPTH: usize = 600;
+pub const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+// Ring buffer of save states for stepping backwards in time. The caller
+// decides the granularity: the frontend pushes once per frame to rewind
+// gameplay, the debugger once per instruction to step back.
+//
+// Consecutive states differ in a handful of bytes, so only the newest state
+// is kept whole, where it can be restored (and the next delta computed)
+// without replaying anything. Every older one is stored as the XOR against
+// its successor, run-length encoded, and is rebuilt by walking back from the
+// newest; dropping the oldest state just forgets its delta.
+//
+// Memory is most of a state, and most of it never changes, so a machine's
+// memory is kept apart from the rest of its state and only the pages
+// written since the last push are diffed (see `Memory::page_stamp`).
+pub struct Rewind {
+    deltas: VecDeque<Delta>,
+    // newest state, in full: the save state without its memory section, and
+    // the memory that goes back in at `memory_at`. States pushed with
+    // `push_state` have no memory.
+    newest: Vec<u8>,
+    memory: Vec<u8>,
+    memory_at: usize,
+    // the page stamps `memory` was last brought up to date at; empty when
+    // any page may differ
+    stamps: Vec<u64>,
+    // limits
+    depth: usize,
+    max_bytes: usize,
+    // bytes held by `deltas`
+    delta_bytes: usize,
+}
+
+// Turns state i + 1 back into state i (and, XOR being its own inverse,
+// forwards again).
+struct Delta {
+    state: Vec<u8>,
+    // (page, delta) for each page of memory that changed
+    pages: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    // bytes held, counting a couple for each page number
+    fn len(&self) -> usize {
+        self.state.len()
+            + self
+                .pages
+                .iter()
+                .map(|(_, delta)| 2 + delta.len())
+                .sum::<usize>()
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEPTH, DEFAULT_MAX_BYTES)
+    }
+}
+
+impl Rewind {
+    // `depth` is the most states kept; `max_bytes` caps the memory used by
+    // them. Whichever limit is hit first drops the oldest states.
+    pub fn new(depth: usize, max_bytes: usize) -> Rewind {
+        Rewind {
+            deltas: VecDeque::new(),
+            newest: Vec::new(),
+            memory: Vec::new(),
+            memory_at: 0,
+            stamps: Vec::new(),
+            depth: depth.max(1),
+            max_bytes,
+            delta_bytes: 0,
+        }
+    }
+
+    // number of states that can be stepped back through
+    pub fn len(&self) -> usize {
+        if self.newest.is_empty() {
+            0
+        } else {
+            self.deltas.len() + 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // approximate memory held by the buffer
+    pub fn bytes_used(&self) -> usize {
+        self.newest.len() + self.memory.len() + self.delta_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.newest.clear();
+        self.memory.clear();
+        self.memory_at = 0;
+        self.stamps.clear();
+        self.delta_bytes = 0;
+    }
+
+    // Records the machine's current state.
+    pub fn push(&mut self, chip8: &Chip8) {
+        let (state, memory_at) = chip8.save_state_without_memory();
+        let memory = chip8.memory();
+        let stamps: Vec<u64> = (0..PAGES).map(|page| memory.page_stamp(page)).collect();
+        if self.newest.is_empty()
+            || self.newest.len() != state.len()
+            || self.memory.is_empty()
+            || self.memory_at != memory_at
+        {
+            // first state, or the format changed under us: start over
+            self.clear();
+            self.newest = state;
+            self.memory = (0..PAGES)
+                .flat_map(|page| memory.page(page))
+                .copied()
+                .collect();
+            self.memory_at = memory_at;
+            self.stamps = stamps;
+            return;
+        }
+
+        let mut pages = Vec::new();
+        for (page, stamp) in stamps.iter().enumerate() {
+            if self.stamps.get(page) == Some(stamp) {
+                continue;
+            }
+            let kept = &mut self.memory[page * PAGE_SIZE..(page + 1) * PAGE_SIZE];
+            let delta = encode_delta(kept, memory.page(page));
+            if !delta.is_empty() {
+                kept.copy_from_slice(memory.page(page));
+                pages.push((page, delta));
+            }
+        }
+        self.stamps = stamps;
+        self.push_delta(Delta {
+            state: encode_delta(&self.newest, &state),
+            pages,
+        });
+        self.newest = state;
+    }
+
+    // Restores the most recent state before the machine's current one and
+    // forgets it, so repeated calls walk further back. The frontend pushes
+    // right after each frame, so the newest state is often the one on
+    // screen; that one is skipped. Returns false once there is nothing left.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        if self.is_current(chip8) {
+            self.pop_state();
+        }
+        match self.pop_state() {
+            // states come from the same machine, so they always load
+            Some(state) => chip8.load_state(&state).is_ok(),
+            None => false,
+        }
+    }
+
+    // Whether the newest state is the machine's current one.
+    fn is_current(&self, chip8: &Chip8) -> bool {
+        let memory = chip8.memory();
+        if self.stamps.is_empty()
+            || (0..PAGES).any(|page| memory.page_stamp(page) != self.stamps[page])
+        {
+            return false;
+        }
+        let (state, memory_at) = chip8.save_state_without_memory();
+        state == self.newest && memory_at == self.memory_at
+    }
+
+    // Records a state as it is, without splitting out memory; for states
+    // that don't come from a machine.
+    pub fn push_state(&mut self, state: Vec<u8>) {
+        if self.newest.is_empty() || self.newest.len() != state.len() || !self.memory.is_empty() {
+            self.clear();
+            self.memory_at = state.len();
+            self.newest = state;
+            return;
+        }
+        self.push_delta(Delta {
+            state: encode_delta(&self.newest, &state),
+            pages: Vec::new(),
+        });
+        self.newest = state;
+    }
+
+    // Removes and returns the newest state, whole.
+    pub fn pop_state(&mut self) -> Option<Vec<u8>> {
+        if self.newest.is_empty() {
+            return None;
+        }
+        let mut state = self.newest.clone();
+        state.splice(self.memory_at..self.memory_at, self.memory.iter().copied());
+        match self.deltas.pop_back() {
+            Some(delta) => {
+                self.delta_bytes -= delta.len();
+                apply_delta(&mut self.newest, &delta.state);
+                for (page, delta) in &delta.pages {
+                    apply_delta(
+                        &mut self.memory[page * PAGE_SIZE..(page + 1) * PAGE_SIZE],
+                        delta,
+                    );
+                }
+                // the machine's memory no longer matches what is kept
+                self.stamps.clear();
+            }
+            None => self.clear(),
+        }
+        Some(state)
+    }
+
+    fn push_delta(&mut self, delta: Delta) {
+        self.delta_bytes += delta.len();
+        self.deltas.push_back(delta);
+        while self.len() > self.depth || (self.bytes_used() > self.max_bytes && self.len() > 1) {
+            self.drop_oldest();
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(delta) = self.deltas.pop_front() {
+            self.delta_bytes -= delta.len();
+        }
+    }
+}
+
+// Delta encoding: the XOR of two equally sized states as a sequence of
+//   varint  count of unchanged bytes to skip
+//   varint  count of changed bytes that follow
+//   bytes   the XORed changed bytes
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut idx = 0;
+    while idx < to.len() {
+        let start = idx;
+        while idx < to.len() && from[idx] == to[idx] {
+            idx += 1;
+        }
+        let skip = idx - start;
+        let start = idx;
+        while idx < to.len() && from[idx] != to[idx] {
+            idx += 1;
+        }
+        if idx == start {
+            // only an unchanged tail is left; nothing to record
+            break;
+        }
+        write_varint(&mut out, skip);
+        write_varint(&mut out, idx - start);
+        out.extend(
+            from[start..idx]
+                .iter()
+                .zip(&to[start..idx])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+    out
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut idx = 0;
+    while pos < delta.len() {
+        idx += read_varint(delta, &mut pos);
+        let len = read_varint(delta, &mut pos);
+        for (byte, xor) in state[idx..idx + len].iter_mut().zip(&delta[pos..pos + len]) {
+            *byte ^= xor;
+        }
+        idx += len;
+        pos += len;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,46 @@
+// Random number generator behind Cxkk - RND. It is a xorshift64* generator:
+// its whole state is a single u64, so save states can capture it and a
+// restored machine draws the same numbers it would have drawn originally.
+//
+// A new `Chip8` starts from DEFAULT_SEED, so headless runs and tests are
+// reproducible unless the host picks another seed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+pub const DEFAULT_SEED: u64 = 0x5EED_C8C8_5EED_C8C8;
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.set_state(seed);
+        rng
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: u64) {
+        // xorshift never leaves the all-zero state
+        self.state = if state == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            state
+        };
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
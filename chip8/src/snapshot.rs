@@ -0,0 +1,179 @@
+use super::display::Display;
+use super::error::Chip8Error;
+use super::png;
+use super::Chip8;
+
+// A copy of the framebuffer, stored as text so golden images for tests and
+// headless runs can be read, edited and diffed like any other file. Each
+// line is a row and each character a pixel:
+//
+//   .  off
+//   #  on (the first plane)
+//   2  only the second XO-CHIP plane
+//   3  both planes
+//
+// Lines starting with `;` are comments, e.g. what the picture shows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    width: usize,
+    height: usize,
+    // pixel values from 0 to 3, row by row
+    pixels: Vec<u8>,
+}
+
+const PIXELS: [char; 4] = ['.', '#', '2', '3'];
+
+impl Snapshot {
+    pub fn capture(display: &Display) -> Snapshot {
+        Snapshot {
+            width: display.width(),
+            height: display.height(),
+            pixels: display.rows().flatten().copied().collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            text.extend(row.iter().map(|&pixel| PIXELS[pixel as usize & 3]));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Snapshot, String> {
+        let mut snapshot = Snapshot {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        };
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let row = line
+                .chars()
+                .map(|c| PIXELS.iter().position(|&p| p == c).map(|p| p as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| {
+                    format!(
+                        "line {}: pixels are one of `{}`",
+                        idx + 1,
+                        String::from_iter(PIXELS)
+                    )
+                })?;
+            if snapshot.height == 0 {
+                snapshot.width = row.len();
+            } else if row.len() != snapshot.width {
+                return Err(format!(
+                    "line {}: {} pixels wide, the rows above are {}",
+                    idx + 1,
+                    row.len(),
+                    snapshot.width
+                ));
+            }
+            snapshot.pixels.extend(row);
+            snapshot.height += 1;
+        }
+        if snapshot.height == 0 {
+            return Err("no pixels".to_string());
+        }
+        Ok(snapshot)
+    }
+
+    // Ok if `self` matches `expected`. Otherwise says how they differ, with
+    // the picture redrawn and every wrong pixel shown as `!`.
+    pub fn compare(&self, expected: &Snapshot) -> Result<(), String> {
+        if (self.width, self.height) != (expected.width, expected.height) {
+            return Err(format!(
+                "the screen is {}x{}, expected {}x{}",
+                self.width, self.height, expected.width, expected.height
+            ));
+        }
+        let wrong = self
+            .pixels
+            .iter()
+            .zip(&expected.pixels)
+            .filter(|(a, b)| a != b)
+            .count();
+        if wrong == 0 {
+            return Ok(());
+        }
+        let first = self
+            .pixels
+            .iter()
+            .zip(&expected.pixels)
+            .position(|(a, b)| a != b)
+            .unwrap();
+        let mut report = format!(
+            "{} of {} pixels differ, the first at ({}, {}):\n",
+            wrong,
+            self.pixels.len(),
+            first % self.width,
+            first / self.width
+        );
+        for (row, expected_row) in self
+            .pixels
+            .chunks(self.width)
+            .zip(expected.pixels.chunks(self.width))
+        {
+            report.extend(row.iter().zip(expected_row).map(|(&a, &b)| {
+                if a == b {
+                    PIXELS[a as usize & 3]
+                } else {
+                    '!'
+                }
+            }));
+            report.push('\n');
+        }
+        Err(report)
+    }
+
+    // The picture as a PNG, in the colours of `palette` (indexed by pixel
+    // value, as `display::PALETTE`) and with every pixel drawn as a
+    // `scale` x `scale` square.
+    pub fn to_png(&self, palette: [u32; 4], scale: usize) -> Vec<u8> {
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in self.pixels.chunks(self.width) {
+            let scaled: Vec<u8> = row
+                .iter()
+                .flat_map(|&pixel| std::iter::repeat_n(pixel & 3, scale))
+                .collect();
+            for _ in 0..scale {
+                pixels.extend_from_slice(&scaled);
+            }
+        }
+        let colours = palette.map(|rgb| {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            [r, g, b]
+        });
+        png::encode_indexed(width, height, &colours, &pixels)
+    }
+}
+
+// Runs `chip8` for up to `frames` frames, or until the program exits, and
+// returns the screen it leaves behind. Set up input beforehand, e.g. with
+// `backend::ScriptedInput`.
+pub fn run(chip8: &mut Chip8, frames: u64) -> Result<Snapshot, Chip8Error> {
+    for _ in 0..frames {
+        if chip8.is_halted() {
+            break;
+        }
+        chip8.run_frame()?;
+    }
+    Ok(Snapshot::capture(chip8.display()))
+}
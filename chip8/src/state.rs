@@ -0,0 +1,185 @@
+use std::{error::Error, fmt};
+
+// Save states are a small binary format:
+//
+//   magic   "C8SS"
+//   version u16
+//   rom     u64  FNV-1a hash of the ROM the state was made for
+//   body    cpu, memory, display, keyboard, rng and clock, each written by
+//           the component that owns the fields
+//
+// All multi-byte values are little endian. Bump VERSION whenever the body
+// layout changes; older states are then rejected instead of misread.
+pub const MAGIC: &[u8; 4] = b"C8SS";
+pub const VERSION: u16 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    // not a save state at all
+    BadMagic,
+    // written by a different version of the emulator
+    UnsupportedVersion(u16),
+    // made while running a different ROM
+    RomMismatch { expected: u64, found: u64 },
+    // the data ends before the state does
+    Truncated,
+    // a field holds a value the machine can't be in
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a CHIP-8 save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported (expected {})",
+                version, VERSION
+            ),
+            StateError::RomMismatch { expected, found } => write!(
+                f,
+                "save state was made for a different ROM (hash {:016x}, loaded ROM is {:016x})",
+                found, expected
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt(what) => write!(f, "save state is corrupt: bad {}", what),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+// FNV-1a, used to tie a save state to the ROM it was made with.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in rom {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new(rom_hash: u64) -> StateWriter {
+        let mut writer = StateWriter { data: Vec::new() };
+        writer.bytes(MAGIC);
+        writer.u16(VERSION);
+        writer.u64(rom_hash);
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    // bytes written so far
+    pub(crate) fn position(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    // Checks the header against the ROM that is currently loaded.
+    pub fn new(data: &'a [u8], rom_hash: u64) -> Result<StateReader<'a>, StateError> {
+        let mut reader = StateReader { data };
+        if reader
+            .bytes(MAGIC.len())
+            .map_err(|_| StateError::BadMagic)?
+            != MAGIC
+        {
+            return Err(StateError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let found = reader.u64()?;
+        if found != rom_hash {
+            return Err(StateError::RomMismatch {
+                expected: rom_hash,
+                found,
+            });
+        }
+        Ok(reader)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt("flag")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    // Trailing bytes mean the state was not written by this version either.
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(StateError::Corrupt("length"))
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use super::instruction::{Instruction, Syntax};
+use super::memory::AccessKind;
+use super::Chip8;
+
+// Writes one record per executed instruction: how many came before it, PC,
+// the opcode and its mnemonic, the registers it runs with and the memory it
+// writes. Call `record` before each instruction, e.g. from
+// `Chip8::run_frame_until`, and `finish` once done.
+//
+// The text form is one line per record:
+//
+//        0 0200 6A02 LD VA, #02         V 00000000000000000000000000000000 I 0000 SP 0 DT 00 ST 00
+//      186 03BC F633 LD B, V6           V 30300000002A89EC2C30341500000000 I 03E8 SP 0 DT 00 ST 00 W 03E8=01 03E9=03 03EA=07
+//
+// The binary form is the magic `C8TR`, a u16 version, then per record:
+//
+//   cycle u64, pc u16, opcode u16, F000's address u16 (else 0), V0-VF
+//   16 x u8, I u16,
+//   SP u8, DT u8, ST u8, write count u8, then (address u16, value u8) for
+//   each write
+//
+// with multi-byte values little endian, as in save states.
+//
+// Memory writes are taken from the recorded accesses of `Memory`, which the
+// tracer turns on; the debugger's watches use the same list, so the two
+// can't run together.
+pub struct Tracer<W: Write> {
+    out: W,
+    format: TraceFormat,
+    // only instructions at these addresses are written
+    pc_range: RangeInclusive<u16>,
+    // bytes the trace may grow to, and how far it has got
+    size_limit: Option<u64>,
+    written: u64,
+    full: bool,
+    cycle: u64,
+    // the record for the instruction running now; its writes are only known
+    // once it has run
+    pending: Option<Record>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+pub const FORMATS: [&str; 2] = ["text", "binary"];
+
+pub const MAGIC: &[u8; 4] = b"C8TR";
+pub const VERSION: u16 = 1;
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "text" => Some(TraceFormat::Text),
+            "binary" => Some(TraceFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+// The machine as one instruction found it, and what that instruction wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    // the address of XO-CHIP's F000 nnnn, the word after it; 0 for every
+    // other instruction
+    pub operand: u16,
+    pub regs: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    // (address, value), in the order they were written
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl Record {
+    pub fn capture(chip8: &Chip8, cycle: u64) -> Record {
+        let cpu = chip8.cpu();
+        let memory = chip8.memory();
+        let pc = cpu.pc();
+        let word = |addr: u16| {
+            ((memory.peek(addr) as u16) << 8) | memory.peek(addr.wrapping_add(1)) as u16
+        };
+        Record {
+            cycle,
+            pc,
+            opcode: word(pc),
+            operand: match word(pc) {
+                0xF000 => word(pc.wrapping_add(2)),
+                _ => 0,
+            },
+            regs: *cpu.regs(),
+            i: cpu.i(),
+            sp: cpu.sp(),
+            dt: cpu.delay_timer(),
+            st: cpu.sound_timer(),
+            writes: Vec::new(),
+        }
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        Instruction::decode(self.opcode, self.operand)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut line = format!(
+            "{:>8} {:04X} {:04X} {:<18} V ",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            self.instruction().mnemonic(Syntax::Cowgod)
+        );
+        for reg in self.regs {
+            line.push_str(&format!("{:02X}", reg));
+        }
+        line.push_str(&format!(
+            " I {:04X} SP {:X} DT {:02X} ST {:02X}",
+            self.i, self.sp, self.dt, self.st
+        ));
+        if !self.writes.is_empty() {
+            line.push_str(" W");
+            for (address, value) in &self.writes {
+                line.push_str(&format!(" {:04X}={:02X}", address, value));
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(36 + 3 * self.writes.len());
+        data.extend_from_slice(&self.cycle.to_le_bytes());
+        data.extend_from_slice(&self.pc.to_le_bytes());
+        data.extend_from_slice(&self.opcode.to_le_bytes());
+        data.extend_from_slice(&self.operand.to_le_bytes());
+        data.extend_from_slice(&self.regs);
+        data.extend_from_slice(&self.i.to_le_bytes());
+        data.extend_from_slice(&[self.sp, self.dt, self.st]);
+        // no instruction writes anywhere near 255 bytes; drop any excess
+        // rather than corrupt the record
+        let writes = &self.writes[..self.writes.len().min(255)];
+        data.push(writes.len() as u8);
+        for (address, value) in writes {
+            data.extend_from_slice(&address.to_le_bytes());
+            data.push(*value);
+        }
+        data
+    }
+}
+
+// Reads back a trace in either form, telling them apart by the magic.
+pub fn parse(data: &[u8]) -> Result<Vec<Record>, String> {
+    match data.strip_prefix(MAGIC) {
+        Some(body) => parse_binary(body),
+        None => {
+            let text = std::str::from_utf8(data).map_err(|_| "not a trace".to_string())?;
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(idx, line)| {
+                    Record::from_text(line).map_err(|e| format!("line {}: {}", idx + 1, e))
+                })
+                .collect()
+        }
+    }
+}
+
+// Which parts of a record a trace holds besides PC, I and V0-VF. Ours hold
+// them all; other emulators' logs often leave out the opcode, SP, timers
+// or memory writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fields {
+    pub opcode: bool,
+    pub sp: bool,
+    pub dt: bool,
+    pub st: bool,
+    pub writes: bool,
+}
+
+impl Fields {
+    pub const ALL: Fields = Fields {
+        opcode: true,
+        sp: true,
+        dt: true,
+        st: true,
+        writes: true,
+    };
+
+    // the parts both traces hold, i.e. what can be compared
+    pub fn both(self, other: Fields) -> Fields {
+        Fields {
+            opcode: self.opcode && other.opcode,
+            sp: self.sp && other.sp,
+            dt: self.dt && other.dt,
+            st: self.st && other.st,
+            writes: self.writes && other.writes,
+        }
+    }
+}
+
+// Reads a trace in either of our forms or a register dump log (see
+// `parse_log`), telling them apart by the magic and the first field, and
+// says which parts it holds.
+pub fn read(data: &[u8]) -> Result<(Vec<Record>, Fields), String> {
+    if !data.starts_with(MAGIC) {
+        let text = std::str::from_utf8(data).map_err(|_| "not a trace".to_string())?;
+        let first = text.split_whitespace().next().unwrap_or_default();
+        if first.contains([':', '=']) {
+            return parse_log(text);
+        }
+    }
+    Ok((parse(data)?, Fields::ALL))
+}
+
+// Reads the register dump that many emulators log, one line per instruction
+// with the machine as the instruction found it:
+//
+//   PC:0200 OP:6A02 I:0000 V0:00 V1:00 ... VF:00 SP:0 DT:00 ST:00
+//
+// Fields are NAME:VALUE or NAME=VALUE in hex, with or without 0x, in any
+// order or case, split by spaces or commas. PC, I and V0-VF are required;
+// OP (or OPCODE), SP, DT and ST count only if every line has them. CYCLE
+// numbers the lines in decimal, else they count up from 0. These logs don't
+// say what was written to memory, so writes never count.
+pub fn parse_log(text: &str) -> Result<(Vec<Record>, Fields), String> {
+    let mut fields = Fields {
+        writes: false,
+        ..Fields::ALL
+    };
+    let mut records = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cycle = records.len() as u64;
+        let (record, has) =
+            log_line(line, cycle).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+        fields = fields.both(has);
+        records.push(record);
+    }
+    Ok((records, fields))
+}
+
+fn log_line(line: &str, cycle: u64) -> Result<(Record, Fields), String> {
+    let mut record = Record {
+        cycle,
+        ..Record::default()
+    };
+    let mut has = Fields {
+        opcode: false,
+        sp: false,
+        dt: false,
+        st: false,
+        writes: false,
+    };
+    // PC, I, then V0-VF from bit 2 up
+    let mut required = 0u32;
+    for field in line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|field| !field.is_empty())
+    {
+        let (name, value) = field
+            .split_once([':', '='])
+            .ok_or_else(|| format!("bad field `{}`", field))?;
+        let name = name.to_ascii_uppercase();
+        let digits = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .unwrap_or(value);
+        let hex =
+            || u16::from_str_radix(digits, 16).map_err(|_| format!("bad {} `{}`", name, value));
+        let byte =
+            || u8::from_str_radix(digits, 16).map_err(|_| format!("bad {} `{}`", name, value));
+        match name.as_str() {
+            "CYCLE" => {
+                record.cycle = value
+                    .parse()
+                    .map_err(|_| format!("bad cycle `{}`", value))?
+            }
+            "PC" => {
+                record.pc = hex()?;
+                required |= 1;
+            }
+            "I" => {
+                record.i = hex()?;
+                required |= 2;
+            }
+            "OP" | "OPCODE" => {
+                record.opcode = hex()?;
+                has.opcode = true;
+            }
+            "SP" => {
+                record.sp = byte()?;
+                has.sp = true;
+            }
+            "DT" => {
+                record.dt = byte()?;
+                has.dt = true;
+            }
+            "ST" => {
+                record.st = byte()?;
+                has.st = true;
+            }
+            _ => {
+                let x = name
+                    .strip_prefix('V')
+                    .filter(|x| x.len() == 1)
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+                    .ok_or_else(|| format!("unknown field `{}`", field))?;
+                record.regs[x as usize] = byte()?;
+                required |= 4 << x;
+            }
+        }
+    }
+    if required != (4 << 16) - 1 {
+        return Err("expected PC, I and V0-VF".to_string());
+    }
+    Ok((record, has))
+}
+
+fn parse_binary(data: &[u8]) -> Result<Vec<Record>, String> {
+    let data = Cell::new(data);
+    let take = |len: usize| -> Result<&[u8], String> {
+        if data.get().len() < len {
+            return Err("binary trace is truncated".to_string());
+        }
+        let (head, rest) = data.get().split_at(len);
+        data.set(rest);
+        Ok(head)
+    };
+    let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(format!(
+            "trace version {} is not supported (expected {})",
+            version, VERSION
+        ));
+    }
+    let mut records = Vec::new();
+    while !data.get().is_empty() {
+        let cycle = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let word = |bytes: &[u8]| u16::from_le_bytes(bytes.try_into().unwrap());
+        let mut record = Record {
+            cycle,
+            pc: word(take(2)?),
+            opcode: word(take(2)?),
+            operand: word(take(2)?),
+            regs: take(16)?.try_into().unwrap(),
+            i: word(take(2)?),
+            ..Record::default()
+        };
+        let [sp, dt, st, writes] = take(4)?.try_into().unwrap();
+        (record.sp, record.dt, record.st) = (sp, dt, st);
+        for _ in 0..writes {
+            let write = take(3)?;
+            record.writes.push((word(&write[..2]), write[2]));
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+impl Record {
+    // One line of the text form. The mnemonic follows from the opcode, so
+    // only F000's is read, for the address it loads.
+    pub fn from_text(line: &str) -> Result<Record, String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let hex = |text: &str, what: &str| {
+            u16::from_str_radix(text, 16).map_err(|_| format!("bad {} `{}`", what, text))
+        };
+        let byte = |text: &str, what: &str| {
+            u8::from_str_radix(text, 16).map_err(|_| format!("bad {} `{}`", what, text))
+        };
+        let [cycle, pc, opcode, ..] = tokens[..] else {
+            return Err("expected cycle, PC and opcode".to_string());
+        };
+        let mut record = Record {
+            cycle: cycle
+                .parse()
+                .map_err(|_| format!("bad cycle `{}`", cycle))?,
+            pc: hex(pc, "PC")?,
+            opcode: hex(opcode, "opcode")?,
+            ..Record::default()
+        };
+
+        let start = tokens
+            .iter()
+            .skip(3)
+            .position(|token| *token == "V")
+            .ok_or("missing registers (`V ...`)")?
+            + 3;
+        if record.opcode == 0xF000 {
+            // `LD I, LONG #nnnn`
+            let address = tokens[3..start].last().and_then(|t| t.strip_prefix('#'));
+            record.operand = hex(address.unwrap_or_default(), "address")?;
+        }
+        let mut fields = tokens[start..].iter();
+        let mut seen = 0;
+        while let Some(name) = fields.next() {
+            if *name == "W" {
+                for write in fields.by_ref() {
+                    let (address, value) = write
+                        .split_once('=')
+                        .ok_or_else(|| format!("bad write `{}`", write))?;
+                    record
+                        .writes
+                        .push((hex(address, "address")?, byte(value, "value")?));
+                }
+                break;
+            }
+            let value = fields
+                .next()
+                .ok_or_else(|| format!("`{}` has no value", name))?;
+            match *name {
+                "V" => {
+                    if value.len() != 32 {
+                        return Err(format!(
+                            "registers should be 32 hex digits, not `{}`",
+                            value
+                        ));
+                    }
+                    for (x, reg) in record.regs.iter_mut().enumerate() {
+                        *reg = byte(&value[2 * x..2 * x + 2], "register")?;
+                    }
+                }
+                "I" => record.i = hex(value, "I")?,
+                "SP" => record.sp = byte(value, "SP")?,
+                "DT" => record.dt = byte(value, "DT")?,
+                "ST" => record.st = byte(value, "ST")?,
+                _ => return Err(format!("unknown field `{}`", name)),
+            }
+            seen += 1;
+        }
+        if seen < 5 {
+            return Err("expected V, I, SP, DT and ST".to_string());
+        }
+        Ok(record)
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W, format: TraceFormat) -> Tracer<W> {
+        Tracer {
+            out,
+            format,
+            pc_range: 0..=0xFFFF,
+            size_limit: None,
+            written: 0,
+            full: false,
+            cycle: 0,
+            pending: None,
+        }
+    }
+
+    // Leaves out instructions outside `range`; cycles still count them.
+    pub fn set_pc_range(&mut self, range: RangeInclusive<u16>) {
+        self.pc_range = range;
+    }
+
+    // Stops writing before the trace would grow past `bytes`.
+    pub fn set_size_limit(&mut self, bytes: u64) {
+        self.size_limit = Some(bytes);
+    }
+
+    // true once the size limit has cut the trace short
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn record(&mut self, chip8: &Chip8) -> io::Result<()> {
+        self.finish_pending(chip8)?;
+        let memory = chip8.memory();
+        if self.cycle == 0 {
+            memory.record_accesses(true);
+        }
+        if !self.full && self.pc_range.contains(&chip8.cpu().pc()) {
+            self.pending = Some(Record::capture(chip8, self.cycle));
+        }
+        self.cycle += 1;
+        Ok(())
+    }
+
+    // Writes out the last instruction, flushes, and stops the memory access
+    // recording `record` turned on. Call it whenever tracing stops, even
+    // after an error, or every access goes on being recorded.
+    pub fn finish(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let written = self.finish_pending(chip8);
+        chip8.memory().record_accesses(false);
+        written?;
+        self.out.flush()
+    }
+
+    // The writer back, e.g. to read an in-memory trace; call `finish` first.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn finish_pending(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let accesses = chip8.memory().take_accesses();
+        let Some(mut record) = self.pending.take() else {
+            return Ok(());
+        };
+        record.writes = accesses
+            .iter()
+            .filter(|access| matches!(access.kind, AccessKind::Write))
+            .map(|access| (access.address, access.value))
+            .collect();
+
+        let mut data = match self.format {
+            TraceFormat::Text => record.to_text().into_bytes(),
+            TraceFormat::Binary => record.to_binary(),
+        };
+        if self.written == 0 && self.format == TraceFormat::Binary {
+            let mut header = MAGIC.to_vec();
+            header.extend_from_slice(&VERSION.to_le_bytes());
+            data.splice(0..0, header);
+        }
+        if let Some(limit) = self.size_limit {
+            if self.written + data.len() as u64 > limit {
+                self.full = true;
+                return Ok(());
+            }
+        }
+        self.out.write_all(&data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+}
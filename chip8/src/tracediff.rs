@@ -0,0 +1,252 @@
+use super::instruction::Syntax;
+use super::trace::{Fields, Record};
+
+// Compares two traces of the same ROM in this project's format (see
+// `trace`), e.g. from two builds or two quirk profiles run with the same
+// seed and input, and finds the first record where they disagree. Another
+// emulator's register dump log (see `trace::parse_log`) can stand in for
+// either; `diff_only` then leaves out what the log doesn't hold.
+//
+// A record holds the machine as its instruction found it plus what that
+// instruction wrote to memory. So a register, I, SP, timer or PC that
+// differs was set by the instruction before it, while a different write is
+// the instruction's own doing; `Divergence::culprit` says which. Only the
+// instruction that ran right before a record, one cycle earlier, can have
+// set its state; if either trace left it out, the culprit is unknown and
+// `Divergence::last_match` is as close as the traces get.
+
+// How records are paired up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    // first with first, second with second, ...
+    Sequence,
+    // by cycle number, skipping records only one trace has, e.g. when one
+    // was filtered by PC
+    Cycle,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // indices into (ours, theirs) of the first pair that differs
+    pub ours: usize,
+    pub theirs: usize,
+    // what differs, one item per register, byte, ...
+    pub differences: Vec<String>,
+    // indices into (ours, theirs) of the instruction responsible, or None if
+    // that isn't known, e.g. because a trace left it out
+    pub culprit: Option<(usize, usize)>,
+    // indices into (ours, theirs) of the last pair that agreed, or None if
+    // the traces already differ at their first record
+    pub last_match: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Same,
+    // every pair agrees, but one trace has records the other lacks
+    Length { ours: usize, theirs: usize },
+    Diverged(Divergence),
+}
+
+pub fn diff(ours: &[Record], theirs: &[Record], align: Align) -> Outcome {
+    diff_only(ours, theirs, align, Fields::ALL)
+}
+
+// `diff`, comparing `fields` on top of PC, I and V0-VF.
+pub fn diff_only(ours: &[Record], theirs: &[Record], align: Align, fields: Fields) -> Outcome {
+    let pairs = pair_up(ours, theirs, align);
+    for (k, &(a, b)) in pairs.iter().enumerate() {
+        let last_match = (k > 0).then(|| pairs[k - 1]);
+        let (ours_rec, theirs_rec) = (&ours[a], &theirs[b]);
+
+        // what the previous instruction left behind
+        let state = state_differences(ours_rec, theirs_rec, fields);
+        if !state.is_empty() {
+            return Outcome::Diverged(Divergence {
+                ours: a,
+                theirs: b,
+                differences: state,
+                culprit: last_match.filter(|&(c, d)| {
+                    ours[c].cycle + 1 == ours_rec.cycle && theirs[d].cycle + 1 == theirs_rec.cycle
+                }),
+                last_match,
+            });
+        }
+        if fields.opcode && ours_rec.opcode != theirs_rec.opcode {
+            // same PC, different code there: memory at PC was written
+            // differently at some point
+            return Outcome::Diverged(Divergence {
+                ours: a,
+                theirs: b,
+                differences: vec![format!(
+                    "opcode at {:04X}: {:04X} here, {:04X} in the reference",
+                    ours_rec.pc, ours_rec.opcode, theirs_rec.opcode
+                )],
+                culprit: None,
+                last_match,
+            });
+        }
+        // what this instruction did
+        let writes = write_differences(ours_rec, theirs_rec);
+        if fields.writes && !writes.is_empty() {
+            return Outcome::Diverged(Divergence {
+                ours: a,
+                theirs: b,
+                differences: writes,
+                culprit: Some((a, b)),
+                last_match,
+            });
+        }
+    }
+    match align {
+        Align::Sequence if ours.len() != theirs.len() => Outcome::Length {
+            ours: ours.len(),
+            theirs: theirs.len(),
+        },
+        _ => Outcome::Same,
+    }
+}
+
+fn pair_up(ours: &[Record], theirs: &[Record], align: Align) -> Vec<(usize, usize)> {
+    match align {
+        Align::Sequence => (0..ours.len().min(theirs.len())).map(|k| (k, k)).collect(),
+        Align::Cycle => {
+            let mut pairs = Vec::new();
+            let (mut a, mut b) = (0, 0);
+            while a < ours.len() && b < theirs.len() {
+                match ours[a].cycle.cmp(&theirs[b].cycle) {
+                    std::cmp::Ordering::Less => a += 1,
+                    std::cmp::Ordering::Greater => b += 1,
+                    std::cmp::Ordering::Equal => {
+                        pairs.push((a, b));
+                        a += 1;
+                        b += 1;
+                    }
+                }
+            }
+            pairs
+        }
+    }
+}
+
+fn state_differences(ours: &Record, theirs: &Record, fields: Fields) -> Vec<String> {
+    let mut differences = Vec::new();
+    let mut differ = |name: String, ours: String, theirs: String| {
+        if ours != theirs {
+            differences.push(format!(
+                "{}: {} here, {} in the reference",
+                name, ours, theirs
+            ));
+        }
+    };
+    differ("PC".into(), hex4(ours.pc), hex4(theirs.pc));
+    for x in 0..16 {
+        differ(
+            format!("V{:X}", x),
+            hex2(ours.regs[x]),
+            hex2(theirs.regs[x]),
+        );
+    }
+    differ("I".into(), hex4(ours.i), hex4(theirs.i));
+    for (name, compare, ours, theirs) in [
+        ("SP", fields.sp, ours.sp, theirs.sp),
+        ("DT", fields.dt, ours.dt, theirs.dt),
+        ("ST", fields.st, ours.st, theirs.st),
+    ] {
+        if compare {
+            differ(name.into(), hex2(ours), hex2(theirs));
+        }
+    }
+    differences
+}
+
+fn write_differences(ours: &Record, theirs: &Record) -> Vec<String> {
+    let value = |writes: &[(u16, u8)], address: u16| {
+        // the last write wins if an address is written twice
+        writes
+            .iter()
+            .rev()
+            .find(|(a, _)| *a == address)
+            .map_or("nothing".to_string(), |(_, v)| hex2(*v))
+    };
+    let mut addresses: Vec<u16> = ours
+        .writes
+        .iter()
+        .chain(&theirs.writes)
+        .map(|(address, _)| *address)
+        .collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    addresses
+        .into_iter()
+        .filter_map(|address| {
+            let (a, b) = (value(&ours.writes, address), value(&theirs.writes, address));
+            (a != b).then(|| {
+                format!(
+                    "memory {:04X}: wrote {} here, {} in the reference",
+                    address, a, b
+                )
+            })
+        })
+        .collect()
+}
+
+// The divergence spelled out, followed by up to `context` records leading
+// up to it from each trace.
+pub fn report(
+    ours: &[Record],
+    theirs: &[Record],
+    divergence: &Divergence,
+    context: usize,
+) -> String {
+    let record = &ours[divergence.ours];
+    let mut out = format!(
+        "traces diverge at record {} (cycle {}, PC {:04X}):\n",
+        divergence.ours, record.cycle, record.pc
+    );
+    for difference in &divergence.differences {
+        out.push_str(&format!("  {}\n", difference));
+    }
+    let describe = |record: &Record| {
+        format!(
+            "{:04X} {:04X} {} (cycle {})",
+            record.pc,
+            record.opcode,
+            record.instruction().mnemonic(Syntax::Cowgod),
+            record.cycle
+        )
+    };
+    match (divergence.culprit, divergence.last_match) {
+        (Some((a, _)), _) => out.push_str(&format!("caused by {}\n", describe(&ours[a]))),
+        (None, Some((a, _))) => out.push_str(&format!(
+            "cause unknown, the traces last agree at {}\n",
+            describe(&ours[a])
+        )),
+        (None, None) => out.push_str("the traces already disagree before this record\n"),
+    }
+    for (name, records, at) in [
+        ("ours", ours, divergence.ours),
+        ("reference", theirs, divergence.theirs),
+    ] {
+        out.push_str(&format!("\n{}:\n", name));
+        for (idx, record) in records
+            .iter()
+            .enumerate()
+            .take(at + 1)
+            .skip((at + 1).saturating_sub(context))
+        {
+            let marker = if idx == at { '>' } else { ' ' };
+            out.push(marker);
+            out.push_str(&record.to_text());
+        }
+    }
+    out
+}
+
+fn hex2(value: u8) -> String {
+    format!("{:02X}", value)
+}
+
+fn hex4(value: u16) -> String {
+    format!("{:04X}", value)
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::instruction::{Instruction, Syntax};
+
+// What the CPU does with an opcode it has no meaning for: `0nnn` machine
+// code calls, and words no CHIP-8 variant defines (`5xy1`, `9xy4`, `E000`,
+// ...). Skipping them is how this emulator has always behaved, but it hides
+// emulator bugs and broken ROMs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOpcodePolicy {
+    // skip it and carry on
+    #[default]
+    Ignore,
+    // skip it, but report the first time each address runs one
+    Warn,
+    // stop with `Chip8Error::InvalidOpcode`
+    Halt,
+}
+
+pub const POLICIES: [&str; 3] = ["ignore", "warn", "halt"];
+
+impl UnknownOpcodePolicy {
+    pub fn from_name(name: &str) -> Option<UnknownOpcodePolicy> {
+        match name.to_ascii_lowercase().as_str() {
+            "ignore" => Some(UnknownOpcodePolicy::Ignore),
+            "warn" => Some(UnknownOpcodePolicy::Warn),
+            "halt" | "strict" => Some(UnknownOpcodePolicy::Halt),
+            _ => None,
+        }
+    }
+}
+
+// one address that ran an unknown opcode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownOpcode {
+    pub pc: u16,
+    pub opcode: u16,
+    pub count: u32,
+}
+
+// Every unknown opcode executed so far, whatever the policy, by address.
+#[derive(Clone, Debug, Default)]
+pub struct UnknownOpcodes {
+    seen: BTreeMap<u16, UnknownOpcode>,
+    // first sightings not handed out by `take_new` yet
+    new: Vec<UnknownOpcode>,
+}
+
+impl UnknownOpcodes {
+    // Counts one execution of `opcode` at `pc`. Returns true the first time
+    // that address runs an unknown opcode.
+    pub fn record(&mut self, pc: u16, opcode: u16) -> bool {
+        let entry = self.seen.entry(pc).or_insert(UnknownOpcode {
+            pc,
+            opcode,
+            count: 0,
+        });
+        // self-modifying code may put a different one there later
+        entry.opcode = opcode;
+        entry.count = entry.count.saturating_add(1);
+        if entry.count == 1 {
+            self.new.push(*entry);
+        }
+        entry.count == 1
+    }
+
+    // Addresses seen for the first time since the last call.
+    pub fn take_new(&mut self) -> Vec<UnknownOpcode> {
+        std::mem::take(&mut self.new)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UnknownOpcode> {
+        self.seen.values()
+    }
+
+    // One line per address with the opcode, its mnemonic and how often it
+    // ran; empty when there were none.
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            return out;
+        }
+        let total: u32 = self.iter().map(|unknown| unknown.count).sum();
+        let _ = writeln!(
+            out,
+            "{} unknown opcode(s) executed at {} address(es):",
+            total,
+            self.seen.len()
+        );
+        for unknown in self.iter() {
+            let _ = writeln!(
+                out,
+                "  {:#06X}  {:04X}  {:<12} x{}",
+                unknown.pc,
+                unknown.opcode,
+                Instruction::decode(unknown.opcode, 0).mnemonic(Syntax::Cowgod),
+                unknown.count
+            );
+        }
+        out
+    }
+}
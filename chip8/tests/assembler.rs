@@ -0,0 +1,109 @@
+use chip8::asm::assemble;
+use chip8::disasm;
+use chip8::instruction::{Instruction, Syntax};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn every_opcode_round_trips_through_its_mnemonic() {
+    for op in 0..=0xFFFFu16 {
+        let instr = Instruction::decode(op, 0x1234);
+        let bytes = instr.encode();
+        assert_eq!(bytes[..2], op.to_be_bytes(), "{:04X} re-encodes", op);
+
+        let text = instr.mnemonic(Syntax::Cowgod);
+        let assembled = assemble(&text).unwrap_or_else(|e| panic!("`{}`: {}", text, e));
+        assert_eq!(assembled, bytes, "`{}` assembles back", text);
+    }
+}
+
+#[test]
+fn bundled_roms_round_trip_through_the_disassembler() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("../roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let source = disasm::source(&rom, Syntax::Cowgod);
+        let assembled = assemble(&source).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+        assert_eq!(assembled, rom, "{:?}", path);
+    }
+}
+
+#[test]
+fn labels_constants_and_data() {
+    let source = r#"
+        ; bounce a ball
+        SPEED   EQU 2
+        HEIGHT  EQU end - ball          ; constants may refer ahead
+        start:  LD   V0, SPEED
+                LD   I, ball
+                DRW  V0, V1, HEIGHT
+                ADD  V0, -1
+                JP   start
+        ball:   DB   "..XX....", %01111000
+                DB   #30                ; last row
+        end:
+        table:  DW   start, ball + 1
+    "#;
+    let rom = assemble(source).unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x60, 0x02, // LD V0, 2
+            0xA2, 0x0A, // LD I, ball
+            0xD0, 0x13, // DRW V0, V1, 3
+            0x70, 0xFF, // ADD V0, -1
+            0x12, 0x00, // JP start
+            0x30, 0x78, 0x30, // ball
+            0x02, 0x00, 0x02, 0x0B, // table
+        ]
+    );
+}
+
+#[test]
+fn extended_instructions() {
+    let source = "
+        HIGH
+        LD I, LONG #1234
+        LD [I], V2-V5
+        LD V5-V2, [I]
+        PLANE 3
+        SHR V4
+        JP V0, #345
+    ";
+    let rom = assemble(source).unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x00, 0xFF, 0xF0, 0x00, 0x12, 0x34, 0x52, 0x52, 0x55, 0x23, 0xF3, 0x01, 0x84, 0x46,
+            0xB3, 0x45
+        ]
+    );
+}
+
+#[test]
+fn errors_name_the_line() {
+    let cases = [
+        ("CLS\nJP nowhere", 2, "`nowhere` is not defined"),
+        ("LD V0, 256", 1, "256 does not fit in a byte"),
+        ("\n\nFOO V1", 3, "unknown instruction `FOO`"),
+        ("ADD DT, V1", 1, "bad operands for ADD"),
+        ("a: CLS\na: RET", 2, "`a` is defined twice"),
+        ("dt: CLS", 1, "`dt` can't be used as a name"),
+        (
+            "DB \"..X\"",
+            1,
+            "sprite rows are multiples of 8 pixels, `..X` has 3",
+        ),
+        (
+            "X EQU Y\nY EQU X\nJP X",
+            3,
+            "constants refer to each other in a loop",
+        ),
+    ];
+    for (source, line, message) in cases {
+        let err = assemble(source).unwrap_err();
+        assert_eq!(err.line, line, "{}", source);
+        assert!(err.message.starts_with(message), "{}: {}", source, err);
+    }
+}
@@ -0,0 +1,103 @@
+use chip8::audio::{Beeper, SoundState, WavWriter};
+use chip8::backend::AudioSink;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+
+// 500 Hz at 8000 samples per second is a period of exactly 16 samples, and
+// 8000 / 60 puts 133, 133 and then 134 samples in a frame.
+const SAMPLE_RATE: u32 = 8000;
+const PITCH: f32 = 500.0;
+const HEADER_LEN: usize = 44;
+
+fn beeper() -> Beeper {
+    let mut beeper = Beeper::new(SAMPLE_RATE);
+    beeper.set_pitch(PITCH);
+    beeper.set_volume(0.5);
+    beeper
+}
+
+// Records `timers`, one sound timer value per frame, into `out`.
+fn record<W: Write + Seek>(out: W, timers: &[u8]) -> io::Result<()> {
+    let mut wav = WavWriter::new(out, beeper())?;
+    for &timer in timers {
+        wav.update(&SoundState::new(timer));
+    }
+    wav.finish()
+}
+
+fn u32_at(wav: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap())
+}
+
+fn samples(wav: &[u8]) -> Vec<i16> {
+    wav[HEADER_LEN..]
+        .chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+#[test]
+fn sound_timer_plays_a_square_wave() {
+    let mut file = Cursor::new(Vec::new());
+    record(&mut file, &[3, 2, 1, 0]).unwrap();
+    let wav = file.into_inner();
+
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(&wav, 24), SAMPLE_RATE);
+    assert_eq!(&wav[36..40], b"data");
+    let data_len = u32_at(&wav, 40) as usize;
+    assert_eq!(data_len, 2 * (133 + 133 + 134 + 133));
+    assert_eq!(wav.len(), HEADER_LEN + data_len);
+    assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+
+    let samples = samples(&wav);
+    let amplitude = (0.5 * i16::MAX as f32) as i16;
+    let (tone, silence) = samples.split_at(400);
+    for (idx, &sample) in tone.iter().enumerate() {
+        // half a period high, half low
+        let expected = if idx % 16 < 8 { amplitude } else { -amplitude };
+        assert_eq!(sample, expected, "sample {}", idx);
+    }
+    assert!(silence.iter().all(|&sample| sample == 0));
+}
+
+// Takes `capacity` bytes, then fails every write.
+struct FullDisk {
+    file: Cursor<Vec<u8>>,
+    capacity: u64,
+}
+
+impl Write for FullDisk {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.capacity.saturating_sub(self.file.position()) as usize;
+        if room == 0 {
+            return Err(io::Error::other("disk full"));
+        }
+        self.file.write(&buf[..buf.len().min(room)])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FullDisk {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+#[test]
+fn partly_written_frame_is_left_out_of_the_header() {
+    // room for the header, a frame and a half
+    let mut disk = FullDisk {
+        file: Cursor::new(Vec::new()),
+        capacity: (HEADER_LEN + 2 * 200) as u64,
+    };
+    let error = record(&mut disk, &[5; 4]).unwrap_err();
+    assert_eq!(error.to_string(), "disk full");
+
+    let wav = disk.file.into_inner();
+    assert_eq!(u32_at(&wav, 40), 2 * 133, "only the first frame counts");
+    assert_eq!(u32_at(&wav, 4), 36 + 2 * 133);
+}
@@ -0,0 +1,148 @@
+use chip8::audio::SoundState;
+use chip8::backend::{AudioSink, InputSource, TerminalVideo, VideoSink};
+use chip8::display::Display;
+use chip8::Chip8;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Backends that keep what the core hands them, shared with the test.
+#[derive(Clone, Default)]
+struct Recorder<T>(Rc<RefCell<Vec<T>>>);
+
+impl<T: Clone> Recorder<T> {
+    fn taken(&self) -> Vec<T> {
+        self.0.borrow().clone()
+    }
+}
+
+fn lit(display: &Display) -> usize {
+    display
+        .rows()
+        .flatten()
+        .filter(|&&pixel| pixel != 0)
+        .count()
+}
+
+impl VideoSink for Recorder<String> {
+    fn present(&mut self, display: &Display) {
+        self.0.borrow_mut().push(format!("{} lit", lit(display)));
+    }
+}
+
+impl AudioSink for Recorder<u8> {
+    fn update(&mut self, sound: &SoundState) {
+        self.0.borrow_mut().push(sound.timer);
+    }
+}
+
+impl Write for Recorder<u8> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Holds key 5 down from the `from`th poll on.
+struct PressLater {
+    polls: usize,
+    from: usize,
+}
+
+impl InputSource for PressLater {
+    fn poll(&mut self) -> [bool; 16] {
+        self.polls += 1;
+        let mut keys = [false; 16];
+        keys[5] = self.polls >= self.from;
+        keys
+    }
+}
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+}
+
+#[test]
+fn video_is_presented_only_when_the_screen_changes() {
+    let mut chip8 = machine(&[
+        0xA2, 0x12, // LD I, #212
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x61, 0x05, // LD V1, #05
+        0xF1, 0x15, // LD DT, V1
+        0xF1, 0x07, // LD V1, DT
+        0x31, 0x00, // SE V1, #00
+        0x12, 0x08, // JP #208
+        0xD0, 0x01, // DRW V0, V0, 1: and off again
+        0x12, 0x10, // JP #210
+        0x80, // one pixel
+    ]);
+    let video = Recorder::default();
+    chip8.set_video(Box::new(video.clone()));
+    run(&mut chip8, 20);
+    assert_eq!(video.taken(), ["1 lit", "0 lit"]);
+}
+
+#[test]
+fn audio_hears_the_sound_timer_once_a_frame() {
+    let mut chip8 = machine(&[
+        0x60, 0x03, // LD V0, #03
+        0xF0, 0x18, // LD ST, V0
+        0x12, 0x04, // JP #204
+    ]);
+    let audio = Recorder::default();
+    chip8.set_audio(Box::new(audio.clone()));
+    run(&mut chip8, 5);
+    // the timers tick at the end of each frame, before the audio hears them
+    assert_eq!(audio.taken(), [2, 1, 0, 0, 0]);
+}
+
+#[test]
+fn input_is_polled_once_a_frame() {
+    let mut chip8 = machine(&[
+        0x60, 0x05, // LD V0, #05
+        0xE0, 0x9E, // SKP V0
+        0x12, 0x02, // JP #202
+        0xA2, 0x0C, // LD I, #20C
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x12, 0x0A, // JP #20A
+        0x80, // one pixel
+    ]);
+    chip8.set_input(Box::new(PressLater { polls: 0, from: 3 }));
+    run(&mut chip8, 2);
+    assert_eq!(lit(chip8.display()), 0, "waiting for key 5");
+    run(&mut chip8, 1);
+    assert_eq!(lit(chip8.display()), 1);
+}
+
+#[test]
+fn terminal_video_packs_two_rows_into_a_line() {
+    let mut chip8 = machine(&[
+        0xA2, 0x06, // LD I, #206
+        0xD0, 0x03, // DRW V0, V0, 3
+        0x12, 0x04, // JP #204
+        0xC0, 0x80, 0x80, // a corner
+    ]);
+    let out = Recorder::default();
+    chip8.set_video(Box::new(TerminalVideo::new(out.clone())));
+    run(&mut chip8, 1);
+
+    let text = String::from_utf8(out.taken()).unwrap();
+    let frame = text.strip_prefix("\x1B[H").expect("cursor home first");
+    let lines: Vec<&str> = frame.lines().collect();
+    assert_eq!(lines.len(), 16);
+    assert!(lines.iter().all(|line| line.chars().count() == 64));
+    assert!(lines[0].starts_with("█▀ "), "{:?}", lines[0]);
+    assert!(lines[1].starts_with("▀  "), "{:?}", lines[1]);
+}
@@ -0,0 +1,32 @@
+use chip8::octo::compile;
+use chip8::Chip8;
+
+fn machine(source: &str, ips: u32) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&compile(source).unwrap()).unwrap();
+    chip8.set_ips(ips);
+    chip8
+}
+
+#[test]
+fn instructions_per_frame_average_out() {
+    // 90 IPS is one and a half instructions a frame: 1, then 2, then 1, ...
+    let mut chip8 = machine(": main loop v0 += 1 again", 90);
+    let mut counts = Vec::new();
+    for _ in 0..4 {
+        chip8.run_frame().unwrap();
+        counts.push(chip8.cpu().regs()[0]);
+    }
+    // every other instruction is the jump back: add | jump add | jump | add jump
+    assert_eq!(counts, [1, 2, 2, 3]);
+}
+
+#[test]
+fn largest_ips_does_not_overflow_the_clock() {
+    // exits straight away, so the frames are only the clock's arithmetic
+    let mut chip8 = machine(": main exit", u32::MAX);
+    for _ in 0..3 {
+        chip8.run_frame().unwrap();
+    }
+    assert!(chip8.is_halted());
+}
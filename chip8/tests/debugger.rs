@@ -0,0 +1,203 @@
+use chip8::debugger::Debugger;
+use chip8::memory::MEM_SIZE;
+use chip8::octo::compile;
+use chip8::Chip8;
+
+// Counts up in V0, calling `bump` every pass, which counts in V1 and stores
+// V0 at 0x300.
+const COUNTER: &str = "
+    : main
+      loop
+        v0 += 1
+        bump
+      again
+
+    : bump
+      v1 += 1
+      i := 0x300
+      save v0
+      return
+";
+
+fn machine(rom: &[u8]) -> (Chip8, Debugger) {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    (chip8, Debugger::new())
+}
+
+fn counter() -> (Chip8, Debugger) {
+    machine(&compile(COUNTER).unwrap())
+}
+
+// Runs frames until the debugger stops, and returns its report.
+fn run_until_stopped(debugger: &mut Debugger, chip8: &mut Chip8) -> String {
+    for _ in 0..100 {
+        if let Some(report) = debugger.run_frame(chip8) {
+            return report;
+        }
+    }
+    panic!("the debugger never stopped");
+}
+
+fn first_line(report: &str) -> &str {
+    report.lines().next().unwrap_or_default()
+}
+
+fn reg(chip8: &Chip8, x: usize) -> u8 {
+    chip8.cpu().regs()[x]
+}
+
+#[test]
+fn starts_paused_and_steps() {
+    let (mut chip8, mut debugger) = counter();
+    assert!(debugger.is_paused());
+    assert_eq!(debugger.run_frame(&mut chip8), None);
+    assert_eq!(chip8.cpu().pc(), 0x200);
+
+    let report = debugger.execute("s 3", &mut chip8);
+    assert!(report.starts_with("PC=0x0208"), "{}", report);
+    assert_eq!((reg(&chip8, 0), reg(&chip8, 1)), (1, 1));
+    // an empty line repeats the last command, here through the return
+    debugger.execute("", &mut chip8);
+    assert_eq!(chip8.cpu().pc(), 0x204);
+
+    debugger.execute("bs 4", &mut chip8);
+    assert_eq!(chip8.cpu().pc(), 0x206);
+    assert_eq!((reg(&chip8, 0), reg(&chip8, 1)), (1, 0));
+    let report = debugger.execute("back 5", &mut chip8);
+    assert!(
+        report.starts_with("history exhausted after 2"),
+        "{}",
+        report
+    );
+    assert_eq!(chip8.cpu().pc(), 0x200);
+}
+
+#[test]
+fn breakpoints_stop_execution() {
+    let (mut chip8, mut debugger) = counter();
+    assert_eq!(
+        debugger.execute("b 0x208", &mut chip8),
+        "breakpoint at 0x0208"
+    );
+    debugger.execute("c", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "breakpoint at 0x0208");
+    assert_eq!(reg(&chip8, 1), 1);
+
+    // resuming doesn't stop on the same instruction again
+    debugger.execute("c", &mut chip8);
+    run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(reg(&chip8, 1), 2);
+
+    debugger.execute("d 0x208", &mut chip8);
+    debugger.execute("b 0x208 if V0 == 5", &mut chip8);
+    debugger.execute("c", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "breakpoint at 0x0208 if (V0 == 0x5)");
+    assert_eq!(reg(&chip8, 0), 5);
+}
+
+#[test]
+fn next_and_finish_run_whole_subroutines() {
+    let (mut chip8, mut debugger) = counter();
+    debugger.execute("s", &mut chip8);
+    assert_eq!(debugger.execute("n", &mut chip8), "stepping over call");
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "stepped over call, now at 0x0204");
+    assert_eq!(reg(&chip8, 1), 1);
+
+    debugger.execute("s 3", &mut chip8);
+    assert_eq!(chip8.cpu().sp(), 1);
+    debugger.execute("f", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "returned to 0x0204");
+    assert_eq!(reg(&chip8, 1), 2);
+
+    assert_eq!(
+        debugger.execute("f", &mut chip8),
+        "error: not inside a subroutine"
+    );
+}
+
+#[test]
+fn watches_stop_on_memory_and_value_changes() {
+    let (mut chip8, mut debugger) = counter();
+    assert_eq!(
+        debugger.execute("w 0x300", &mut chip8),
+        "watch 1: write of 0x0300"
+    );
+    debugger.execute("c", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "watch 1: wrote 0x01 to 0x0300");
+
+    debugger.execute("uw 1", &mut chip8);
+    assert_eq!(
+        debugger.execute("w V1", &mut chip8),
+        "watch 1: change of V1"
+    );
+    debugger.execute("c", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "watch 1: V1 changed from 0x1 to 0x2");
+
+    debugger.execute("uw 1", &mut chip8);
+    debugger.execute("cond V0 > 9", &mut chip8);
+    debugger.execute("c", &mut chip8);
+    let report = run_until_stopped(&mut debugger, &mut chip8);
+    assert_eq!(first_line(&report), "watch 1: (V0 > 0x9) is true");
+    assert_eq!(reg(&chip8, 0), 10);
+}
+
+#[test]
+fn bad_commands_are_errors() {
+    let (mut chip8, mut debugger) = counter();
+    for (command, error) in [
+        ("b 0x12345", "error: 0x12345 is past the end of memory"),
+        ("d 0x10000", "error: 0x10000 is past the end of memory"),
+        ("x 0x10000", "error: 0x10000 is past the end of memory"),
+        ("w 0x10000", "error: 0x10000 is past the end of memory"),
+        (
+            "w 0x300..0x1FFFF",
+            "error: 0x1FFFF is past the end of memory",
+        ),
+        (
+            "w 0x300..0x2FF",
+            "error: `0x300..0x2FF` ends before it starts",
+        ),
+        ("d 0x300", "error: no breakpoint at 0x0300"),
+        (
+            "b 0x300 when V0",
+            "error: expected `if <expr>` after the address",
+        ),
+        ("s lots", "error: `lots` is not a number"),
+        ("uw 1", "error: no watch 1"),
+        ("w V0 ==", "error: unexpected end of expression"),
+        ("w é", "error: unexpected `é`"),
+        ("cond [I", "error: expected `]`"),
+        (
+            "frobnicate",
+            "error: unknown command `frobnicate` (try `help`)",
+        ),
+    ] {
+        assert_eq!(debugger.execute(command, &mut chip8), error);
+    }
+    assert_eq!(
+        debugger.execute("bl", &mut chip8),
+        "no breakpoints or watches"
+    );
+}
+
+#[test]
+fn next_over_a_call_at_the_top_of_memory() {
+    // loads as far as memory goes, with a CALL in the last two bytes
+    let mut rom = [0x60, 0x00].repeat((MEM_SIZE - 0x200) / 2);
+    let len = rom.len();
+    rom[len - 2..].copy_from_slice(&[0x22, 0x00]);
+    let (mut chip8, mut debugger) = machine(&rom);
+    while chip8.cpu().pc() != 0xFFFE {
+        chip8.step().unwrap();
+    }
+    // the call returns to 0x0000, past the end
+    assert_eq!(debugger.execute("n", &mut chip8), "stepping over call");
+    assert_eq!(debugger.execute("x 0xFFFE 2", &mut chip8), "0xFFFE: 22 00");
+}
@@ -0,0 +1,74 @@
+use chip8::disasm;
+use chip8::instruction::Syntax;
+
+// A call into a subroutine whose skip steps over XO-CHIP's four-byte
+// F000 nnnn, with a sprite between the two that nothing executes and a table
+// after them.
+const ROM: [u8; 21] = [
+    0x00, 0xE0, // CLS
+    0x22, 0x08, // CALL #208
+    0x12, 0x04, // JP #204
+    0xF0, 0x90, // sprite
+    0x30, 0x00, // SE V0, #00
+    0xF0, 0x00, 0x03, 0x00, // LD I, LONG #0300
+    0x00, 0xEE, // RET
+    0x11, 0x22, 0x33, 0x44, 0x55, // table
+];
+
+#[test]
+fn code_is_told_from_data_by_following_every_path() {
+    assert_eq!(
+        disasm::listing(&ROM, Syntax::Cowgod),
+        "\
+0x0200  00 E0         CLS
+0x0202  22 08         CALL #208
+0x0204  12 04         JP #204
+0x0206  F0 90         DB #F0, #90
+0x0208  30 00         SE V0, #00
+0x020A  F0 00 03 00   LD I, LONG #0300
+0x020E  00 EE         RET
+0x0210  11 22 33 44   DB #11, #22, #33, #44
+0x0214  55            DB #55
+"
+    );
+    assert_eq!(
+        disasm::source(&ROM, Syntax::Octo),
+        "\
+clear
+:call 0x208
+jump 0x204
+0xF0 0x90
+if v0 != 0x00 then
+i := long 0x0300
+return
+0x11 0x22 0x33 0x44
+0x55
+"
+    );
+}
+
+#[test]
+fn computed_jumps_and_cut_off_instructions_are_data() {
+    // JP V0, #204 can't be followed, so what it jumps to looks unused
+    let rom = [0xB2, 0x04, 0x00, 0x00, 0x00, 0xE0];
+    assert_eq!(
+        disasm::reachable(&rom).into_iter().collect::<Vec<_>>(),
+        [0x200]
+    );
+    assert_eq!(
+        disasm::listing(&rom, Syntax::Cowgod),
+        "\
+0x0200  B2 04         JP V0, #204
+0x0202  00 00 00 E0   DB #00, #00, #00, #E0
+"
+    );
+
+    // half an instruction at the end of the ROM
+    assert_eq!(
+        disasm::listing(&[0x00, 0xE0, 0x12], Syntax::Cowgod),
+        "\
+0x0200  00 E0         CLS
+0x0202  12            DB #12
+"
+    );
+}
@@ -0,0 +1,167 @@
+use chip8::expr::{BinOp, Expr, MAX_DEPTH};
+use chip8::octo::compile;
+use chip8::Chip8;
+
+fn num(value: i64) -> Box<Expr> {
+    Box::new(Expr::Number(value))
+}
+
+fn bin(op: BinOp, lhs: Box<Expr>, rhs: Box<Expr>) -> Box<Expr> {
+    Box::new(Expr::Binary(op, lhs, rhs))
+}
+
+// V3 = 0x10, VF = 0xFF, I = 0x2FF with 0 0 0 0x10 stored from 0x300 and
+// the delay timer started from 0x10, then one frame run
+fn machine() -> Chip8 {
+    let source = "
+        : main
+          v3 := 0x10
+          vf := 0xFF
+          i := 0x300
+          save v3
+          i := 0x2FF
+          delay := v3
+          loop again
+    ";
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&compile(source).unwrap()).unwrap();
+    chip8.run_frame().unwrap();
+    chip8
+}
+
+fn eval(text: &str) -> i64 {
+    Expr::parse(text)
+        .unwrap_or_else(|e| panic!("{}: {}", text, e))
+        .eval(&machine())
+}
+
+#[test]
+fn operators_bind_loosest_to_tightest() {
+    // || && | ^ & == != < <= > >= + -
+    assert_eq!(
+        Expr::parse("1 || 2 && 3").unwrap(),
+        *bin(BinOp::Or, num(1), bin(BinOp::And, num(2), num(3)))
+    );
+    assert_eq!(
+        Expr::parse("1 | 2 ^ 3 & 4").unwrap(),
+        *bin(
+            BinOp::BitOr,
+            num(1),
+            bin(BinOp::BitXor, num(2), bin(BinOp::BitAnd, num(3), num(4)))
+        )
+    );
+    assert_eq!(
+        Expr::parse("1 == 2 < 3 + 4").unwrap(),
+        *bin(
+            BinOp::Eq,
+            num(1),
+            bin(BinOp::Lt, num(2), bin(BinOp::Add, num(3), num(4)))
+        )
+    );
+    // same level: left to right
+    assert_eq!(
+        Expr::parse("10 - 3 - 2").unwrap(),
+        *bin(BinOp::Sub, bin(BinOp::Sub, num(10), num(3)), num(2))
+    );
+    assert_eq!(
+        Expr::parse("(1 | 2) & 2").unwrap(),
+        *bin(BinOp::BitAnd, bin(BinOp::BitOr, num(1), num(2)), num(2))
+    );
+
+    for (text, value) in [
+        ("10 - 3 - 2", 5),
+        ("1 + 2 == 3 && 4 < 5 || 0", 1),
+        ("2 + 2 != 4", 0),
+        ("6 & 3 ^ 1 | 8", 11),
+        ("!0 + !7", 1),
+        ("-3 + 1", -2),
+        ("0x10 + $10 + #10 + 10", 58),
+        ("3 >= 3 && 3 <= 3 && !(3 > 3)", 1),
+    ] {
+        assert_eq!(eval(text), value, "{}", text);
+    }
+}
+
+#[test]
+fn registers_and_memory_read_the_machine() {
+    for (text, value) in [
+        ("V3", 0x10),
+        ("v3", 0x10),
+        ("VF", 0xFF),
+        ("V0", 0),
+        ("I", 0x2FF),
+        ("PC", 0x20C),
+        ("SP", 0),
+        ("DT", 0x0F),
+        ("ST", 0),
+        ("[0x303]", 0x10),
+        ("[I + 4]", 0x10),
+        ("[[0x303] + 0x2F3]", 0x10),
+        // addresses wrap around 64 KiB
+        ("[0x10303]", 0x10),
+        ("[I - 0x1FFFC]", 0x10),
+        ("V3 == 0x10 && I > 0x200", 1),
+    ] {
+        assert_eq!(eval(text), value, "{}", text);
+    }
+}
+
+#[test]
+fn expressions_print_fully_parenthesised() {
+    let expr = Expr::parse("V3 == 16 && [I + 2] != 0 || !DT").unwrap();
+    assert_eq!(
+        expr.to_string(),
+        "(((V3 == 0x10) && ([(I + 0x2)] != 0x0)) || !DT)"
+    );
+    assert_eq!(Expr::parse(&expr.to_string()).unwrap(), expr);
+}
+
+#[test]
+fn malformed_expressions_are_errors() {
+    for (text, error) in [
+        ("", "unexpected end of expression"),
+        ("1 +", "unexpected end of expression"),
+        ("(1", "expected `)`"),
+        ("[I", "expected `]`"),
+        ("1 2", "unexpected `2`"),
+        (")", "unexpected `)`"),
+        ("VG", "unknown register `VG`"),
+        ("V10", "unknown register `V10`"),
+        ("foo == 1", "unknown register `foo`"),
+        ("V0 == é", "unexpected `é`"),
+        ("€", "unexpected `€`"),
+    ] {
+        assert_eq!(Expr::parse(text), Err(error.to_string()), "{:?}", text);
+    }
+}
+
+#[test]
+fn nesting_is_capped() {
+    let deep = |open: &str, close: &str, depth: usize| {
+        format!("{}V0{}", open.repeat(depth), close.repeat(depth))
+    };
+    for (open, close) in [("(", ")"), ("[", "]"), ("!", ""), ("-", "")] {
+        assert!(
+            Expr::parse(&deep(open, close, MAX_DEPTH)).is_ok(),
+            "{}",
+            open
+        );
+        assert_eq!(
+            Expr::parse(&deep(open, close, MAX_DEPTH + 1)),
+            Err(format!("expression nests more than {} deep", MAX_DEPTH)),
+            "{}",
+            open
+        );
+    }
+
+    // so do long chains of operators, which nest to the left
+    let chain = |len: usize| vec!["1"; len + 1].join(" + ");
+    assert_eq!(eval(&chain(MAX_DEPTH)), MAX_DEPTH as i64 + 1);
+    assert!(Expr::parse(&chain(MAX_DEPTH + 1)).is_err());
+    // chains in separate brackets don't add up
+    let parts: Vec<String> = (0..4).map(|_| format!("({})", chain(40))).collect();
+    assert!(Expr::parse(&parts.join(" && ")).is_ok());
+
+    // a hostile one fails cleanly rather than overflowing the stack
+    assert!(Expr::parse(&"(".repeat(100_000)).is_err());
+}
@@ -0,0 +1,137 @@
+use chip8::backend::{KeyEvent, ScriptedInput};
+use chip8::octo::compile;
+use chip8::Chip8;
+
+// A machine running `source`, fed `script` one frame at a time.
+fn machine(source: &str, script: Vec<(u64, KeyEvent)>) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&compile(source).unwrap()).unwrap();
+    chip8.set_input(Box::new(ScriptedInput::new(script)));
+    chip8
+}
+
+fn run_frames(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+}
+
+fn reg(chip8: &Chip8, x: usize) -> u8 {
+    chip8.cpu().regs()[x]
+}
+
+#[test]
+fn held_key_stays_down_every_frame() {
+    // one pass of the loop per frame: counts the frames key 5 is down
+    let source = "
+        : main
+          v0 := 5
+          loop
+            if v0 key then v1 += 1
+          again
+    ";
+    let mut chip8 = machine(
+        source,
+        vec![(10, KeyEvent::Press(5)), (30, KeyEvent::Release(5))],
+    );
+    chip8.set_ips(3 * chip8::FRAME_RATE);
+    run_frames(&mut chip8, 40);
+    assert_eq!(reg(&chip8, 1), 20);
+}
+
+#[test]
+fn wait_for_key_ends_on_release() {
+    let source = "
+        : main
+          v0 := key
+          v1 := 1
+          loop again
+    ";
+    let mut chip8 = machine(
+        source,
+        vec![(5, KeyEvent::Press(7)), (15, KeyEvent::Release(7))],
+    );
+
+    run_frames(&mut chip8, 10);
+    assert!(chip8.keyboard().is_waiting_for_key());
+    assert_eq!(reg(&chip8, 1), 0, "a press alone doesn't end the wait");
+
+    run_frames(&mut chip8, 5);
+    assert_eq!(reg(&chip8, 1), 0, "still held");
+
+    run_frames(&mut chip8, 1);
+    assert!(!chip8.keyboard().is_waiting_for_key());
+    assert_eq!(reg(&chip8, 0), 7);
+    assert_eq!(reg(&chip8, 1), 1);
+}
+
+#[test]
+fn key_held_before_the_wait_counts_once_released() {
+    let source = "
+        : main
+          v0 := key
+          v1 := 1
+          loop again
+    ";
+    let mut chip8 = machine(
+        source,
+        vec![(0, KeyEvent::Press(0xC)), (3, KeyEvent::Release(0xC))],
+    );
+    run_frames(&mut chip8, 3);
+    assert_eq!(reg(&chip8, 1), 0);
+    run_frames(&mut chip8, 1);
+    assert_eq!(reg(&chip8, 0), 0xC);
+    assert_eq!(reg(&chip8, 1), 1);
+}
+
+#[test]
+fn each_wait_takes_its_own_key_press() {
+    let source = "
+        : main
+          v0 := key
+          v1 := key
+          v2 := 1
+          loop again
+    ";
+    let mut chip8 = machine(
+        source,
+        vec![
+            (2, KeyEvent::Press(0xA)),
+            (4, KeyEvent::Release(0xA)),
+            (12, KeyEvent::Press(0xB)),
+            (14, KeyEvent::Release(0xB)),
+        ],
+    );
+    run_frames(&mut chip8, 10);
+    assert_eq!(reg(&chip8, 0), 0xA);
+    assert_eq!(reg(&chip8, 2), 0, "the second wait is still waiting");
+    assert!(chip8.keyboard().is_waiting_for_key());
+
+    run_frames(&mut chip8, 10);
+    assert_eq!(reg(&chip8, 1), 0xB);
+    assert_eq!(reg(&chip8, 2), 1);
+}
+
+#[test]
+fn wait_survives_a_save_state() {
+    let source = "
+        : main
+          v0 := key
+          v1 := 1
+          loop again
+    ";
+    let mut chip8 = machine(source, vec![(0, KeyEvent::Press(3))]);
+    run_frames(&mut chip8, 2);
+    let state = chip8.save_state();
+
+    // the restored machine has already seen key 3 go down, so its release
+    // alone finishes the wait
+    let mut restored = machine(
+        source,
+        vec![(0, KeyEvent::Press(3)), (1, KeyEvent::Release(3))],
+    );
+    restored.load_state(&state).unwrap();
+    run_frames(&mut restored, 2);
+    assert_eq!(reg(&restored, 0), 3);
+    assert_eq!(reg(&restored, 1), 1);
+}
@@ -0,0 +1,143 @@
+use chip8::instruction::{Instruction, Syntax};
+use chip8::octo::compile;
+use chip8::{disasm, Chip8, PROGRAM_OFFSET};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn every_opcode_round_trips_through_its_octo_mnemonic() {
+    for op in 0..=0xFFFFu16 {
+        let instr = Instruction::decode(op, 0x1234);
+        let text = instr.mnemonic(Syntax::Octo);
+        let compiled = compile(&text).unwrap_or_else(|e| panic!("`{}`: {}", text, e));
+        assert_eq!(compiled, instr.encode(), "`{}` compiles back", text);
+    }
+}
+
+#[test]
+fn bundled_roms_round_trip_through_octo_source() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("../roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let source = disasm::source(&rom, Syntax::Octo);
+        let compiled = compile(&source).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+        assert_eq!(compiled, rom, "{:?}", path);
+    }
+}
+
+#[test]
+fn structured_program_runs_on_the_interpreter() {
+    let source = "
+        :alias total v0
+        :alias n v1
+        :const LIMIT 10
+        :calc DOUBLE { LIMIT * 2 }
+        :macro add-to reg amount { reg += amount }
+
+        : main
+          total := 0
+          n := 1
+          loop
+            while n <= LIMIT
+            total += n
+            n += 1
+          again                     # total is 55
+          if total > 50 begin
+            v2 := DOUBLE
+          else
+            v2 := 1
+          end
+          if total < 50 then v2 := 99
+          add-to v2 3               # 23
+          v3 := 7
+          if v3 >= 7 then v3 += 1   # 8
+          if v3 <= 7 then v3 := 0
+          set-v4                    # defined further down
+          i := result
+          save v4
+          exit
+
+        : set-v4
+          v4 := 0x42
+          return
+
+        : result 0 0 0 0 0
+    ";
+    let rom = compile(source).unwrap();
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+    for _ in 0..60 {
+        if chip8.is_halted() {
+            break;
+        }
+        chip8.run_frame().unwrap();
+    }
+    assert!(chip8.is_halted());
+    assert_eq!(chip8.cpu().regs()[..5], [55, 11, 23, 8, 0x42]);
+
+    let result = PROGRAM_OFFSET + rom.len() as u16 - 5;
+    let saved: Vec<u8> = (result..result + 5)
+        .map(|addr| chip8.memory().peek(addr))
+        .collect();
+    assert_eq!(saved, [55, 11, 23, 8, 0x42]);
+}
+
+#[test]
+fn directives_and_xo_chip_statements() {
+    let source = "
+        :unpack 0xA data
+        i := long data
+        :next operand
+        v5 := 0
+        plane 3
+        audio
+        save v2 - v5
+        pitch := v1
+        jump0 data
+        :org 0x280
+        : data
+        :byte { data >> 4 }
+        :byte -1
+    ";
+    let rom = compile(source).unwrap();
+    assert_eq!(
+        rom[..22],
+        [
+            0x60, 0xA2, 0x61, 0x80, // :unpack 0xA data
+            0xF0, 0x00, 0x02, 0x80, // i := long data
+            0x65, 0x00, // v5 := 0
+            0xF3, 0x01, // plane 3
+            0xF0, 0x02, // audio
+            0x52, 0x52, // save v2 - v5
+            0xF1, 0x3A, // pitch := v1
+            0xB2, 0x80, // jump0 data
+            0x00, 0x00,
+        ]
+    );
+    assert_eq!(rom[0x80..], [0x28, 0xFF]);
+}
+
+#[test]
+fn errors_name_the_line() {
+    let cases = [
+        ("clear\njump nowhere", 2, "`nowhere` is not defined"),
+        ("v0 := 256", 1, "256 does not fit in a byte"),
+        ("\n\nv1 %= v2", 3, "unknown operator `%=`"),
+        (": a clear\n: a return", 2, "`a` is defined twice"),
+        (": loop", 1, "`loop` can't be used as a name"),
+        ("loop\nclear", 1, "`loop` without `again`"),
+        (
+            "if v0 == 1 begin\nelse\nelse",
+            3,
+            "`else` without `if ... begin`",
+        ),
+        ("if vf < 3 then clear", 1, "vf cannot be compared"),
+        (":const A B\n", 1, "`B` is not defined yet"),
+    ];
+    for (source, line, message) in cases {
+        let err = compile(source).unwrap_err();
+        assert_eq!(err.line, line, "{}", source);
+        assert!(err.message.starts_with(message), "{}: {}", source, err);
+    }
+}
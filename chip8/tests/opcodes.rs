@@ -0,0 +1,594 @@
+use chip8::cpu::Cpu;
+use chip8::display::{Display, HIRES_WIDTH, WIDTH};
+use chip8::error::Chip8Error;
+use chip8::instruction::Instruction;
+use chip8::keyboard::Keyboard;
+use chip8::memory::{Memory, BIG_FONT_ADDR, FONT_ADDR};
+use chip8::quirks::Quirks;
+use chip8::rng::Rng;
+use chip8::unknown::UnknownOpcodePolicy;
+use chip8::PROGRAM_OFFSET;
+
+// One opcode at a time against a machine set up by hand: each test puts
+// values in registers, memory or the display, runs an opcode and checks
+// what changed. Registers 1 and 2 stand in for Vx and Vy throughout.
+
+const F: usize = 15;
+const SCRATCH: u16 = 0x300;
+
+struct Machine {
+    cpu: Cpu,
+    memory: Memory,
+    display: Display,
+    keyboard: Keyboard,
+    rng: Rng,
+}
+
+impl Machine {
+    fn new() -> Machine {
+        Machine::with_quirks(Quirks::default())
+    }
+
+    fn with_quirks(quirks: Quirks) -> Machine {
+        Machine {
+            cpu: Cpu::new(quirks),
+            memory: Memory::new(),
+            display: Display::new(),
+            keyboard: Keyboard::new(),
+            rng: Rng::default(),
+        }
+    }
+
+    fn regs(mut self, regs: &[(usize, u8)]) -> Machine {
+        for &(x, value) in regs {
+            self.cpu.set_reg(x, value);
+        }
+        self
+    }
+
+    fn try_run(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        self.cpu.execute(
+            Instruction::decode(opcode, 0),
+            &mut self.memory,
+            &mut self.display,
+            &mut self.keyboard,
+            &mut self.rng,
+        )
+    }
+
+    fn run(&mut self, opcode: u16) {
+        self.try_run(opcode)
+            .unwrap_or_else(|e| panic!("{:04X}: {}", opcode, e));
+    }
+
+    fn reg(&self, x: usize) -> u8 {
+        self.cpu.regs()[x]
+    }
+
+    fn poke(&mut self, address: u16, bytes: &[u8]) {
+        for (idx, byte) in bytes.iter().enumerate() {
+            self.memory.write_byte(address + idx as u16, *byte).unwrap();
+        }
+    }
+
+    fn peek(&self, address: u16, len: u16) -> Vec<u8> {
+        (address..address + len)
+            .map(|addr| self.memory.peek(addr))
+            .collect()
+    }
+
+    // Draws an 8-pixel-wide `sprite` at (x, y) and returns VF.
+    fn draw(&mut self, x: u8, y: u8, sprite: &[u8]) -> u8 {
+        self.poke(SCRATCH, sprite);
+        self.cpu.set_i(SCRATCH);
+        self.cpu.set_reg(1, x);
+        self.cpu.set_reg(2, y);
+        self.run(0xD120 | sprite.len() as u16);
+        self.reg(F)
+    }
+
+    // The lit pixels, as (x, y)
+    fn lit(&self) -> Vec<(usize, usize)> {
+        let mut lit = Vec::new();
+        for y in 0..self.display.height() {
+            for x in 0..self.display.width() {
+                if self.display.read_pixel(x, y) != 0 {
+                    lit.push((x, y));
+                }
+            }
+        }
+        lit
+    }
+}
+
+const NEXT: u16 = PROGRAM_OFFSET + 2;
+const SKIPPED: u16 = PROGRAM_OFFSET + 4;
+
+#[test]
+fn arithmetic_and_logic() {
+    // (opcode, V1, V2, V1 after, VF after); VF starts out as 0xAA so the
+    // ones that leave it alone show up
+    #[rustfmt::skip]
+    let table: &[(u16, u8, u8, u8, u8)] = &[
+        (0x6142, 0x00, 0x00, 0x42, 0xAA), // LD V1, byte
+        (0x7105, 0x10, 0x00, 0x15, 0xAA), // ADD V1, byte
+        (0x7102, 0xFF, 0x00, 0x01, 0xAA), // ...wraps without touching VF
+        (0x8120, 0x00, 0x37, 0x37, 0xAA), // LD V1, V2
+        (0x8121, 0xF0, 0x0F, 0xFF, 0xAA), // OR
+        (0x8122, 0xF3, 0x3F, 0x33, 0xAA), // AND
+        (0x8123, 0xFF, 0x0F, 0xF0, 0xAA), // XOR
+        (0x8124, 0x10, 0x20, 0x30, 0x00), // ADD, no carry
+        (0x8124, 0xFF, 0x01, 0x00, 0x01), // ADD, carry
+        (0x8124, 0xC8, 0x64, 0x2C, 0x01), // 200 + 100 = 44 carry 1
+        (0x8125, 0x32, 0x14, 0x1E, 0x01), // SUB, no borrow
+        (0x8125, 0x14, 0x14, 0x00, 0x01), // SUB, equal: still no borrow
+        (0x8125, 0x14, 0x32, 0xE2, 0x00), // SUB, borrow
+        (0x8126, 0x05, 0x00, 0x02, 0x01), // SHR, bit 0 set
+        (0x8126, 0x04, 0x00, 0x02, 0x00), // SHR, bit 0 clear
+        (0x8127, 0x14, 0x32, 0x1E, 0x01), // SUBN, no borrow
+        (0x8127, 0x32, 0x14, 0xE2, 0x00), // SUBN, borrow
+        (0x812E, 0x81, 0x00, 0x02, 0x01), // SHL, bit 7 set
+        (0x812E, 0x41, 0x00, 0x82, 0x00), // SHL, bit 7 clear
+    ];
+    for &(op, v1, v2, result, vf) in table {
+        let mut machine = Machine::new().regs(&[(1, v1), (2, v2), (F, 0xAA)]);
+        machine.run(op);
+        assert_eq!(
+            (machine.reg(1), machine.reg(F)),
+            (result, vf),
+            "{:04X} with V1 = {:02X}, V2 = {:02X}",
+            op,
+            v1,
+            v2
+        );
+        assert_eq!(machine.reg(2), v2, "{:04X} leaves V2 alone", op);
+        assert_eq!(machine.cpu.pc(), NEXT);
+    }
+}
+
+#[test]
+fn flag_register_as_an_operand() {
+    // (opcode, V1, VF, VF after): with VF as Vx the flag is written last
+    // and wins over the result
+    #[rustfmt::skip]
+    let table: &[(u16, u8, u8, u8)] = &[
+        (0x8F14, 0x64, 0xC8, 0x01), // 200 + 100 carries
+        (0x8F14, 0x01, 0x01, 0x00), // 1 + 1 doesn't
+        (0x8F15, 0x32, 0x14, 0x00), // 20 - 50 borrows
+        (0x8F15, 0x14, 0x32, 0x01),
+        (0x8F17, 0x32, 0x14, 0x01), // 50 - 20 doesn't
+        (0x8F17, 0x14, 0x32, 0x00),
+        (0x8FF6, 0x00, 0x04, 0x00), // bit shifted out of 0x04
+        (0x8FF6, 0x00, 0x03, 0x01),
+        (0x8FFE, 0x00, 0x40, 0x00), // bit shifted out of 0x40
+        (0x8FFE, 0x00, 0x80, 0x01),
+    ];
+    for &(op, v1, vf, result) in table {
+        let mut machine = Machine::new().regs(&[(1, v1), (F, vf)]);
+        machine.run(op);
+        assert_eq!(
+            machine.reg(F),
+            result,
+            "{:04X} with V1 = {:02X}, VF = {:02X}",
+            op,
+            v1,
+            vf
+        );
+    }
+
+    // VF as Vy: the sum uses VF's old value before the carry replaces it
+    let mut machine = Machine::new().regs(&[(1, 0x01), (F, 0xFF)]);
+    machine.run(0x81F4);
+    assert_eq!((machine.reg(1), machine.reg(F)), (0x00, 0x01));
+}
+
+#[test]
+fn quirks_change_shifts_logic_and_jumps() {
+    // shift_uses_vy: Vy is shifted into Vx
+    let mut machine = Machine::with_quirks(Quirks::vip()).regs(&[(1, 0xFF), (2, 0x05)]);
+    machine.run(0x8126);
+    assert_eq!((machine.reg(1), machine.reg(F)), (0x02, 0x01));
+    let mut machine = Machine::with_quirks(Quirks::vip()).regs(&[(1, 0xFF), (2, 0x81)]);
+    machine.run(0x812E);
+    assert_eq!((machine.reg(1), machine.reg(F)), (0x02, 0x01));
+
+    // logic_resets_vf
+    for op in [0x8121, 0x8122, 0x8123] {
+        let mut machine = Machine::with_quirks(Quirks::vip()).regs(&[(F, 0xAA)]);
+        machine.run(op);
+        assert_eq!(machine.reg(F), 0, "{:04X}", op);
+    }
+
+    // Bnnn adds V0, or with jump_uses_vx Vx from the top nibble of nnn
+    let mut machine = Machine::new().regs(&[(0, 0x10), (3, 0x20)]);
+    machine.run(0xB300);
+    assert_eq!(machine.cpu.pc(), 0x310);
+    let mut machine = Machine::with_quirks(Quirks::chip48()).regs(&[(0, 0x10), (3, 0x20)]);
+    machine.run(0xB300);
+    assert_eq!(machine.cpu.pc(), 0x320);
+}
+
+#[test]
+fn skips() {
+    // (opcode, V1, V2, skips)
+    #[rustfmt::skip]
+    let table: &[(u16, u8, u8, bool)] = &[
+        (0x3142, 0x42, 0x00, true),  // SE V1, byte
+        (0x3142, 0x41, 0x00, false),
+        (0x4142, 0x41, 0x00, true),  // SNE V1, byte
+        (0x4142, 0x42, 0x00, false),
+        (0x5120, 0x07, 0x07, true),  // SE V1, V2
+        (0x5120, 0x07, 0x08, false),
+        (0x9120, 0x07, 0x08, true),  // SNE V1, V2
+        (0x9120, 0x07, 0x07, false),
+    ];
+    for &(op, v1, v2, skips) in table {
+        let mut machine = Machine::new().regs(&[(1, v1), (2, v2)]);
+        machine.run(op);
+        let expected = if skips { SKIPPED } else { NEXT };
+        assert_eq!(
+            machine.cpu.pc(),
+            expected,
+            "{:04X} with V1 = {:02X}, V2 = {:02X}",
+            op,
+            v1,
+            v2
+        );
+    }
+
+    // skipping XO-CHIP's four-byte F000 nnnn skips all of it
+    let mut machine = Machine::new().regs(&[(1, 0x42)]);
+    machine.poke(NEXT, &[0xF0, 0x00, 0x12, 0x34]);
+    machine.run(0x3142);
+    assert_eq!(machine.cpu.pc(), NEXT + 4);
+}
+
+#[test]
+fn jumps_calls_and_returns() {
+    let mut machine = Machine::new();
+    machine.run(0x1ABC);
+    assert_eq!(machine.cpu.pc(), 0xABC);
+
+    machine.run(0x2400);
+    assert_eq!((machine.cpu.pc(), machine.cpu.sp()), (0x400, 1));
+    assert_eq!(machine.cpu.stack()[0], 0xABE);
+    machine.run(0x00EE);
+    assert_eq!((machine.cpu.pc(), machine.cpu.sp()), (0xABE, 0));
+
+    assert_eq!(machine.try_run(0x00EE), Err(Chip8Error::StackUnderflow));
+    for _ in 0..16 {
+        machine.run(0x2400);
+    }
+    assert_eq!(machine.try_run(0x2400), Err(Chip8Error::StackOverflow));
+}
+
+#[test]
+fn index_register() {
+    let mut machine = Machine::new().regs(&[(1, 0x10), (2, 0x0A)]);
+    machine.run(0xA123);
+    assert_eq!(machine.cpu.i(), 0x123);
+    machine.run(0xF11E);
+    assert_eq!(machine.cpu.i(), 0x133);
+
+    // Fx1E doesn't touch VF, and I wraps at 16 bits
+    machine.cpu.set_i(0xFFFF);
+    machine.cpu.set_reg(F, 0xAA);
+    machine.run(0xF11E);
+    assert_eq!((machine.cpu.i(), machine.reg(F)), (0x000F, 0xAA));
+
+    // font digits: 5 bytes each, and SUPER-CHIP's big ones 10
+    machine.run(0xF229);
+    assert_eq!(machine.cpu.i(), FONT_ADDR + 0xA * 5);
+    machine.run(0xF230);
+    assert_eq!(machine.cpu.i(), BIG_FONT_ADDR + 0xA * 10);
+
+    // XO-CHIP F000 nnnn loads a 16-bit address and steps over it
+    machine.cpu.set_pc(PROGRAM_OFFSET);
+    machine.poke(PROGRAM_OFFSET, &[0xF0, 0x00, 0xBE, 0xEF]);
+    machine
+        .cpu
+        .execute_next_instruction(
+            &mut machine.memory,
+            &mut machine.display,
+            &mut machine.keyboard,
+            &mut machine.rng,
+        )
+        .unwrap();
+    assert_eq!(
+        (machine.cpu.i(), machine.cpu.pc()),
+        (0xBEEF, PROGRAM_OFFSET + 4)
+    );
+}
+
+#[test]
+fn binary_coded_decimal() {
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (7, [0, 0, 7]),
+        (42, [0, 4, 2]),
+        (100, [1, 0, 0]),
+        (255, [2, 5, 5]),
+    ] {
+        let mut machine = Machine::new().regs(&[(1, value)]);
+        machine.cpu.set_i(SCRATCH);
+        machine.run(0xF133);
+        assert_eq!(machine.peek(SCRATCH, 3), digits, "BCD of {}", value);
+        assert_eq!(machine.cpu.i(), SCRATCH, "I is left alone");
+    }
+
+    // the fonts live below PROGRAM_OFFSET and can't be overwritten
+    let mut machine = Machine::new();
+    machine.cpu.set_i(FONT_ADDR);
+    assert_eq!(
+        machine.try_run(0xF133),
+        Err(Chip8Error::ProtectedWrite { address: FONT_ADDR })
+    );
+}
+
+#[test]
+fn register_store_and_load() {
+    let regs: Vec<(usize, u8)> = (0..16).map(|x| (x, 0x10 + x as u8)).collect();
+    let mut machine = Machine::new().regs(&regs);
+    machine.cpu.set_i(SCRATCH);
+    machine.run(0xF355);
+    assert_eq!(machine.peek(SCRATCH, 5), [0x10, 0x11, 0x12, 0x13, 0x00]);
+    assert_eq!(machine.cpu.i(), SCRATCH);
+
+    machine.poke(SCRATCH, &[0xA0, 0xA1, 0xA2]);
+    machine.run(0xF165);
+    assert_eq!(machine.cpu.regs()[..3], [0xA0, 0xA1, 0x12]);
+
+    // load_store_increments_i leaves I past the last byte
+    let mut machine = Machine::with_quirks(Quirks::vip()).regs(&regs);
+    machine.cpu.set_i(SCRATCH);
+    machine.run(0xF355);
+    assert_eq!(machine.cpu.i(), SCRATCH + 4);
+    machine.run(0xF065);
+    assert_eq!(machine.cpu.i(), SCRATCH + 5);
+
+    // XO-CHIP ranges, either way round, never move I
+    let mut machine = Machine::new().regs(&regs);
+    machine.cpu.set_i(SCRATCH);
+    machine.run(0x5352);
+    assert_eq!(machine.peek(SCRATCH, 3), [0x13, 0x14, 0x15]);
+    machine.run(0x5532);
+    assert_eq!(machine.peek(SCRATCH, 3), [0x15, 0x14, 0x13]);
+    machine.run(0x5893);
+    assert_eq!((machine.reg(8), machine.reg(9)), (0x15, 0x14));
+    assert_eq!(machine.cpu.i(), SCRATCH);
+
+    // SUPER-CHIP RPL flags
+    machine.run(0xF275);
+    assert_eq!(machine.cpu.rpl()[..3], [0x10, 0x11, 0x12]);
+    machine.cpu.set_reg(0, 0);
+    machine.run(0xF085);
+    assert_eq!(machine.reg(0), 0x10);
+}
+
+#[test]
+fn random_numbers_are_masked() {
+    let mut machine = Machine::new();
+    for _ in 0..100 {
+        machine.run(0xC10F);
+        assert_eq!(machine.reg(1) & 0xF0, 0);
+    }
+    machine.run(0xC100);
+    assert_eq!(machine.reg(1), 0);
+}
+
+#[test]
+fn sprites_collide_wrap_and_clip() {
+    let mut machine = Machine::new();
+    assert_eq!(machine.draw(10, 5, &[0xC0]), 0);
+    assert_eq!(machine.lit(), [(10, 5), (11, 5)]);
+
+    // drawing over a lit pixel erases it and sets VF
+    assert_eq!(machine.draw(11, 5, &[0x80]), 1);
+    assert_eq!(machine.lit(), [(10, 5)]);
+    // ...but a sprite that only lights pixels clears VF again
+    assert_eq!(machine.draw(0, 0, &[0x80]), 0);
+
+    // past the right and bottom edges the sprite wraps around
+    let mut machine = Machine::new();
+    machine.draw(62, 31, &[0xF0, 0x80]);
+    assert_eq!(
+        machine.lit(),
+        [(62, 0), (0, 31), (1, 31), (62, 31), (63, 31)]
+    );
+
+    // with clip_sprites it is cut off instead
+    let mut machine = Machine::with_quirks(Quirks::vip());
+    machine.draw(62, 31, &[0xF0, 0x80]);
+    assert_eq!(machine.lit(), [(62, 31), (63, 31)]);
+
+    // the starting position wraps either way
+    let mut machine = Machine::with_quirks(Quirks::vip());
+    machine.draw(WIDTH as u8 + 3, 32 + 2, &[0x80]);
+    assert_eq!(machine.lit(), [(3, 2)]);
+
+    // 00E0 clears it all
+    machine.run(0x00E0);
+    assert!(machine.lit().is_empty());
+}
+
+#[test]
+fn super_chip_display() {
+    let mut machine = Machine::new();
+    machine.run(0x00FF);
+    assert_eq!(machine.display.width(), HIRES_WIDTH);
+
+    // Dxy0 draws 16x16 from 32 bytes
+    let mut sprite = [0u8; 32];
+    sprite[0] = 0x80;
+    sprite[31] = 0x01;
+    machine.poke(SCRATCH, &sprite);
+    machine.cpu.set_i(SCRATCH);
+    machine.run(0xD120);
+    assert_eq!(machine.lit(), [(0, 0), (15, 15)]);
+
+    machine.run(0x00C3); // down 3
+    assert_eq!(machine.lit(), [(0, 3), (15, 18)]);
+    machine.run(0x00FB); // right 4
+    assert_eq!(machine.lit(), [(4, 3), (19, 18)]);
+    machine.run(0x00FC); // left 4
+    machine.run(0x00D3); // up 3 (XO-CHIP)
+    assert_eq!(machine.lit(), [(0, 0), (15, 15)]);
+
+    // switching resolution clears the screen
+    machine.run(0x00FE);
+    assert_eq!(machine.display.width(), WIDTH);
+    assert!(machine.lit().is_empty());
+
+    machine.run(0x00FD);
+    assert!(machine.cpu.is_halted());
+}
+
+#[test]
+fn xo_chip_planes_and_audio() {
+    let mut machine = Machine::new();
+    // plane 2 only: the sprite lands on the second bit-plane
+    machine.run(0xF201);
+    machine.draw(0, 0, &[0x80]);
+    assert_eq!(machine.display.read_pixel(0, 0), 2);
+    // with both selected each plane reads a sprite of its own, one after
+    // the other: the first plane's lights (0, 0), the second's (1, 0)
+    machine.run(0xF301);
+    machine.poke(SCRATCH, &[0x80, 0x40]);
+    machine.run(0xD121);
+    assert_eq!(machine.display.read_pixel(0, 0), 3);
+    assert_eq!(machine.display.read_pixel(1, 0), 2);
+
+    machine.poke(SCRATCH, &[0x55; 16]);
+    machine.cpu.set_i(SCRATCH);
+    machine.run(0xF002);
+    machine.cpu.set_reg(1, 0x70);
+    machine.run(0xF13A);
+    let sound = machine.cpu.sound();
+    assert_eq!((sound.pattern, sound.pitch), (Some([0x55; 16]), 0x70));
+}
+
+#[test]
+fn timers() {
+    let mut machine = Machine::new().regs(&[(1, 3), (2, 2)]);
+    machine.run(0xF115);
+    machine.run(0xF218);
+    assert_eq!(
+        (machine.cpu.delay_timer(), machine.cpu.sound_timer()),
+        (3, 2)
+    );
+    for _ in 0..5 {
+        machine.cpu.tick_timers();
+    }
+    assert_eq!(
+        (machine.cpu.delay_timer(), machine.cpu.sound_timer()),
+        (0, 0)
+    );
+
+    machine.cpu.set_delay_timer(0x42);
+    machine.run(0xF307);
+    assert_eq!(machine.reg(3), 0x42);
+}
+
+#[test]
+fn keys() {
+    let mut keys = [false; 16];
+    keys[0xB] = true;
+    // (opcode, key in V1, skips)
+    for (op, key, skips) in [
+        (0xE19E, 0xB, true),
+        (0xE19E, 0xC, false),
+        (0xE1A1, 0xB, false),
+        (0xE1A1, 0xC, true),
+    ] {
+        let mut machine = Machine::new().regs(&[(1, key)]);
+        machine.keyboard.set_keys(keys);
+        machine.run(op);
+        let expected = if skips { SKIPPED } else { NEXT };
+        assert_eq!(machine.cpu.pc(), expected, "{:04X} with key {:X}", op, key);
+    }
+
+    // Fx0A stays put until a key goes down and comes back up
+    let mut machine = Machine::new();
+    machine.run(0xF10A);
+    assert_eq!(machine.cpu.pc(), PROGRAM_OFFSET);
+    machine.keyboard.set_keys(keys);
+    machine.run(0xF10A);
+    assert_eq!(machine.cpu.pc(), PROGRAM_OFFSET);
+    machine.keyboard.set_keys([false; 16]);
+    machine.run(0xF10A);
+    assert_eq!((machine.cpu.pc(), machine.reg(1)), (NEXT, 0xB));
+}
+
+#[test]
+fn unknown_opcodes_follow_the_policy() {
+    let mut machine = Machine::new();
+    machine.run(0x0123);
+    machine.run(0x5121);
+    assert_eq!(machine.cpu.pc(), PROGRAM_OFFSET + 4);
+    assert_eq!(machine.cpu.unknown_opcodes().iter().count(), 2);
+
+    let mut machine = Machine::new();
+    machine
+        .cpu
+        .set_unknown_opcode_policy(UnknownOpcodePolicy::Halt);
+    assert_eq!(
+        machine.try_run(0xE1FF),
+        Err(Chip8Error::InvalidOpcode {
+            pc: PROGRAM_OFFSET,
+            instr: 0xE1FF
+        })
+    );
+    assert_eq!(machine.cpu.pc(), PROGRAM_OFFSET);
+    // the error reports it, so it isn't counted as well
+    assert_eq!(machine.cpu.unknown_opcodes().iter().count(), 0);
+}
+
+#[test]
+fn failing_instructions_change_nothing() {
+    // each of these runs off the end of memory partway through
+    for (op, i) in [
+        (0xF355, 0xFFFE), // LD [I], V3
+        (0xF365, 0xFFFE), // LD V3, [I]
+        (0x5142, 0xFFFE), // LD [I], V1-V4
+        (0x5413, 0xFFFE), // LD V4-V1, [I]
+        (0xF133, 0xFFFF), // LD B, V1
+        (0xD125, 0xFFFE), // DRW V1, V2, 5
+    ] {
+        let regs: Vec<(usize, u8)> = (0..16).map(|x| (x, 0x10 + x as u8)).collect();
+        let mut machine = Machine::new().regs(&regs);
+        machine.draw(0, 0, &[0x80]);
+        machine.cpu.set_reg(F, 0xAA);
+        machine.poke(0xFFFE, &[0xEE, 0xEE]);
+        machine.cpu.set_i(i);
+        let (pc, regs) = (machine.cpu.pc(), *machine.cpu.regs());
+
+        assert_eq!(
+            machine.try_run(op),
+            Err(Chip8Error::MemoryOutOfBounds { address: 0x10000 }),
+            "{:04X}",
+            op
+        );
+        assert_eq!(machine.cpu.pc(), pc, "{:04X}", op);
+        assert_eq!(*machine.cpu.regs(), regs, "{:04X}", op);
+        assert_eq!(machine.cpu.i(), i, "{:04X}", op);
+        assert_eq!(
+            [machine.memory.peek(0xFFFE), machine.memory.peek(0xFFFF)],
+            [0xEE, 0xEE],
+            "{:04X}",
+            op
+        );
+        assert_eq!(machine.lit(), [(0, 0)], "{:04X}", op);
+    }
+
+    // nor does one whose next instruction would be past the end
+    let mut machine = Machine::new();
+    machine.cpu.set_pc(0xFFFE);
+    assert_eq!(
+        machine.try_run(0x6155),
+        Err(Chip8Error::MemoryOutOfBounds { address: 0x10000 })
+    );
+    assert_eq!((machine.cpu.pc(), machine.reg(1)), (0xFFFE, 0));
+    // though a jump from there is fine
+    machine.run(0x1200);
+    assert_eq!(machine.cpu.pc(), PROGRAM_OFFSET);
+}
@@ -0,0 +1,155 @@
+use chip8::display::PALETTE;
+use chip8::octo::compile;
+use chip8::snapshot::{self, Snapshot};
+use chip8::Chip8;
+
+// The encoder only ever writes one fixed-Huffman deflate block, so that is
+// all this reader understands. It returns the chunks of `png`, after
+// checking each CRC, with IDAT inflated.
+fn read_png(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(
+        png[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+    );
+    let mut chunks = Vec::new();
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = &rest[8..8 + len];
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(crc, crc32(&rest[4..8 + len]), "CRC of {:?}", kind);
+        let data = match &kind {
+            b"IDAT" => inflate(data),
+            _ => data.to_vec(),
+        };
+        chunks.push((kind, data));
+        rest = &rest[12 + len..];
+    }
+    chunks
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn inflate(zlib: &[u8]) -> Vec<u8> {
+    #[rustfmt::skip]
+    const LENGTHS: [(usize, u32); 29] = [
+        (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+        (11, 1), (13, 1), (15, 1), (17, 1), (19, 2), (23, 2), (27, 2), (31, 2),
+        (35, 3), (43, 3), (51, 3), (59, 3), (67, 4), (83, 4), (99, 4), (115, 4),
+        (131, 5), (163, 5), (195, 5), (227, 5), (258, 0),
+    ];
+    let mut pos = 16; // bits: past the zlib header
+    let mut bit = |count: u32| {
+        let mut value = 0;
+        for k in 0..count {
+            value |= ((zlib[pos / 8] >> (pos % 8)) as u32 & 1) << k;
+            pos += 1;
+        }
+        value
+    };
+    assert_eq!((bit(1), bit(2)), (1, 1), "one final fixed-Huffman block");
+
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+        // Huffman codes arrive most significant bit first
+        let mut code = (0..7).fold(0, |code, _| (code << 1) | bit(1));
+        let symbol = if code < 0x18 {
+            256 + code
+        } else {
+            code = (code << 1) | bit(1);
+            match code {
+                0x30..=0xBF => code - 0x30,
+                0xC0..=0xC7 => 280 + code - 0xC0,
+                _ => 144 + ((code << 1) | bit(1)) - 0x190,
+            }
+        };
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => break,
+            _ => {
+                let (base, extra) = LENGTHS[symbol as usize - 257];
+                let length = base + bit(extra) as usize;
+                let code = (0..5).fold(0, |code, _| (code << 1) | bit(1));
+                let extra = code.saturating_sub(2) / 2;
+                let base = if code < 4 {
+                    code + 1
+                } else {
+                    (2 + code % 2) << extra | 1
+                };
+                let distance = (base + bit(extra)) as usize;
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn screenshot_round_trips_through_the_encoder() {
+    let mut chip8 = Chip8::new();
+    let source = "
+        : main
+          v0 := 3
+          v1 := 30
+          i := hex v0
+          sprite v0 v1 5
+          loop again
+    ";
+    chip8.load_rom(&compile(source).unwrap()).unwrap();
+    let screen = snapshot::run(&mut chip8, 2).unwrap();
+
+    for scale in [1, 3] {
+        let chunks = read_png(&screen.to_png(PALETTE, scale));
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+
+        let header = &chunks[0].1;
+        let (width, height) = (64 * scale, 32 * scale);
+        assert_eq!(header[..4], (width as u32).to_be_bytes());
+        assert_eq!(header[4..8], (height as u32).to_be_bytes());
+        assert_eq!(header[8..], [8, 3, 0, 0, 0], "8-bit indexed colour");
+        assert_eq!(
+            chunks[1].1,
+            [0, 0, 0, 0x4C, 0xD1, 0x37, 0xE8, 0x41, 0x18, 0xFB, 0xC5, 0x31]
+        );
+
+        // each row: filter type 0, then the palette index of every pixel
+        let data = &chunks[2].1;
+        assert_eq!(data.len(), (width + 1) * height);
+        for y in 0..height {
+            let row = &data[y * (width + 1)..(y + 1) * (width + 1)];
+            assert_eq!(row[0], 0);
+            for x in 0..width {
+                assert_eq!(
+                    row[1 + x],
+                    screen.pixel(x / scale, y / scale),
+                    "({}, {}) at scale {}",
+                    x,
+                    y,
+                    scale
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn flat_screens_compress_well() {
+    let blank = Snapshot::from_text(&(".".repeat(64) + "\n").repeat(32)).unwrap();
+    let png = blank.to_png(PALETTE, 10);
+    // 200 KiB of pixel data; a match covers at most 258 bytes of it
+    assert!(png.len() < 4096, "{} bytes", png.len());
+    assert_eq!(read_png(&png)[2].1, vec![0; 641 * 320]);
+}
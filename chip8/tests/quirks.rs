@@ -0,0 +1,191 @@
+use chip8::cpu::Cpu;
+use chip8::display::Display;
+use chip8::keyboard::Keyboard;
+use chip8::memory::Memory;
+use chip8::quirks::{Quirks, PROFILES};
+use chip8::rng::Rng;
+
+// Runs `steps` instructions of `rom` under `quirks`. The programs store
+// what they found from 0x300, or draw it.
+fn run(quirks: Quirks, rom: &[u8], steps: usize) -> (Memory, Display) {
+    let mut cpu = Cpu::new(quirks);
+    let mut memory = Memory::new();
+    let mut display = Display::new();
+    let mut keyboard = Keyboard::new();
+    let mut rng = Rng::new(0);
+    for (idx, byte) in rom.iter().enumerate() {
+        memory.write_byte(0x200 + idx as u16, *byte).unwrap();
+    }
+    for _ in 0..steps {
+        cpu.execute_next_instruction(&mut memory, &mut display, &mut keyboard, &mut rng)
+            .unwrap();
+    }
+    (memory, display)
+}
+
+// Which quirks a machine runs with, as the programs see them.
+fn observed(quirks: Quirks) -> Quirks {
+    let shifted = run(
+        quirks,
+        &[
+            0x60, 0xFF, // LD V0, #FF
+            0x61, 0x04, // LD V1, #04
+            0x80, 0x16, // SHR V0, V1
+            0xA3, 0x00, // LD I, #300
+            0xF0, 0x55, // LD [I], V0
+        ],
+        5,
+    )
+    .0
+    .read_byte(0x300);
+
+    // the second store lands one further on if the first moved I
+    let stored = run(
+        quirks,
+        &[
+            0x60, 0x07, // LD V0, #07
+            0xA3, 0x00, // LD I, #300
+            0xF0, 0x55, // LD [I], V0
+            0xF0, 0x55, // LD [I], V0
+        ],
+        4,
+    )
+    .0
+    .read_byte(0x301);
+
+    // lands on LD V3, #02 if V2 was added instead of V0
+    let jumped = run(
+        quirks,
+        &[
+            0x62, 0x04, // LD V2, #04
+            0xB2, 0x04, // JP V0, #204
+            0x63, 0x01, // LD V3, #01
+            0x12, 0x0A, // JP #20A
+            0x63, 0x02, // LD V3, #02
+            0xA3, 0x00, // LD I, #300
+            0xF3, 0x55, // LD [I], V3
+            0x12, 0x0E, // JP #20E
+        ],
+        6,
+    )
+    .0
+    .read_byte(0x303);
+
+    // two pixels from the right edge: the second wraps or is cut off
+    let (_, display) = run(
+        quirks,
+        &[
+            0x60, 0x3F, // LD V0, #3F
+            0xA2, 0x08, // LD I, #208
+            0xD0, 0x11, // DRW V0, V1, 1
+            0x12, 0x06, // JP #206
+            0xC0, // two pixels
+        ],
+        3,
+    );
+
+    let flag = run(
+        quirks,
+        &[
+            0x6F, 0x05, // LD VF, #05
+            0x80, 0x11, // OR V0, V1
+            0xA3, 0x00, // LD I, #300
+            0xFF, 0x55, // LD [I], VF
+        ],
+        4,
+    )
+    .0
+    .read_byte(0x30F);
+
+    Quirks {
+        shift_uses_vy: shifted == 0x02,
+        load_store_increments_i: stored == 0x07,
+        jump_uses_vx: jumped == 0x02,
+        clip_sprites: display.read_pixel(0, 0) == 0,
+        logic_resets_vf: flag == 0,
+    }
+}
+
+#[test]
+fn every_profile_behaves_as_it_says() {
+    assert_eq!(observed(Quirks::default()), Quirks::default());
+    for name in PROFILES {
+        let quirks = Quirks::from_profile(name).unwrap();
+        assert_eq!(observed(quirks), quirks, "{}", name);
+    }
+    // and each flag on its own
+    for flag in [
+        "shift_uses_vy",
+        "load_store_increments_i",
+        "jump_uses_vx",
+        "clip_sprites",
+        "logic_resets_vf",
+    ] {
+        let mut quirks = Quirks::default();
+        quirks.set(flag, true).unwrap();
+        assert_eq!(observed(quirks), quirks, "{}", flag);
+    }
+}
+
+#[test]
+fn every_profile_can_be_picked_by_name() {
+    for (name, quirks) in [
+        ("VIP", Quirks::vip()),
+        ("cosmac", Quirks::vip()),
+        ("SuperChip", Quirks::schip()),
+        ("octo", Quirks::modern()),
+        ("xochip", Quirks::modern()),
+    ] {
+        assert_eq!(Quirks::from_profile(name), Some(quirks), "{}", name);
+    }
+    assert_eq!(Quirks::from_profile("chip-9"), None);
+    // the default is none of them: every flag off
+    for name in PROFILES {
+        assert_ne!(
+            Quirks::from_profile(name),
+            Some(Quirks::default()),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn overrides_flip_one_flag_on_top_of_a_profile() {
+    let mut quirks = Quirks::vip();
+    quirks.apply_override("clip_sprites=off").unwrap();
+    quirks.apply_override("jump_uses_vx=1").unwrap();
+    quirks.apply_override("logic_resets_vf=false").unwrap();
+    assert_eq!(
+        quirks,
+        Quirks {
+            clip_sprites: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            ..Quirks::vip()
+        }
+    );
+}
+
+#[test]
+fn bad_overrides_are_errors() {
+    let mut quirks = Quirks::default();
+    for (spec, error) in [
+        (
+            "clip_sprites",
+            "quirk override `clip_sprites` must look like flag=on|off",
+        ),
+        (
+            "clip_sprites=maybe",
+            "quirk value `maybe` must be on or off",
+        ),
+        (
+            "wrap_sprites=on",
+            "unknown quirk `wrap_sprites` (expected one of: shift_uses_vy, \
+             load_store_increments_i, jump_uses_vx, clip_sprites, logic_resets_vf)",
+        ),
+    ] {
+        assert_eq!(quirks.apply_override(spec), Err(error.to_string()));
+    }
+    assert_eq!(quirks, Quirks::default(), "left untouched");
+}
@@ -0,0 +1,164 @@
+use chip8::asm;
+use chip8::rewind::Rewind;
+use chip8::rng::Rng;
+use chip8::Chip8;
+use std::fs;
+use std::path::Path;
+
+const LEN: usize = 1000;
+
+// Each state is the one before with a different kind of change, so the
+// deltas between them cover runs and gaps short and long, both ends of the
+// state and no change at all.
+fn states() -> Vec<Vec<u8>> {
+    let mut rng = Rng::new(3);
+    let mut state = vec![0u8; LEN];
+    let mut states = vec![state.clone()];
+    for step in 0..12 {
+        let runs = match step {
+            0 => vec![(0, 1)],
+            1 => vec![(LEN - 1, LEN)],
+            2 => vec![],
+            // longer than a one-byte varint, on both counts
+            3 => vec![(300, 600)],
+            4 => vec![(5, 6), (900, 903)],
+            5 => vec![(0, LEN)],
+            _ => (0..20)
+                .map(|_| rng.next_u8() as usize * 3)
+                .map(|at| (at, at + 2))
+                .collect(),
+        };
+        for (start, end) in runs {
+            for byte in &mut state[start..end] {
+                *byte ^= rng.next_u8() | 1;
+            }
+        }
+        states.push(state.clone());
+    }
+    states
+}
+
+#[test]
+fn states_come_back_newest_first() {
+    let states = states();
+    let mut rewind = Rewind::new(100, usize::MAX);
+    for state in &states {
+        rewind.push_state(state.clone());
+    }
+    assert_eq!(rewind.len(), states.len());
+    for (idx, state) in states.iter().enumerate().rev() {
+        assert_eq!(rewind.pop_state().as_ref(), Some(state), "state {}", idx);
+    }
+    assert_eq!(rewind.pop_state(), None);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn only_changes_take_up_memory() {
+    let mut state = vec![0u8; LEN];
+    let mut rewind = Rewind::new(100, usize::MAX);
+    for frame in 0..100 {
+        state[frame] = 1;
+        rewind.push_state(state.clone());
+    }
+    // the newest state whole, then a few bytes per delta
+    assert!(
+        rewind.bytes_used() < LEN + 100 * 4,
+        "{} bytes",
+        rewind.bytes_used()
+    );
+}
+
+#[test]
+fn limits_drop_the_oldest_states() {
+    let states = states();
+
+    let mut rewind = Rewind::new(5, usize::MAX);
+    for state in &states {
+        rewind.push_state(state.clone());
+    }
+    assert_eq!(rewind.len(), 5);
+    for state in states.iter().rev().take(5) {
+        assert_eq!(rewind.pop_state().as_ref(), Some(state));
+    }
+    assert_eq!(rewind.pop_state(), None);
+
+    // all of state 6 changed: its delta and the newest state go over the
+    // limit together, so state 5 and everything before it have to go
+    let mut rewind = Rewind::new(100, 2 * LEN);
+    for state in &states {
+        rewind.push_state(state.clone());
+        assert!(rewind.bytes_used() <= 2 * LEN || rewind.len() == 1);
+    }
+    assert!(rewind.len() <= states.len() - 6, "{} states", rewind.len());
+    for state in states.iter().rev().take(rewind.len()) {
+        assert_eq!(rewind.pop_state().as_ref(), Some(state));
+    }
+}
+
+#[test]
+fn rewinding_restores_earlier_frames() {
+    let rom = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../roms/TETRIS")).unwrap();
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+    let mut rewind = Rewind::default();
+    let mut saved = Vec::new();
+    for _ in 0..30 {
+        chip8.run_frame().unwrap();
+        rewind.push(&chip8);
+        saved.push(chip8.save_state());
+    }
+    // the newest state is the one on screen, so the first rewind skips it
+    for state in saved.iter().rev().skip(1) {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+    assert!(!rewind.rewind(&mut chip8));
+}
+
+#[test]
+fn memory_writes_anywhere_are_rewound() {
+    // counts up in V0 and stores it low and high in memory every loop
+    let rom = asm::assemble(
+        "
+        loop:
+            ADD V0, 1
+            LD I, #300
+            LD B, V0
+            LD I, LONG #F123
+            LD [I], V0
+            JP loop
+        ",
+    )
+    .unwrap();
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+    let mut rewind = Rewind::default();
+    let mut saved = Vec::new();
+    for _ in 0..20 {
+        chip8.step().unwrap();
+        rewind.push(&chip8);
+        saved.push(chip8.save_state());
+    }
+    for state in saved.iter().rev().skip(1).take(10) {
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(&chip8.save_state(), state);
+    }
+
+    // and carries on from there, diffing memory against what it went back to
+    for _ in 0..5 {
+        chip8.step().unwrap();
+        rewind.push(&chip8);
+    }
+    for _ in 0..5 {
+        assert!(rewind.rewind(&mut chip8));
+    }
+    assert_eq!(chip8.save_state(), saved[8]);
+
+    // a state popped without loading it leaves the machine out of step
+    // with the buffer, so the next push diffs every page
+    assert_eq!(rewind.pop_state().unwrap(), saved[7]);
+    rewind.push(&chip8);
+    assert!(rewind.rewind(&mut chip8));
+    assert_eq!(chip8.save_state(), saved[6]);
+}
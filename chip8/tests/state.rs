@@ -0,0 +1,117 @@
+use chip8::state::{self, StateError};
+use chip8::Chip8;
+use std::fs;
+use std::path::Path;
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../roms")
+            .join(name),
+    )
+    .unwrap()
+}
+
+// `name` after `frames` frames. The random generator is seeded afresh each
+// time, so only a machine loaded from a state runs on the same way.
+fn machine(name: &str, frames: usize) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom(name)).unwrap();
+    run(&mut chip8, frames);
+    chip8
+}
+
+fn run(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+}
+
+#[test]
+fn loading_a_state_picks_up_where_it_was_saved() {
+    let mut chip8 = machine("TETRIS", 50);
+    let saved = chip8.save_state();
+    run(&mut chip8, 100);
+    let later = chip8.save_state();
+    assert_ne!(saved, later);
+
+    chip8.load_state(&saved).unwrap();
+    assert_eq!(chip8.save_state(), saved, "loads back exactly");
+    run(&mut chip8, 100);
+    assert_eq!(chip8.save_state(), later, "and runs on the same way");
+
+    // into a fresh machine running the same ROM
+    let mut fresh = machine("TETRIS", 0);
+    fresh.load_state(&saved).unwrap();
+    assert_eq!(fresh.save_state(), saved);
+}
+
+#[test]
+fn states_for_another_rom_are_rejected() {
+    let saved = machine("TETRIS", 10).save_state();
+    let mut pong = machine("PONG", 10);
+    assert_eq!(
+        pong.load_state(&saved),
+        Err(StateError::RomMismatch {
+            expected: state::rom_hash(&rom("PONG")),
+            found: state::rom_hash(&rom("TETRIS")),
+        })
+    );
+}
+
+#[test]
+fn damaged_states_are_rejected() {
+    let mut chip8 = machine("TETRIS", 10);
+    let saved = chip8.save_state();
+
+    let mut bad_magic = saved.clone();
+    bad_magic[0] = b'X';
+    assert_eq!(chip8.load_state(&bad_magic), Err(StateError::BadMagic));
+    assert_eq!(chip8.load_state(b"C8"), Err(StateError::BadMagic));
+
+    let mut old = saved.clone();
+    old[4..6].copy_from_slice(&(state::VERSION - 1).to_le_bytes());
+    assert_eq!(
+        chip8.load_state(&old),
+        Err(StateError::UnsupportedVersion(state::VERSION - 1))
+    );
+
+    for len in [5, 13, saved.len() / 2, saved.len() - 1] {
+        assert_eq!(
+            chip8.load_state(&saved[..len]),
+            Err(StateError::Truncated),
+            "cut to {} bytes",
+            len
+        );
+    }
+
+    let mut longer = saved.clone();
+    longer.push(0);
+    assert_eq!(
+        chip8.load_state(&longer),
+        Err(StateError::Corrupt("length"))
+    );
+}
+
+#[test]
+fn failed_load_leaves_the_machine_untouched() {
+    let saved = machine("TETRIS", 100).save_state();
+    let mut chip8 = machine("TETRIS", 20);
+    let before = chip8.save_state();
+
+    // each of these fails only after the CPU and memory were read
+    let truncated = &saved[..saved.len() - 4];
+    let mut bad_flag = saved.clone();
+    *bad_flag.last_mut().unwrap() = 2;
+    for data in [truncated, &bad_flag[..]] {
+        assert!(chip8.load_state(data).is_err());
+        assert_eq!(chip8.save_state(), before);
+    }
+
+    // and it still runs on as if nothing happened
+    let mut untouched = machine("TETRIS", 0);
+    untouched.load_state(&before).unwrap();
+    run(&mut chip8, 30);
+    run(&mut untouched, 30);
+    assert_eq!(chip8.save_state(), untouched.save_state());
+}
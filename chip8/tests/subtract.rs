@@ -0,0 +1,47 @@
+use chip8::asm;
+use chip8::Chip8;
+
+// Runs `LD V1, a`, `LD V2, b` and then `op`, returning V1 and VF.
+fn subtract(op: &str, a: u8, b: u8) -> (u8, u8) {
+    let source = format!("LD V1, #{:02X}\nLD V2, #{:02X}\n{}\n", a, b, op);
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&asm::assemble(&source).unwrap()).unwrap();
+    for _ in 0..3 {
+        chip8.execute_next_instruction().unwrap();
+    }
+    let regs = chip8.cpu().regs();
+    (regs[1], regs[0xF])
+}
+
+#[test]
+fn sub_sets_vf_unless_it_borrows() {
+    assert_eq!(subtract("SUB V1, V2", 5, 3), (2, 1));
+    assert_eq!(subtract("SUB V1, V2", 3, 5), (0xFE, 0));
+    // equal values borrow nothing either
+    assert_eq!(subtract("SUB V1, V2", 4, 4), (0, 1));
+}
+
+#[test]
+fn subn_sets_vf_unless_it_borrows() {
+    assert_eq!(subtract("SUBN V1, V2", 3, 5), (2, 1));
+    assert_eq!(subtract("SUBN V1, V2", 5, 3), (0xFE, 0));
+    assert_eq!(subtract("SUBN V1, V2", 4, 4), (0, 1));
+}
+
+#[test]
+fn the_flag_wins_when_vf_is_the_destination() {
+    for (op, expected) in [
+        ("LD VF, #05\nSUB VF, V2", 1),
+        ("LD VF, #01\nSUB VF, V2", 0),
+        ("LD VF, #01\nSUBN VF, V2", 1),
+        ("LD VF, #05\nSUBN VF, V2", 0),
+    ] {
+        let source = format!("LD V2, #03\n{}\n", op);
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&asm::assemble(&source).unwrap()).unwrap();
+        for _ in 0..3 {
+            chip8.execute_next_instruction().unwrap();
+        }
+        assert_eq!(chip8.cpu().regs()[0xF], expected, "{}", op);
+    }
+}
@@ -0,0 +1,132 @@
+use chip8::cpu::Cpu;
+use chip8::display::{Display, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+use chip8::keyboard::Keyboard;
+use chip8::memory::{Memory, BIG_FONT_ADDR};
+use chip8::quirks::Quirks;
+use chip8::rng::Rng;
+
+// Switches to 128x64, draws the big 8 at (112, 50), scrolls it down 4 and
+// right 4 into the bottom right corner, keeps V0 and V1 in the RPL flags
+// across clearing them, stores them at 0x300 and exits.
+const PROGRAM: [u8; 36] = [
+    0x00, 0xFF, // HIGH
+    0x60, 0x08, // LD V0, #08
+    0xF0, 0x30, // LD HF, V0
+    0x61, 0x70, // LD V1, #70
+    0x62, 0x32, // LD V2, #32
+    0xD1, 0x2A, // DRW V1, V2, 10
+    0x00, 0xC4, // SCD 4
+    0x00, 0xFB, // SCR
+    0x60, 0x12, // LD V0, #12
+    0x61, 0x34, // LD V1, #34
+    0xF1, 0x75, // LD R, V1
+    0x60, 0x00, // LD V0, #00
+    0x61, 0x00, // LD V1, #00
+    0xF1, 0x85, // LD V1, R
+    0xA3, 0x00, // LD I, #300
+    0xF1, 0x55, // LD [I], V1
+    0x00, 0xFD, // EXIT
+    0x62, 0xEE, // LD V2, #EE: never runs
+];
+
+struct Machine {
+    cpu: Cpu,
+    memory: Memory,
+    display: Display,
+    keyboard: Keyboard,
+    rng: Rng,
+}
+
+impl Machine {
+    fn new(rom: &[u8]) -> Machine {
+        let mut memory = Memory::new();
+        for (idx, byte) in rom.iter().enumerate() {
+            memory.write_byte(0x200 + idx as u16, *byte).unwrap();
+        }
+        Machine {
+            cpu: Cpu::new(Quirks::default()),
+            memory,
+            display: Display::new(),
+            keyboard: Keyboard::new(),
+            rng: Rng::new(0),
+        }
+    }
+
+    fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.cpu
+                .execute_next_instruction(
+                    &mut self.memory,
+                    &mut self.display,
+                    &mut self.keyboard,
+                    &mut self.rng,
+                )
+                .unwrap();
+        }
+    }
+
+    fn lit(&self) -> Vec<(usize, usize)> {
+        let mut lit = Vec::new();
+        for y in 0..self.display.height() {
+            for x in 0..self.display.width() {
+                if self.display.read_pixel(x, y) != 0 {
+                    lit.push((x, y));
+                }
+            }
+        }
+        lit
+    }
+}
+
+#[test]
+fn program_draws_scrolls_and_exits_in_high_resolution() {
+    let mut machine = Machine::new(&PROGRAM);
+    machine.run(30);
+    assert!(machine.display.is_hires());
+    assert_eq!(
+        (machine.display.width(), machine.display.height()),
+        (HIRES_WIDTH, HIRES_HEIGHT)
+    );
+
+    // the big 8 as the font stores it, moved to (116, 54)
+    let mut expected = Vec::new();
+    for row in 0..10 {
+        let bits = machine
+            .memory
+            .read_byte(BIG_FONT_ADDR + 8 * 10 + row as u16);
+        for col in 0..8 {
+            if bits & (0x80 >> col) != 0 {
+                expected.push((116 + col, 54 + row));
+            }
+        }
+    }
+    assert!(!expected.is_empty());
+    assert_eq!(machine.lit(), expected);
+
+    // the flags came back, and nothing after EXIT ran
+    assert_eq!(
+        [
+            machine.memory.read_byte(0x300),
+            machine.memory.read_byte(0x301)
+        ],
+        [0x12, 0x34]
+    );
+    assert!(machine.cpu.is_halted());
+}
+
+#[test]
+fn switching_resolution_clears_the_screen() {
+    let mut machine = Machine::new(&[
+        0xA2, 0x08, // LD I, #208
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x00, 0xFF, // HIGH
+        0x00, 0xFE, // LOW
+        0x80, // one pixel
+    ]);
+    machine.run(2);
+    assert_eq!(machine.lit(), [(0, 0)]);
+    machine.run(1);
+    assert!(machine.lit().is_empty());
+    machine.run(1);
+    assert_eq!(machine.display.width(), WIDTH);
+}
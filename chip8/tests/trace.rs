@@ -0,0 +1,235 @@
+use chip8::octo::compile;
+use chip8::trace::{self, Fields, Record, TraceFormat, Tracer};
+use chip8::tracediff::{self, Align, Outcome};
+use chip8::Chip8;
+use std::io::{self, Write};
+
+// Stores the BCD digits of a counter at 0x300, over and over.
+const COUNT: &str = "
+    : main
+      loop
+        v0 += 7
+        i := 0x300
+        bcd v0
+      again
+";
+
+// Five frames of COUNT, traced in `format`.
+fn trace_of(format: TraceFormat) -> Vec<u8> {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&compile(COUNT).unwrap()).unwrap();
+    let mut tracer = Tracer::new(Vec::new(), format);
+    for _ in 0..5 {
+        chip8
+            .run_frame_until(|chip8| {
+                tracer.record(chip8).unwrap();
+                false
+            })
+            .unwrap();
+    }
+    tracer.finish(&chip8).unwrap();
+    tracer.into_inner()
+}
+
+#[test]
+fn text_and_binary_traces_read_back_the_same() {
+    let text = trace::parse(&trace_of(TraceFormat::Text)).unwrap();
+    let binary = trace::parse(&trace_of(TraceFormat::Binary)).unwrap();
+    assert!(!text.is_empty());
+    assert_eq!(text, binary);
+
+    // `bcd v0` is the third instruction of the loop; v0 is 7 by then
+    let bcd = &text[2];
+    assert_eq!(bcd.opcode, 0xF033);
+    assert_eq!(bcd.writes, vec![(0x300, 0), (0x301, 0), (0x302, 7)]);
+}
+
+// A file on a full disk: every write fails.
+struct FullDisk;
+
+impl Write for FullDisk {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_trace_stops_recording_memory_writes() {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&compile(COUNT).unwrap()).unwrap();
+    let mut tracer = Tracer::new(FullDisk, TraceFormat::Text);
+    let mut failed = None;
+    chip8
+        .run_frame_until(|chip8| {
+            if failed.is_none() {
+                failed = tracer.record(chip8).err();
+            }
+            false
+        })
+        .unwrap();
+    assert_eq!(failed.unwrap().to_string(), "disk full");
+
+    assert!(tracer.finish(&chip8).is_ok(), "nothing left to write");
+    for _ in 0..5 {
+        chip8.run_frame().unwrap();
+    }
+    assert_eq!(chip8.memory().take_accesses(), []);
+}
+
+#[test]
+fn diff_names_the_register_and_the_instruction_that_set_it() {
+    let ours = trace::parse(&trace_of(TraceFormat::Text)).unwrap();
+    let mut theirs = ours.clone();
+    // the reference's first `v0 += 7` added 8
+    for record in &mut theirs[1..] {
+        record.regs[0] = record.regs[0].wrapping_add(1);
+    }
+    let Outcome::Diverged(divergence) = tracediff::diff(&ours, &theirs, Align::Sequence) else {
+        panic!("traces should differ");
+    };
+    assert_eq!(divergence.ours, 1);
+    assert_eq!(divergence.culprit, Some((0, 0)));
+    assert_eq!(divergence.differences, ["V0: 07 here, 08 in the reference"]);
+    let report = tracediff::report(&ours, &theirs, &divergence, 2);
+    assert!(
+        report.contains("caused by 0200 7007 ADD V0, #07"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn diff_blames_a_bad_memory_write_on_its_own_instruction() {
+    let ours = trace::parse(&trace_of(TraceFormat::Text)).unwrap();
+    let mut theirs = ours.clone();
+    theirs[2].writes[2] = (0x302, 8);
+    let Outcome::Diverged(divergence) = tracediff::diff(&ours, &theirs, Align::Sequence) else {
+        panic!("traces should differ");
+    };
+    assert_eq!(divergence.culprit, Some((2, 2)));
+    assert_eq!(
+        divergence.differences,
+        ["memory 0302: wrote 07 here, 08 in the reference"]
+    );
+
+    // records only one trace has are skipped when aligning by cycle
+    let filtered: Vec<Record> = ours.iter().filter(|r| r.pc != 0x202).cloned().collect();
+    assert_eq!(
+        tracediff::diff(&ours, &filtered, Align::Cycle),
+        Outcome::Same
+    );
+    assert_eq!(
+        tracediff::diff(&ours[..10], &ours, Align::Sequence),
+        Outcome::Length {
+            ours: 10,
+            theirs: ours.len()
+        }
+    );
+}
+
+#[test]
+fn diff_only_blames_an_instruction_both_traces_recorded() {
+    let ours = trace::parse(&trace_of(TraceFormat::Text)).unwrap();
+    // the reference's first `i := 0x300` set 0x301
+    let mut theirs = ours.clone();
+    for record in &mut theirs[2..] {
+        record.i += 1;
+    }
+    let Outcome::Diverged(divergence) = tracediff::diff(&ours, &theirs, Align::Cycle) else {
+        panic!("traces should differ");
+    };
+    assert_eq!(divergence.culprit, Some((1, 1)));
+
+    // without `i := 0x300` in the reference, the instruction before the
+    // divergence that both have is the wrong one to blame
+    theirs.retain(|record| record.pc != 0x202);
+    let Outcome::Diverged(divergence) = tracediff::diff(&ours, &theirs, Align::Cycle) else {
+        panic!("traces should differ");
+    };
+    assert_eq!((divergence.ours, divergence.theirs), (2, 1));
+    assert_eq!(
+        divergence.differences,
+        ["I: 0300 here, 0301 in the reference"]
+    );
+    assert_eq!(divergence.culprit, None);
+    assert_eq!(divergence.last_match, Some((0, 0)));
+    let report = tracediff::report(&ours, &theirs, &divergence, 2);
+    assert!(
+        report.contains("cause unknown, the traces last agree at 0200 7007 ADD V0, #07 (cycle 0)"),
+        "{}",
+        report
+    );
+}
+
+// `records` as a register dump log like other emulators write, leaving out
+// the opcode, SP and timers, and with its own way of writing numbers.
+fn log_of(records: &[Record]) -> String {
+    let mut log = String::new();
+    for record in records {
+        log.push_str(&format!("pc=0x{:04x}, i=0x{:04x}", record.pc, record.i));
+        for (x, reg) in record.regs.iter().enumerate() {
+            log.push_str(&format!(", v{:x}=0x{:02x}", x, reg));
+        }
+        log.push('\n');
+    }
+    log
+}
+
+#[test]
+fn register_dump_logs_read_with_what_they_hold() {
+    let (records, fields) =
+        trace::read(b"PC:0200 OP:7007 I:0000 V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 SP:0 DT:3C ST:00\n\
+                      CYCLE:1 PC:0202 OPCODE:A300 I:0000 V0:07 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 SP:0 DT:3C\n")
+            .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[1].cycle, records[1].pc), (1, 0x202));
+    assert_eq!((records[1].opcode, records[1].regs[0]), (0xA300, 7));
+    assert_eq!(records[0].dt, 0x3C);
+    // the second line has no ST, and no log has memory writes
+    assert!(fields.opcode && fields.sp && fields.dt);
+    assert!(!fields.st && !fields.writes);
+
+    // our own traces hold everything
+    let (_, fields) = trace::read(&trace_of(TraceFormat::Binary)).unwrap();
+    assert_eq!(fields, Fields::ALL);
+
+    assert_eq!(
+        trace::parse_log("PC:0200 I:0000 V0:00\n").unwrap_err(),
+        "line 1: expected PC, I and V0-VF"
+    );
+    assert_eq!(
+        trace::parse_log("\nPC:0200 X:1\n").unwrap_err(),
+        "line 2: unknown field `X:1`"
+    );
+    assert_eq!(
+        trace::parse_log("PC:02G0\n").unwrap_err(),
+        "line 1: bad PC `02G0`"
+    );
+}
+
+#[test]
+fn diff_against_a_log_compares_only_what_it_holds() {
+    let ours = trace::parse(&trace_of(TraceFormat::Text)).unwrap();
+    let (theirs, fields) = trace::read(log_of(&ours).as_bytes()).unwrap();
+    assert_eq!(
+        tracediff::diff_only(&ours, &theirs, Align::Sequence, fields),
+        Outcome::Same
+    );
+
+    // the reference's first `v0 += 7` added 8
+    let mut wrong = ours.clone();
+    for record in &mut wrong[1..] {
+        record.regs[0] = record.regs[0].wrapping_add(1);
+    }
+    let (theirs, fields) = trace::read(log_of(&wrong).as_bytes()).unwrap();
+    let Outcome::Diverged(divergence) = tracediff::diff_only(&ours, &theirs, Align::Cycle, fields)
+    else {
+        panic!("traces should differ");
+    };
+    assert_eq!(divergence.culprit, Some((0, 0)));
+    assert_eq!(divergence.differences, ["V0: 07 here, 08 in the reference"]);
+}
@@ -0,0 +1,96 @@
+use chip8::cpu::Cpu;
+use chip8::display::Display;
+use chip8::keyboard::Keyboard;
+use chip8::memory::Memory;
+use chip8::quirks::Quirks;
+use chip8::rng::Rng;
+
+// Everything this program reads lives past the first 4 KiB, at 0x8000: a
+// two-plane sprite, registers loaded and saved back as ranges, and an audio
+// pattern played at a set pitch.
+const PROGRAM: [u8; 36] = [
+    0xF3, 0x01, // PLANE 3
+    0xF0, 0x00, 0x80, 0x00, // LD I, LONG #8000
+    0xD0, 0x01, // DRW V0, V0, 1
+    0xF0, 0x00, 0x80, 0x02, // LD I, LONG #8002
+    0x51, 0x33, // LD V1-V3, [I]
+    0xF0, 0x00, 0x80, 0x05, // LD I, LONG #8005
+    0x53, 0x12, // LD [I], V3-V1
+    0xF0, 0x00, 0x80, 0x08, // LD I, LONG #8008
+    0xF0, 0x02, // AUDIO
+    0x64, 0x70, // LD V4, #70
+    0xF4, 0x3A, // PITCH V4
+    0x64, 0x1E, // LD V4, #1E
+    0xF4, 0x18, // LD ST, V4
+    0x12, 0x22, // JP #222
+];
+
+const DATA: [u8; 24] = [
+    0xF0, 0x3C, // the first plane's row, then the second's
+    1, 2, 3, // loaded into V1-V3
+    0, 0, 0, // where they are saved
+    0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, // the pattern
+    0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55,
+];
+
+struct Machine {
+    cpu: Cpu,
+    memory: Memory,
+    display: Display,
+    keyboard: Keyboard,
+    rng: Rng,
+}
+
+impl Machine {
+    fn new() -> Machine {
+        let mut memory = Memory::new();
+        for (idx, byte) in PROGRAM.iter().enumerate() {
+            memory.write_byte(0x200 + idx as u16, *byte).unwrap();
+        }
+        for (idx, byte) in DATA.iter().enumerate() {
+            memory.write_byte(0x8000 + idx as u16, *byte).unwrap();
+        }
+        Machine {
+            cpu: Cpu::new(Quirks::default()),
+            memory,
+            display: Display::new(),
+            keyboard: Keyboard::new(),
+            rng: Rng::new(0),
+        }
+    }
+
+    fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.cpu
+                .execute_next_instruction(
+                    &mut self.memory,
+                    &mut self.display,
+                    &mut self.keyboard,
+                    &mut self.rng,
+                )
+                .unwrap();
+        }
+    }
+}
+
+#[test]
+fn program_uses_memory_past_4k_planes_and_audio() {
+    let mut machine = Machine::new();
+    machine.run(20);
+
+    // only the first plane, both, only the second
+    let row: Vec<u8> = (0..8).map(|x| machine.display.read_pixel(x, 0)).collect();
+    assert_eq!(row, [1, 1, 3, 3, 2, 2, 0, 0]);
+    assert_eq!(machine.display.selected_planes(), 3);
+
+    // loaded as 1 2 3, saved from V3 down to V1
+    let saved: Vec<u8> = (0x8005..0x8008)
+        .map(|address| machine.memory.read_byte(address))
+        .collect();
+    assert_eq!(saved, [3, 2, 1]);
+
+    let sound = machine.cpu.sound();
+    assert_eq!(sound.pattern, Some([0x55; 16]));
+    assert_eq!(sound.pitch, 0x70);
+    assert_eq!(sound.timer, 30);
+}
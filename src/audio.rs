@@ -0,0 +1,65 @@
+use std::{
+    io::Write,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use chip8::audio::{Beeper, SoundState};
+use chip8::backend::AudioSink;
+
+// Plays the buzzer by streaming raw PCM into `aplay`, which keeps the
+// frontend free of native audio libraries. That means sound only works on
+// Linux with alsa-utils installed; anywhere else a warning is printed once
+// and the buzzer is silent.
+pub struct PipeAudio {
+    player: Option<(Child, ChildStdin)>,
+    beeper: Beeper,
+    samples: Vec<i16>,
+}
+
+impl PipeAudio {
+    pub fn new(beeper: Beeper) -> PipeAudio {
+        let player = Command::new("aplay")
+            .args(["-q", "-t", "raw", "-f", "S16_LE", "-c", "1", "-r"])
+            .arg(beeper.sample_rate().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| eprintln!("warning: no sound, could not start aplay: {}", e))
+            .ok()
+            .and_then(|mut child| child.stdin.take().map(|stdin| (child, stdin)));
+
+        PipeAudio {
+            player,
+            beeper,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl AudioSink for PipeAudio {
+    fn update(&mut self, sound: &SoundState) {
+        let Some((_, stdin)) = &mut self.player else {
+            return;
+        };
+
+        self.samples.clear();
+        self.beeper.render_frame(sound, &mut self.samples);
+        let bytes: Vec<u8> = self.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        if let Err(e) = stdin.write_all(&bytes) {
+            // the player went away; stop trying
+            eprintln!("warning: no more sound, aplay stopped: {}", e);
+            self.player = None;
+        }
+    }
+}
+
+impl Drop for PipeAudio {
+    fn drop(&mut self) {
+        if let Some((mut child, stdin)) = self.player.take() {
+            drop(stdin);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
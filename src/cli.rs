@@ -0,0 +1,256 @@
+use std::ops::RangeInclusive;
+
+use chip8::{
+    audio,
+    backend::{KeyEvent, ScriptedInput},
+    debugger,
+    display::PALETTE,
+    quirks::{Quirks, PROFILES},
+    rewind,
+    trace::{self, TraceFormat},
+    unknown::{self, UnknownOpcodePolicy},
+    DEFAULT_IPS, FRAME_RATE,
+};
+
+pub const USAGE: &str = "\
+usage: chip8-rust [OPTIONS] ROM
+
+options:
+  --ips N                    instructions per second (default 700)
+  --scale N                  window pixels per CHIP-8 pixel (default 10)
+  --fg RRGGBB, --bg RRGGBB   foreground and background colours
+  --quirks PROFILE           vip, chip48, schip or modern
+  --quirk FLAG=on|off        override a single quirk
+  --unknown-opcodes POLICY   ignore, warn (default) or halt
+  --mute                     no sound
+  --pitch HZ                 buzzer tone (default 440)
+  --volume PERCENT           buzzer volume, 0 to 100 (default 25)
+  --rewind-seconds N         how far back Backspace can rewind (default 10)
+  --rewind-memory MIB        memory the rewind buffer may use (default 16)
+  --seed N                   seed for the random number generator
+  --headless                 run without a window, as fast as possible
+  --frames N                 stop after N frames
+  --keys SCRIPT              headless key presses, e.g. 30+5,40-5
+  --expect-screen FILE       headless: exit 1 unless the final screen matches
+  --save-screen FILE         headless: write the final screen as a golden
+  --screenshot-at-frame N    save a PNG screenshot after frame N (repeatable)
+  --screenshot-scale N       image pixels per CHIP-8 pixel (default 1)
+  --trace FILE               log every instruction executed to FILE
+  --trace-format FORMAT      text (default) or binary
+  --trace-pc START-END       only log instructions at these addresses
+  --trace-limit BYTES        stop logging once the trace reaches BYTES
+  --debug                    start paused with a debugger prompt
+  --break ADDR               start the debugger with a breakpoint at ADDR
+  --load-state FILE          start from a save state
+  --config FILE              key bindings (default chip8.ini, see config.rs)
+  --keymap FILE              same as --config
+  -h, --help                 show this help";
+
+// Everything the command line can ask for.
+pub struct Options {
+    pub rom_path: String,
+    pub ips: u32,
+    pub scale: usize,
+    pub palette: [u32; 4],
+    pub quirks: Quirks,
+    pub unknown_policy: UnknownOpcodePolicy,
+    pub mute: bool,
+    pub pitch: f32,
+    pub volume: f32,
+    pub rewind_depth: usize,
+    pub rewind_max_bytes: usize,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub keys: Vec<(u64, KeyEvent)>,
+    pub expect_screen: Option<String>,
+    pub save_screen: Option<String>,
+    pub screenshot_frames: Vec<u64>,
+    pub screenshot_scale: usize,
+    pub trace_path: Option<String>,
+    pub trace_format: TraceFormat,
+    pub trace_pc: RangeInclusive<u16>,
+    pub trace_limit: Option<u64>,
+    pub debug: bool,
+    pub breakpoints: Vec<u16>,
+    pub state_path: Option<String>,
+    pub config_path: Option<String>,
+}
+
+// Parses the arguments after the program name. `Ok(None)` means help was
+// asked for.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut rom_path = None;
+    let mut options = Options {
+        rom_path: String::new(),
+        ips: DEFAULT_IPS,
+        scale: 10,
+        palette: PALETTE,
+        quirks: Quirks::default(),
+        unknown_policy: UnknownOpcodePolicy::Warn,
+        mute: false,
+        pitch: audio::DEFAULT_PITCH,
+        volume: audio::DEFAULT_VOLUME,
+        rewind_depth: rewind::DEFAULT_DEPTH,
+        rewind_max_bytes: rewind::DEFAULT_MAX_BYTES,
+        seed: None,
+        headless: false,
+        frames: None,
+        keys: Vec::new(),
+        expect_screen: None,
+        save_screen: None,
+        screenshot_frames: Vec::new(),
+        screenshot_scale: 1,
+        trace_path: None,
+        trace_format: TraceFormat::Text,
+        trace_pc: 0..=0xFFFF,
+        trace_limit: None,
+        debug: false,
+        breakpoints: Vec::new(),
+        state_path: None,
+        config_path: None,
+    };
+    let mut quirk_overrides = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |what: &str| {
+            args.next()
+                .ok_or_else(|| format!("`{}` needs {}", arg, what))
+        };
+        match arg.as_str() {
+            "--ips" => options.ips = number(&value("a number")?, 1, u32::MAX as u64)? as u32,
+            "--scale" => options.scale = number(&value("a number")?, 1, 64)? as usize,
+            "--fg" => options.palette[1] = colour(&value("a colour")?)?,
+            "--bg" => options.palette[0] = colour(&value("a colour")?)?,
+            "--quirks" => {
+                let name = value("a profile")?;
+                options.quirks = Quirks::from_profile(&name).ok_or_else(|| {
+                    format!(
+                        "unknown quirk profile `{}` (expected one of: {})",
+                        name,
+                        PROFILES.join(", ")
+                    )
+                })?;
+            }
+            "--quirk" => quirk_overrides.push(value("FLAG=on|off")?),
+            "--unknown-opcodes" => {
+                let name = value("a policy")?;
+                options.unknown_policy =
+                    UnknownOpcodePolicy::from_name(&name).ok_or_else(|| {
+                        format!(
+                            "unknown opcode policy `{}` (expected one of: {})",
+                            name,
+                            unknown::POLICIES.join(", ")
+                        )
+                    })?;
+            }
+            "--mute" => options.mute = true,
+            "--pitch" => options.pitch = number(&value("a frequency")?, 20, 20_000)? as f32,
+            "--volume" => options.volume = number(&value("a percentage")?, 0, 100)? as f32 / 100.0,
+            "--rewind-seconds" => {
+                let seconds = number(&value("a number")?, 1, 3600)?;
+                options.rewind_depth = (seconds * FRAME_RATE as u64) as usize;
+            }
+            "--rewind-memory" => {
+                let mib = number(&value("a size in MiB")?, 1, 4096)?;
+                options.rewind_max_bytes = mib as usize * 1024 * 1024;
+            }
+            "--seed" => options.seed = Some(number(&value("a number")?, 0, u64::MAX)?),
+            "--headless" => options.headless = true,
+            "--frames" => options.frames = Some(number(&value("a number")?, 0, u64::MAX)?),
+            "--keys" => options
+                .keys
+                .extend(ScriptedInput::parse_script(&value("a script")?)?),
+            "--expect-screen" => options.expect_screen = Some(value("a file")?),
+            "--save-screen" => options.save_screen = Some(value("a file")?),
+            "--screenshot-at-frame" => {
+                options
+                    .screenshot_frames
+                    .push(number(&value("a frame number")?, 1, u64::MAX)?)
+            }
+            "--screenshot-scale" => {
+                options.screenshot_scale = number(&value("a number")?, 1, 64)? as usize
+            }
+            "--trace" => options.trace_path = Some(value("a file")?),
+            "--trace-format" => {
+                let name = value("a format")?;
+                options.trace_format = TraceFormat::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown trace format `{}` (expected one of: {})",
+                        name,
+                        trace::FORMATS.join(", ")
+                    )
+                })?;
+            }
+            "--trace-pc" => options.trace_pc = address_range(&value("START-END")?)?,
+            "--trace-limit" => options.trace_limit = Some(number(&value("a size")?, 1, u64::MAX)?),
+            "--debug" => options.debug = true,
+            "--break" => {
+                let addr = number(&value("an address")?, 0, 0xFFFF)?;
+                options.breakpoints.push(addr as u16);
+                options.debug = true;
+            }
+            "--load-state" => options.state_path = Some(value("a file")?),
+            "--config" | "--keymap" => options.config_path = Some(value("a file")?),
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if rom_path.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    // per-flag overrides win over the profile, whatever order they came in
+    for spec in quirk_overrides {
+        options.quirks.apply_override(&spec)?;
+    }
+    if options.headless && options.debug {
+        return Err("the debugger needs the window, drop `--headless`".to_string());
+    }
+    let headless_only = [
+        ("--keys", !options.keys.is_empty()),
+        ("--expect-screen", options.expect_screen.is_some()),
+        ("--save-screen", options.save_screen.is_some()),
+    ];
+    if let Some((flag, _)) = headless_only
+        .iter()
+        .find(|(_, given)| *given && !options.headless)
+    {
+        return Err(format!("`{}` only works with `--headless`", flag));
+    }
+    if options.trace_path.is_some() && options.debug {
+        return Err("`--trace` can't be combined with the debugger".to_string());
+    }
+    options.rom_path = rom_path.ok_or_else(|| "no ROM given".to_string())?;
+    Ok(Some(options))
+}
+
+fn number(text: &str, min: u64, max: u64) -> Result<u64, String> {
+    match debugger::parse_number(text) {
+        Some(n) if (min as usize..=max as usize).contains(&n) => Ok(n as u64),
+        Some(_) => Err(format!("{} is out of range ({} to {})", text, min, max)),
+        None => Err(format!("`{}` is not a number", text)),
+    }
+}
+
+// `START-END`, both ends included
+fn address_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = text
+        .split_once('-')
+        .ok_or_else(|| format!("`{}` should look like 0x200-0x2FF", text))?;
+    let start = number(start.trim(), 0, 0xFFFF)? as u16;
+    let end = number(end.trim(), start as u64, 0xFFFF)? as u16;
+    Ok(start..=end)
+}
+
+// `RRGGBB`, optionally written `#RRGGBB` or `0xRRGGBB`
+fn colour(text: &str) -> Result<u32, String> {
+    let digits = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    match u32::from_str_radix(digits, 16) {
+        Ok(rgb) if digits.len() == 6 => Ok(rgb),
+        _ => Err(format!("`{}` is not a colour (expected RRGGBB)", text)),
+    }
+}
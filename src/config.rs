@@ -0,0 +1,435 @@
+use std::{fs, path::Path};
+
+use minifb::Key;
+
+use crate::keymap::{self, key_from_name, Chord, Keymap};
+
+// Where the frontend looks for its config when `--config` isn't given.
+pub const DEFAULT_PATH: &str = "chip8.ini";
+
+// The frontend's key configuration, read from an INI-style file. Lines
+// before any section header belong to `[keypad]`; `#` and `;` start
+// comments.
+//
+//   [keypad]
+//   preset = azerty        ; qwerty (the default), azerty or dvorak
+//   5 = W, Up              ; keypad key = one or more host keys
+//   0 =                    ; or none at all
+//
+//   [hotkeys]
+//   pause = Space
+//   save_state = Shift+F1, Shift+F2   ; one chord per slot
+//
+//   [rom PONG]             ; only when the ROM file is named PONG
+//   1 = Up
+//   4 = Down
+//
+// A binding replaces everything that keypad key was bound to before, and
+// `[rom ...]` sections apply after `[keypad]` wherever they are in the file.
+// A host key can't be both on the keypad and part of a hotkey, since the
+// game would see it pressed too.
+#[derive(Default)]
+pub struct Config {
+    pub keymap: Keymap,
+    pub hotkeys: HotkeyMap,
+}
+
+// Frontend shortcuts, each bound to any number of chords. The n-th chord of
+// `save_state` and `load_state` is slot n.
+#[derive(Clone, Debug)]
+pub struct HotkeyMap {
+    pub pause: Vec<Chord>,
+    pub reset: Vec<Chord>,
+    pub rewind: Vec<Chord>,
+    pub screenshot: Vec<Chord>,
+    pub save_state: Vec<Chord>,
+    pub load_state: Vec<Chord>,
+}
+
+pub const HOTKEYS: [&str; 6] = [
+    "pause",
+    "reset",
+    "rewind",
+    "screenshot",
+    "save_state",
+    "load_state",
+];
+
+const SLOT_KEYS: [Key; 9] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+];
+
+impl Default for HotkeyMap {
+    fn default() -> Self {
+        HotkeyMap {
+            pause: vec![Chord::new(Key::F11)],
+            reset: vec![Chord::new(Key::F12)],
+            rewind: vec![Chord::new(Key::Backspace)],
+            screenshot: vec![Chord::new(Key::F10)],
+            save_state: SLOT_KEYS.iter().map(|key| Chord::shifted(*key)).collect(),
+            load_state: SLOT_KEYS.iter().map(|key| Chord::new(*key)).collect(),
+        }
+    }
+}
+
+impl HotkeyMap {
+    fn bind(&mut self, action: &str, chords: Vec<Chord>) -> Result<(), String> {
+        let slot = match action {
+            "pause" => &mut self.pause,
+            "reset" => &mut self.reset,
+            "rewind" => &mut self.rewind,
+            "screenshot" => &mut self.screenshot,
+            "save_state" => &mut self.save_state,
+            "load_state" => &mut self.load_state,
+            _ => {
+                return Err(format!(
+                    "unknown hotkey `{}` (expected one of: {})",
+                    action,
+                    HOTKEYS.join(", ")
+                ))
+            }
+        };
+        *slot = chords;
+        Ok(())
+    }
+
+    // every action with its chords, in HOTKEYS order
+    fn actions(&self) -> [(&'static str, &[Chord]); 6] {
+        [
+            ("pause", &self.pause),
+            ("reset", &self.reset),
+            ("rewind", &self.rewind),
+            ("screenshot", &self.screenshot),
+            ("save_state", &self.save_state),
+            ("load_state", &self.load_state),
+        ]
+    }
+}
+
+enum Section {
+    Keypad,
+    Hotkeys,
+    Rom(String),
+}
+
+// `key = value` with the line it came from
+struct Entry<'a> {
+    line: usize,
+    key: &'a str,
+    value: &'a str,
+}
+
+impl Config {
+    // Reads the config at `path` as it applies to the ROM at `rom_path`.
+    pub fn load(path: &str, rom_path: &str) -> Result<Config, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Config::parse(&text, rom_path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str, rom_path: &str) -> Result<Config, String> {
+        let mut keypad = Vec::new();
+        let mut hotkeys = Vec::new();
+        let mut roms: Vec<(String, Vec<Entry>)> = Vec::new();
+        let mut section = Section::Keypad;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split(['#', ';']).next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at = |e: String| format!("line {}: {}", idx + 1, e);
+            if let Some(header) = line.strip_prefix('[') {
+                section = parse_header(header).map_err(at)?;
+                if let Section::Rom(name) = &section {
+                    roms.push((name.clone(), Vec::new()));
+                }
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| at(format!("`{}` should look like `5 = W, Up`", line)))?;
+            let entry = Entry {
+                line: idx + 1,
+                key: key.trim(),
+                value: value.trim(),
+            };
+            match section {
+                Section::Keypad => keypad.push(entry),
+                Section::Hotkeys => hotkeys.push(entry),
+                Section::Rom(_) => roms.last_mut().unwrap().1.push(entry),
+            }
+        }
+
+        let mut config = Config::default();
+        apply_keypad(&mut config.keymap, &keypad)?;
+        for entry in &hotkeys {
+            let chords = entry
+                .value
+                .split(',')
+                .filter(|chord| !chord.trim().is_empty())
+                .map(Chord::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {}", entry.line, e))?;
+            config
+                .hotkeys
+                .bind(entry.key, chords)
+                .map_err(|e| format!("line {}: {}", entry.line, e))?;
+        }
+        for (name, entries) in &roms {
+            if rom_matches(name, rom_path) {
+                apply_keypad(&mut config.keymap, entries)?;
+            } else {
+                // still check it, so a typo shows up before that ROM is run
+                apply_keypad(&mut Keymap::default(), entries)?;
+            }
+        }
+        check_clashes(&config)?;
+        Ok(config)
+    }
+}
+
+// Rejects host keys that are both on the keypad and in a hotkey.
+fn check_clashes(config: &Config) -> Result<(), String> {
+    for (action, chords) in config.hotkeys.actions() {
+        for chord in chords {
+            if let Some(pad) = config.keymap.pad_for(chord.key()) {
+                return Err(format!(
+                    "{:?} is bound to keypad key {:X} and to the {} hotkey; rebind one of them",
+                    chord.key(),
+                    pad,
+                    action
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// `keypad]`, `hotkeys]` or `rom NAME]`
+fn parse_header(header: &str) -> Result<Section, String> {
+    let name = header
+        .strip_suffix(']')
+        .ok_or_else(|| format!("`[{}` is missing its `]`", header))?
+        .trim();
+    match name.split_once(char::is_whitespace) {
+        Some(("rom", rom)) => Ok(Section::Rom(rom.trim().to_string())),
+        _ if name == "keypad" => Ok(Section::Keypad),
+        _ if name == "hotkeys" => Ok(Section::Hotkeys),
+        _ => Err(format!(
+            "unknown section `[{}]` (expected [keypad], [hotkeys] or [rom NAME])",
+            name
+        )),
+    }
+}
+
+fn apply_keypad(keymap: &mut Keymap, entries: &[Entry]) -> Result<(), String> {
+    for entry in entries {
+        bind_keypad(keymap, entry).map_err(|e| format!("line {}: {}", entry.line, e))?;
+    }
+    Ok(())
+}
+
+// `preset = NAME` or `KEYPAD = HOST, HOST...`
+fn bind_keypad(keymap: &mut Keymap, entry: &Entry) -> Result<(), String> {
+    if entry.key == "preset" {
+        *keymap = Keymap::preset(entry.value).ok_or_else(|| {
+            format!(
+                "unknown preset `{}` (expected one of: {})",
+                entry.value,
+                keymap::PRESETS.join(", ")
+            )
+        })?;
+        return Ok(());
+    }
+    let pad = u8::from_str_radix(entry.key, 16)
+        .ok()
+        .filter(|pad| *pad < 16)
+        .ok_or_else(|| format!("`{}` is not a keypad key (0-F)", entry.key))?;
+    let mut keys = Vec::new();
+    for name in entry
+        .value
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        keys.push(key_from_name(name).ok_or_else(|| format!("unknown key `{}`", name))?);
+    }
+    keymap.bind(pad, &keys);
+    Ok(())
+}
+
+// `[rom PONG]` matches `roms/PONG` and `pong.ch8` alike.
+fn rom_matches(name: &str, rom_path: &str) -> bool {
+    let path = Path::new(rom_path);
+    [path.file_name(), path.file_stem()]
+        .into_iter()
+        .flatten()
+        .any(|file| file.to_string_lossy().eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, String> {
+        Config::parse(text, "roms/PONG")
+    }
+
+    #[test]
+    fn empty_file_is_the_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.keymap.pad_for(Key::X), Some(0x0));
+        assert_eq!(config.keymap.pad_for(Key::V), Some(0xF));
+        assert_eq!(config.hotkeys.pause, [Chord::new(Key::F11)]);
+        assert_eq!(config.hotkeys.save_state[8], Chord::shifted(Key::F9));
+    }
+
+    #[test]
+    fn sections_bind_keys_and_hotkeys() {
+        let config = parse(
+            "
+            # lines before any section are the keypad
+            preset = azerty
+            5 = W, Up     ; two host keys
+            0 =
+            [hotkeys]
+            pause = Space
+            save_state = Shift+F1, ctrl + alt + f2
+            [keypad]
+            A = Enter
+            ",
+        )
+        .unwrap();
+        let keymap = &config.keymap;
+        assert_eq!(keymap.pad_for(Key::Q), Some(0x7), "AZERTY");
+        assert_eq!(keymap.pad_for(Key::W), Some(0x5));
+        assert_eq!(keymap.pad_for(Key::Up), Some(0x5));
+        assert_eq!(keymap.pad_for(Key::Z), None, "no longer key 5");
+        assert_eq!(keymap.pad_for(Key::X), None, "0 is unbound");
+        assert_eq!(keymap.pad_for(Key::Enter), Some(0xA));
+        assert_eq!(keymap.pad_for(Key::W), Some(0x5));
+
+        assert_eq!(config.hotkeys.pause, [Chord::new(Key::Space)]);
+        assert_eq!(config.hotkeys.save_state.len(), 2);
+        assert_eq!(config.hotkeys.save_state[0], Chord::shifted(Key::F1));
+        assert_eq!(
+            config.hotkeys.save_state[1],
+            Chord::parse("Alt+Ctrl+F2").unwrap()
+        );
+        assert_eq!(config.hotkeys.reset, [Chord::new(Key::F12)], "untouched");
+    }
+
+    #[test]
+    fn rom_sections_only_apply_to_their_rom() {
+        let text = "
+            [rom PONG]
+            1 = Up
+            [keypad]
+            1 = 9
+            ";
+        let pong = parse(text).unwrap();
+        assert_eq!(pong.keymap.pad_for(Key::Up), Some(0x1), "after [keypad]");
+        assert_eq!(pong.keymap.pad_for(Key::Key9), None, "replaced");
+        let pong = Config::parse(text, "pong.ch8").unwrap();
+        assert_eq!(pong.keymap.pad_for(Key::Up), Some(0x1));
+
+        let tetris = Config::parse(text, "roms/TETRIS").unwrap();
+        assert_eq!(tetris.keymap.pad_for(Key::Up), None);
+        assert_eq!(tetris.keymap.pad_for(Key::Key9), Some(0x1));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        for (text, error) in [
+            ("5 W", "line 1: `5 W` should look like `5 = W, Up`"),
+            ("[keypad", "line 1: `[keypad` is missing its `]`"),
+            (
+                "\n[joystick]",
+                "line 2: unknown section `[joystick]` (expected [keypad], [hotkeys] or [rom NAME])",
+            ),
+            (
+                "preset = colemak",
+                "line 1: unknown preset `colemak` (expected one of: qwerty, azerty, dvorak)",
+            ),
+            ("G = X", "line 1: `G` is not a keypad key (0-F)"),
+            ("10 = X", "line 1: `10` is not a keypad key (0-F)"),
+            ("5 = Hyper", "line 1: unknown key `Hyper`"),
+            ("[hotkeys]\nreset = F13", "line 2: unknown key `F13`"),
+            (
+                "[hotkeys]\nreset = Meta+R",
+                "line 2: unknown modifier `Meta`",
+            ),
+            (
+                "[hotkeys]\nquit = Q",
+                "line 2: unknown hotkey `quit` (expected one of: pause, reset, rewind, \
+                 screenshot, save_state, load_state)",
+            ),
+            // checked even when another ROM is running
+            ("[rom TETRIS]\n5 = Nope", "line 2: unknown key `Nope`"),
+        ] {
+            assert_eq!(parse(text).err().as_deref(), Some(error), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn keys_on_the_keypad_cannot_be_hotkeys() {
+        // Dvorak puts keypad key D on P
+        assert!(parse("preset = dvorak").is_ok());
+        assert_eq!(
+            parse("preset = dvorak\n[hotkeys]\npause = P")
+                .err()
+                .as_deref(),
+            Some("P is bound to keypad key D and to the pause hotkey; rebind one of them")
+        );
+        assert_eq!(
+            parse("1 = F1").err().as_deref(),
+            Some("F1 is bound to keypad key 1 and to the save_state hotkey; rebind one of them")
+        );
+        assert_eq!(
+            parse("[rom PONG]\n2 = Backspace").err().as_deref(),
+            Some("Backspace is bound to keypad key 2 and to the rewind hotkey; rebind one of them")
+        );
+        assert!(Config::parse("[rom PONG]\n2 = Backspace", "roms/TETRIS").is_ok());
+    }
+
+    #[test]
+    fn shipped_file_works_with_every_preset() {
+        let shipped = include_str!("../chip8.ini");
+        for preset in crate::keymap::PRESETS {
+            let text = shipped.replace("preset = qwerty", &format!("preset = {}", preset));
+            let config = parse(&text).unwrap_or_else(|error| panic!("{}: {}", preset, error));
+            assert_eq!(config.hotkeys.pause, [Chord::new(Key::F11)], "{}", preset);
+        }
+    }
+
+    #[test]
+    fn chords_need_exactly_their_modifiers() {
+        assert_eq!(Chord::parse("F1"), Ok(Chord::new(Key::F1)));
+        assert_eq!(Chord::parse("shift + f1"), Ok(Chord::shifted(Key::F1)));
+        assert_eq!(Chord::parse("Shift+"), Err("unknown key ``".to_string()));
+
+        let load = Chord::new(Key::F1);
+        let save = Chord::shifted(Key::F1);
+        let pressed = [Key::F1];
+        assert!(load.fired_by(&pressed, &[Key::F1]));
+        assert!(!save.fired_by(&pressed, &[Key::F1]));
+        assert!(!load.fired_by(&pressed, &[Key::F1, Key::RightShift]));
+        assert!(save.fired_by(&pressed, &[Key::F1, Key::RightShift]));
+        assert!(
+            !save.fired_by(&[], &[Key::F1, Key::LeftShift]),
+            "not pressed"
+        );
+
+        let chord = Chord::parse("Ctrl+Alt+R").unwrap();
+        assert!(chord.fired_by(&[Key::R], &[Key::R, Key::LeftCtrl, Key::RightAlt]));
+        assert!(!chord.fired_by(&[Key::R], &[Key::R, Key::LeftCtrl]));
+    }
+}
@@ -0,0 +1,282 @@
+use minifb::Key;
+
+// Which host keys press which CHIP-8 keys. The presets put the hex keypad on
+// the left of the keyboard, wherever those keys are printed on it; QWERTY is
+// the default:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+//
+// Any keypad key can be bound to several host keys (see config.rs for the
+// file format).
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    // (host key, keypad key)
+    bindings: Vec<(Key, u8)>,
+}
+
+pub const PRESETS: [&str; 3] = ["qwerty", "azerty", "dvorak"];
+
+// host keys for keypad keys 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F, row by row
+#[rustfmt::skip]
+const QWERTY: [Key; 16] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Q, Key::W, Key::E, Key::R,
+    Key::A, Key::S, Key::D, Key::F,
+    Key::Z, Key::X, Key::C, Key::V,
+];
+#[rustfmt::skip]
+const AZERTY: [Key; 16] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::A, Key::Z, Key::E, Key::R,
+    Key::Q, Key::S, Key::D, Key::F,
+    Key::W, Key::X, Key::C, Key::V,
+];
+#[rustfmt::skip]
+const DVORAK: [Key; 16] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Apostrophe, Key::Comma, Key::Period, Key::P,
+    Key::A, Key::O, Key::E, Key::U,
+    Key::Semicolon, Key::Q, Key::J, Key::K,
+];
+const PAD_ORDER: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_layout(&QWERTY)
+    }
+}
+
+impl Keymap {
+    // One of PRESETS, by name.
+    pub fn preset(name: &str) -> Option<Keymap> {
+        let layout = match name.to_ascii_lowercase().as_str() {
+            "qwerty" => &QWERTY,
+            "azerty" => &AZERTY,
+            "dvorak" => &DVORAK,
+            _ => return None,
+        };
+        Some(Keymap::from_layout(layout))
+    }
+
+    fn from_layout(layout: &[Key; 16]) -> Keymap {
+        Keymap {
+            bindings: layout.iter().copied().zip(PAD_ORDER).collect(),
+        }
+    }
+
+    // Replaces whatever `pad` was bound to with `keys`.
+    pub fn bind(&mut self, pad: u8, keys: &[Key]) {
+        self.bindings.retain(|(_, bound)| *bound != pad);
+        self.bindings.extend(keys.iter().map(|key| (*key, pad)));
+    }
+
+    // the keypad key `key` presses, if any
+    pub fn pad_for(&self, key: Key) -> Option<u8> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, pad)| *pad)
+    }
+
+    // the keypad keys held down, given the host keys that are
+    pub fn keypad(&self, host_keys: &[Key]) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (key, pad) in &self.bindings {
+            if host_keys.contains(key) {
+                keys[*pad as usize] = true;
+            }
+        }
+        keys
+    }
+}
+
+// A host key together with the modifiers that must be held with it, written
+// `Shift+F1`, `Ctrl+Alt+R` or just `P`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    key: Key,
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl Chord {
+    pub fn new(key: Key) -> Chord {
+        Chord {
+            key,
+            shift: false,
+            ctrl: false,
+            alt: false,
+        }
+    }
+
+    pub fn shifted(key: Key) -> Chord {
+        Chord {
+            shift: true,
+            ..Chord::new(key)
+        }
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn parse(text: &str) -> Result<Chord, String> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let name = parts.pop().unwrap_or_default();
+        let key = key_from_name(name).ok_or_else(|| format!("unknown key `{}`", name))?;
+        let mut chord = Chord::new(key);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "shift" => chord.shift = true,
+                "ctrl" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                _ => return Err(format!("unknown modifier `{}`", modifier)),
+            }
+        }
+        Ok(chord)
+    }
+
+    // `keys` are the host keys that triggered this update; `held` all of
+    // those down right now. Modifiers must match exactly, so `F1` and
+    // `Shift+F1` can do different things.
+    pub fn fired_by(&self, keys: &[Key], held: &[Key]) -> bool {
+        let down = |a: Key, b: Key| held.contains(&a) || held.contains(&b);
+        keys.contains(&self.key)
+            && down(Key::LeftShift, Key::RightShift) == self.shift
+            && down(Key::LeftCtrl, Key::RightCtrl) == self.ctrl
+            && down(Key::LeftAlt, Key::RightAlt) == self.alt
+    }
+}
+
+const NAMED_KEYS: [(&str, Key); 44] = [
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("space", Key::Space),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("backspace", Key::Backspace),
+    ("escape", Key::Escape),
+    ("insert", Key::Insert),
+    ("delete", Key::Delete),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("leftshift", Key::LeftShift),
+    ("rightshift", Key::RightShift),
+    ("leftctrl", Key::LeftCtrl),
+    ("rightctrl", Key::RightCtrl),
+    ("leftalt", Key::LeftAlt),
+    ("rightalt", Key::RightAlt),
+    ("apostrophe", Key::Apostrophe),
+    ("backquote", Key::Backquote),
+    ("backslash", Key::Backslash),
+    ("comma", Key::Comma),
+    ("equal", Key::Equal),
+    ("leftbracket", Key::LeftBracket),
+    ("minus", Key::Minus),
+    ("period", Key::Period),
+    ("rightbracket", Key::RightBracket),
+    ("semicolon", Key::Semicolon),
+    ("slash", Key::Slash),
+    ("numpad0", Key::NumPad0),
+    ("numpad1", Key::NumPad1),
+    ("numpad2", Key::NumPad2),
+    ("numpad3", Key::NumPad3),
+    ("numpad4", Key::NumPad4),
+    ("numpad5", Key::NumPad5),
+    ("numpad6", Key::NumPad6),
+    ("numpad7", Key::NumPad7),
+    ("numpad8", Key::NumPad8),
+    ("numpad9", Key::NumPad9),
+    ("numpadplus", Key::NumPadPlus),
+    ("numpadminus", Key::NumPadMinus),
+];
+
+const LETTERS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+const DIGITS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+// A host key by name: a letter, a digit, F1 to F12 or one of NAMED_KEYS, in
+// any case.
+pub fn key_from_name(name: &str) -> Option<Key> {
+    let lower = name.to_ascii_lowercase();
+    if let [c] = lower.as_bytes() {
+        return match c {
+            b'a'..=b'z' => Some(LETTERS[(c - b'a') as usize]),
+            b'0'..=b'9' => Some(DIGITS[(c - b'0') as usize]),
+            _ => None,
+        };
+    }
+    if let Some(n) = lower
+        .strip_prefix('f')
+        .and_then(|digits| digits.parse::<usize>().ok())
+    {
+        return FUNCTION_KEYS.get(n.wrapping_sub(1)).copied();
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|(named, _)| *named == lower)
+        .map(|(_, key)| *key)
+}
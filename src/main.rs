@@ -1,20 +1,362 @@
-use chip8::Chip8;
-use std::{env, fs::File, io::Read, thread, time::Duration};
+use chip8::{
+    audio::Beeper,
+    backend::{ScriptedInput, TerminalVideo, VideoSink},
+    debugger::{self, Debugger},
+    error::Chip8Error,
+    rewind::Rewind,
+    snapshot::Snapshot,
+    trace::Tracer,
+    Chip8,
+};
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    fs::{self, File},
+    hash::{BuildHasher, Hasher},
+    io::{self, BufWriter},
+    path::Path,
+    process, thread,
+    time::{Duration, Instant},
+};
 
-mod chip8;
+mod audio;
+mod cli;
+mod config;
+mod keymap;
+mod repl;
+mod window;
+
+use cli::Options;
+use config::Config;
+use window::Hotkey;
+
+type Trace = Option<Tracer<BufWriter<File>>>;
 
 fn main() {
-    // 1. Read ROM from arguments
-    let args: Vec<String> = env::args().collect();
-    let mut file = File::open(args.get(1).unwrap()).unwrap();
-    let mut data = Vec::<u8>::new();
-    file.read_to_end(&mut data).expect("File not found!");
+    // 1. Read ROM and options from arguments
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => fail(&format!("{}\n(run with --help for usage)", e)),
+    };
+    let data = fs::read(&options.rom_path)
+        .unwrap_or_else(|e| fail(&format!("could not read {}: {}", options.rom_path, e)));
 
-    // 2. Setup Chip8
+    // 2. Setup Chip8 with the requested machine settings
     let mut chip8: Chip8 = Chip8::new();
-    chip8.load_rom(&data);
-    loop {
-        chip8.execute_next_instruction();
-        thread::sleep(Duration::from_millis(5));
+    chip8.set_ips(options.ips);
+    chip8.set_quirks(options.quirks);
+    chip8.set_unknown_opcode_policy(options.unknown_policy);
+    // a fresh seed every run unless one was asked for; it is printed so a
+    // run that went wrong can be repeated. Golden screens are made and
+    // checked with the machine's own generator, as in the tests.
+    let golden = options.expect_screen.is_some() || options.save_screen.is_some();
+    match options.seed {
+        Some(seed) => chip8.seed_rng(seed),
+        None if golden => {}
+        None => {
+            let seed = random_seed();
+            eprintln!("seed {} (pass --seed {} to repeat this run)", seed, seed);
+            chip8.seed_rng(seed);
+        }
+    }
+    chip8
+        .load_rom(&data)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", options.rom_path, e)));
+    // the reset hotkey goes back to this
+    let boot = chip8.save_state();
+    if let Some(path) = &options.state_path {
+        let state =
+            fs::read(path).unwrap_or_else(|e| fail(&format!("could not read {}: {}", path, e)));
+        chip8
+            .load_state(&state)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+    let mut trace = options.trace_path.as_ref().map(|path| {
+        let file = File::create(path)
+            .unwrap_or_else(|e| fail(&format!("could not create {}: {}", path, e)));
+        let mut tracer = Tracer::new(BufWriter::new(file), options.trace_format);
+        tracer.set_pc_range(options.trace_pc.clone());
+        if let Some(limit) = options.trace_limit {
+            tracer.set_size_limit(limit);
+        }
+        tracer
+    });
+
+    // 3. Run it, in a window or as fast as possible without one
+    let failed = if options.headless {
+        run_headless(&mut chip8, &options, &mut trace)
+    } else {
+        let config = match &options.config_path {
+            Some(path) => Config::load(path, &options.rom_path),
+            None if Path::new(config::DEFAULT_PATH).exists() => {
+                Config::load(config::DEFAULT_PATH, &options.rom_path)
+            }
+            None => Ok(Config::default()),
+        };
+        let config = config.unwrap_or_else(|e| fail(&e));
+        let (video, input, hotkeys) = window::open(options.scale, options.palette, config);
+        chip8.set_video(Box::new(video));
+        chip8.set_input(Box::new(input));
+        if !options.mute {
+            let mut beeper = Beeper::default();
+            beeper.set_pitch(options.pitch);
+            beeper.set_volume(options.volume);
+            chip8.set_audio(Box::new(audio::PipeAudio::new(beeper)));
+        }
+        run_window(&mut chip8, &options, &mut trace, &hotkeys, &boot)
+    };
+
+    if let Some(tracer) = trace.as_mut() {
+        if let Err(e) = tracer.finish(&chip8) {
+            eprintln!("could not write the trace: {}", e);
+        }
+        if tracer.is_full() {
+            eprintln!("the trace was cut short by --trace-limit");
+        }
+    }
+    eprint!("{}", chip8.cpu().unknown_opcodes().summary());
+    if failed {
+        process::exit(1);
+    }
+}
+
+// Runs one emulated frame per 1/60 s of wall-clock time until the program
+// exits, the window is closed or the frame limit is reached. Returns true if
+// the program crashed.
+fn run_window(
+    chip8: &mut Chip8,
+    options: &Options,
+    trace: &mut Trace,
+    hotkeys: &window::Hotkeys,
+    boot: &[u8],
+) -> bool {
+    let frame_time = Duration::from_secs(1) / chip8::FRAME_RATE;
+    let mut next_frame = Instant::now();
+    let mut rewind = Rewind::new(options.rewind_depth, options.rewind_max_bytes);
+    let mut frames = 0;
+    let mut paused = false;
+    // with --debug, execution starts paused and is driven from stdin
+    let mut debugger = options.debug.then(|| {
+        let mut debugger = Debugger::new();
+        for addr in &options.breakpoints {
+            debugger.add_breakpoint(*addr);
+        }
+        let repl = repl::Repl::spawn();
+        repl.print(&debugger::registers(chip8));
+        repl.prompt();
+        (debugger, repl)
+    });
+    while (!chip8.is_halted() || debugger.is_some())
+        && hotkeys.is_open()
+        && options.frames.is_none_or(|limit| frames < limit)
+    {
+        if let Some((debugger, repl)) = debugger.as_mut() {
+            while let Some(line) = repl.try_line() {
+                let reply = debugger.execute(&line, chip8);
+                if debugger.should_quit() {
+                    return false;
+                }
+                repl.print(&reply);
+                repl.prompt();
+            }
+            if debugger.is_paused() {
+                hotkeys.pump();
+                next_frame = Instant::now() + frame_time;
+                thread::sleep(frame_time);
+                continue;
+            }
+        }
+
+        let mut rewinding = false;
+        for hotkey in hotkeys.poll() {
+            match hotkey {
+                Hotkey::Pause => {
+                    paused = !paused;
+                    eprintln!("{}", if paused { "paused" } else { "resumed" });
+                }
+                Hotkey::Reset => {
+                    // boot came from this very ROM, so this can't fail
+                    chip8.load_state(boot).unwrap();
+                    eprintln!("reset");
+                }
+                Hotkey::Rewind => rewinding = true,
+                Hotkey::Screenshot => {
+                    // next to the ROM, in the first free `ROM.N.png`
+                    if let Some(path) = (1..)
+                        .map(|n| format!("{}.{}.png", options.rom_path, n))
+                        .find(|path| !Path::new(path).exists())
+                    {
+                        save_screenshot(chip8, &path, options);
+                    }
+                }
+                Hotkey::SaveState(slot) => save_state(chip8, &options.rom_path, slot),
+                Hotkey::LoadState(slot) => load_state(chip8, &options.rom_path, slot),
+            }
+        }
+
+        // while rewinding, step back one frame per frame; once the buffer
+        // runs dry the game just carries on
+        if paused {
+            hotkeys.pump();
+            next_frame = Instant::now() + frame_time;
+            thread::sleep(frame_time);
+            continue;
+        }
+        if rewinding && rewind.rewind(chip8) {
+            chip8.present();
+        } else if let Some((debugger, repl)) = debugger.as_mut() {
+            if let Some(report) = debugger.run_frame(chip8) {
+                repl.print(&report);
+                repl.prompt();
+            }
+            rewind.push(chip8);
+        } else {
+            // without a debugger to drop into, a crash ends the program
+            if let Err(e) = run_frame(chip8, trace) {
+                eprintln!("error: {}\n{}", e, debugger::registers(chip8));
+                return true;
+            }
+            rewind.push(chip8);
+        }
+        report_unknown_opcodes(chip8);
+        frames += 1;
+        screenshot_at_frame(chip8, options, frames);
+
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            // fell behind (e.g. the window was dragged): don't try to catch up
+            next_frame = now;
+        }
+    }
+    false
+}
+
+// Runs frames back to back until the program exits or the frame limit is
+// reached, then prints the screen. Returns true if the program crashed or
+// the final screen doesn't match `--expect-screen`.
+fn run_headless(chip8: &mut Chip8, options: &Options, trace: &mut Trace) -> bool {
+    let expected = options.expect_screen.as_ref().map(|path| {
+        let text = fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(&format!("could not read {}: {}", path, e)));
+        Snapshot::from_text(&text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+    });
+    if !options.keys.is_empty() {
+        chip8.set_input(Box::new(ScriptedInput::new(options.keys.clone())));
+    }
+    let mut frames = 0;
+    let mut crashed = false;
+    while !chip8.is_halted() && options.frames.is_none_or(|limit| frames < limit) {
+        if let Err(e) = run_frame(chip8, trace) {
+            eprintln!("error: {}\n{}", e, debugger::registers(chip8));
+            crashed = true;
+            break;
+        }
+        report_unknown_opcodes(chip8);
+        frames += 1;
+        screenshot_at_frame(chip8, options, frames);
+    }
+    TerminalVideo::new(io::stdout()).present(chip8.display());
+
+    let screen = Snapshot::capture(chip8.display());
+    if let Some(path) = &options.save_screen {
+        fs::write(path, screen.to_text())
+            .unwrap_or_else(|e| fail(&format!("could not write {}: {}", path, e)));
+    }
+    match expected.map(|expected| screen.compare(&expected)) {
+        Some(Err(report)) => {
+            eprintln!("the screen does not match: {}", report);
+            true
+        }
+        _ => crashed,
+    }
+}
+
+// One frame, with every instruction traced when `--trace` asked for it. A
+// trace that can't be written is dropped rather than stopping the program.
+fn run_frame(chip8: &mut Chip8, trace: &mut Trace) -> Result<(), Chip8Error> {
+    let Some(tracer) = trace else {
+        return chip8.run_frame();
+    };
+    let mut failed = None;
+    chip8.run_frame_until(|chip8| {
+        if failed.is_none() {
+            failed = tracer.record(chip8).err();
+        }
+        false
+    })?;
+    if let Some(e) = failed {
+        eprintln!("could not write the trace: {}", e);
+        // it failed already; this only stops the recording of memory writes
+        let _ = tracer.finish(chip8);
+        *trace = None;
+    }
+    Ok(())
+}
+
+fn report_unknown_opcodes(chip8: &mut Chip8) {
+    for unknown in chip8.take_unknown_warnings() {
+        eprintln!(
+            "warning: unknown opcode {:04X} at {:#06X}",
+            unknown.opcode, unknown.pc
+        );
+    }
+}
+
+fn state_path(rom_path: &str, slot: usize) -> String {
+    format!("{}.state{}", rom_path, slot)
+}
+
+fn save_state(chip8: &Chip8, rom_path: &str, slot: usize) {
+    let path = state_path(rom_path, slot);
+    match fs::write(&path, chip8.save_state()) {
+        Ok(()) => eprintln!("saved state to {}", path),
+        Err(e) => eprintln!("could not save state to {}: {}", path, e),
+    }
+}
+
+fn load_state(chip8: &mut Chip8, rom_path: &str, slot: usize) {
+    let path = state_path(rom_path, slot);
+    let result = fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| chip8.load_state(&data).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => eprintln!("loaded state from {}", path),
+        Err(e) => eprintln!("could not load state from {}: {}", path, e),
+    }
+}
+
+// `--screenshot-at-frame`: saves `ROM.frameN.png` once frame N has run.
+fn screenshot_at_frame(chip8: &Chip8, options: &Options, frames: u64) {
+    if options.screenshot_frames.contains(&frames) {
+        let path = format!("{}.frame{}.png", options.rom_path, frames);
+        save_screenshot(chip8, &path, options);
     }
 }
+
+// Writes the screen to `path` as a PNG in the palette of the window.
+fn save_screenshot(chip8: &Chip8, path: &str, options: &Options) {
+    let image =
+        Snapshot::capture(chip8.display()).to_png(options.palette, options.screenshot_scale);
+    match fs::write(path, image) {
+        Ok(()) => eprintln!("saved screenshot to {}", path),
+        Err(e) => eprintln!("could not save screenshot to {}: {}", path, e),
+    }
+}
+
+// std seeds its HashMap hashers from the OS, which is all the randomness a
+// seed needs.
+fn random_seed() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(2);
+}
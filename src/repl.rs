@@ -0,0 +1,46 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+// Debugger command lines read from stdin on a background thread, so the
+// emulation loop can keep the window alive while waiting for input.
+pub struct Repl {
+    lines: Receiver<String>,
+}
+
+impl Repl {
+    pub fn spawn() -> Repl {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Repl { lines }
+    }
+
+    // Next line typed, if any. A closed stdin reads as `quit`.
+    pub fn try_line(&self) -> Option<String> {
+        match self.lines.try_recv() {
+            Ok(line) => Some(line),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some("quit".to_string()),
+        }
+    }
+
+    pub fn print(&self, reply: &str) {
+        if !reply.is_empty() {
+            println!("{}", reply);
+        }
+    }
+
+    pub fn prompt(&self) {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+    }
+}
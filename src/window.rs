@@ -0,0 +1,162 @@
+extern crate minifb;
+
+use std::{cell::RefCell, rc::Rc};
+
+use chip8::backend::{InputSource, VideoSink};
+use chip8::display::{Display, HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::config::{Config, HotkeyMap};
+use crate::keymap::{Chord, Keymap};
+
+// The minifb window is both a video sink and an input source, so the two
+// backends share it.
+pub struct MinifbVideo {
+    window: Rc<RefCell<Window>>,
+    buffer: Vec<u32>,
+    // colours of the four pixel values, background first
+    palette: [u32; 4],
+}
+
+pub struct MinifbInput {
+    window: Rc<RefCell<Window>>,
+    keymap: Keymap,
+}
+
+// Frontend shortcuts that act on the emulator rather than the CHIP-8 keypad.
+pub struct Hotkeys {
+    window: Rc<RefCell<Window>>,
+    bindings: HotkeyMap,
+}
+
+pub enum Hotkey {
+    Pause,
+    Reset,
+    // held down to run time backwards
+    Rewind,
+    Screenshot,
+    SaveState(usize),
+    LoadState(usize),
+}
+
+// Opens a window `scale` times the size of the 64x32 display; the 128x64
+// high-resolution mode is drawn at half that scale in the same window.
+pub fn open(
+    scale: usize,
+    palette: [u32; 4],
+    config: Config,
+) -> (MinifbVideo, MinifbInput, Hotkeys) {
+    let window = Window::new(
+        "Rusty CHIP-8 🦀",
+        WIDTH * scale,
+        HEIGHT * scale,
+        WindowOptions::default(),
+    )
+    .unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let window = Rc::new(RefCell::new(window));
+
+    (
+        MinifbVideo {
+            window: Rc::clone(&window),
+            buffer: Vec::with_capacity(HIRES_WIDTH * HIRES_HEIGHT),
+            palette,
+        },
+        MinifbInput {
+            window: Rc::clone(&window),
+            keymap: config.keymap,
+        },
+        Hotkeys {
+            window,
+            bindings: config.hotkeys,
+        },
+    )
+}
+
+impl MinifbVideo {
+    fn build_buffer(&mut self, display: &Display) {
+        self.buffer.clear();
+        for row in display.rows() {
+            for pixel in row {
+                self.buffer.push(self.palette[*pixel as usize]);
+            }
+        }
+    }
+}
+
+impl VideoSink for MinifbVideo {
+    fn present(&mut self, display: &Display) {
+        self.build_buffer(display);
+        let mut window = self.window.borrow_mut();
+        if window.is_open() {
+            window
+                .update_with_buffer(&self.buffer, display.width(), display.height())
+                .unwrap();
+        }
+    }
+}
+
+impl InputSource for MinifbInput {
+    fn poll(&mut self) -> [bool; 16] {
+        let mut window = self.window.borrow_mut();
+        if !window.is_open() {
+            return [false; 16];
+        }
+        // pump window events so key state stays fresh between redraws
+        window.update();
+
+        // keys held down, not key-press events: a held key stays down for
+        // every frame it is held
+        self.keymap.keypad(&window.get_keys())
+    }
+}
+
+impl Hotkeys {
+    // Keeps the window responsive while the emulator isn't running frames
+    // (e.g. paused in the debugger).
+    pub fn pump(&self) {
+        let mut window = self.window.borrow_mut();
+        if window.is_open() {
+            window.update();
+        }
+    }
+
+    // false once the user has closed the window
+    pub fn is_open(&self) -> bool {
+        self.window.borrow().is_open()
+    }
+
+    pub fn poll(&self) -> Vec<Hotkey> {
+        let window = self.window.borrow();
+        let held = window.get_keys();
+        let pressed = window.get_keys_pressed(KeyRepeat::No);
+        let fired =
+            |chords: &[Chord], keys: &[Key]| chords.iter().any(|chord| chord.fired_by(keys, &held));
+
+        let mut hotkeys = Vec::new();
+        if fired(&self.bindings.pause, &pressed) {
+            hotkeys.push(Hotkey::Pause);
+        }
+        if fired(&self.bindings.reset, &pressed) {
+            hotkeys.push(Hotkey::Reset);
+        }
+        if fired(&self.bindings.rewind, &held) {
+            hotkeys.push(Hotkey::Rewind);
+        }
+        if fired(&self.bindings.screenshot, &pressed) {
+            hotkeys.push(Hotkey::Screenshot);
+        }
+        for (idx, chord) in self.bindings.save_state.iter().enumerate() {
+            if chord.fired_by(&pressed, &held) {
+                hotkeys.push(Hotkey::SaveState(idx + 1));
+            }
+        }
+        for (idx, chord) in self.bindings.load_state.iter().enumerate() {
+            if chord.fired_by(&pressed, &held) {
+                hotkeys.push(Hotkey::LoadState(idx + 1));
+            }
+        }
+        hotkeys
+    }
+}